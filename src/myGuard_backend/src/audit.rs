@@ -0,0 +1,245 @@
+use std::borrow::Cow;
+
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::{Memory, StableBTreeMap, Storable};
+use serde::Serialize;
+
+// Oldest events are dropped beyond this many
+pub const MAX_AUDIT_EVENTS: u64 = 100_000;
+
+// Events older than this are dropped (one year)
+pub const AUDIT_RETENTION_NS: u64 = 365 * 24 * 60 * 60 * 1_000_000_000;
+
+// Longest payload summary that is kept
+pub const MAX_SUMMARY_LENGTH: usize = 500;
+
+pub const DEFAULT_PAGE_SIZE: u32 = 50;
+pub const MAX_PAGE_SIZE: u32 = 200;
+
+// Events examined per call, matching or not, so a selective filter over a long
+// log cannot exhaust the instruction limit. The page then ends early with a
+// cursor to continue from.
+pub const MAX_SCANNED_EVENTS: usize = 5_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum AuditEventKind {
+    AnalysisRun,
+    FeedbackSubmitted,
+    FeedbackReviewed,
+    DatasetImported,
+    DatasetRolledBack,
+    ReviewerAdded,
+    ReviewerRemoved,
+    CacheCleared,
+    CircuitBreakerReset,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub event_id: u64,
+    pub kind: AuditEventKind,
+    // Caller of the operation
+    pub principal: Principal,
+    // Nanoseconds since the Unix epoch
+    pub timestamp: u64,
+    // Short description of what changed; never contains contract text
+    pub summary: String,
+}
+
+impl Storable for AuditEvent {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).expect("failed to encode audit event"))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).expect("failed to decode audit event")
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// All given conditions must hold; time bounds are inclusive
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct AuditFilter {
+    pub kind: Option<AuditEventKind>,
+    pub principal: Option<Principal>,
+    pub from_timestamp: Option<u64>,
+    pub to_timestamp: Option<u64>,
+}
+
+impl AuditFilter {
+    fn matches(&self, event: &AuditEvent) -> bool {
+        self.kind.is_none_or(|kind| kind == event.kind)
+            && self.principal.is_none_or(|principal| principal == event.principal)
+            && self.from_timestamp.is_none_or(|from| event.timestamp >= from)
+            && self.to_timestamp.is_none_or(|to| event.timestamp <= to)
+    }
+}
+
+// Newest events first. Pass `next_cursor` back to get the following page; it is
+// set whenever older events remain to be examined, so a page can hold fewer
+// events than asked for (even none) and still have a continuation.
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct AuditPage {
+    pub events: Vec<AuditEvent>,
+    pub next_cursor: Option<u64>,
+    // Events currently retained, matching or not
+    pub retained_events: u64,
+}
+
+// Append an event and apply the retention limits. Existing events are never changed.
+pub fn append<M: Memory>(
+    log: &mut StableBTreeMap<u64, AuditEvent, M>,
+    kind: AuditEventKind,
+    principal: Principal,
+    timestamp: u64,
+    summary: &str,
+) -> u64 {
+    let event_id = log.last_key_value().map_or(1, |(id, _)| id + 1);
+    let summary = match summary.char_indices().nth(MAX_SUMMARY_LENGTH) {
+        Some((end, _)) => format!("{}…", &summary[..end]),
+        None => summary.to_string(),
+    };
+    log.insert(event_id, AuditEvent {
+        event_id,
+        kind,
+        principal,
+        timestamp,
+        summary,
+    });
+
+    let oldest_kept = timestamp.saturating_sub(AUDIT_RETENTION_NS);
+    while let Some((_, oldest)) = log.first_key_value() {
+        if log.len() <= MAX_AUDIT_EVENTS && oldest.timestamp >= oldest_kept {
+            break;
+        }
+        log.pop_first();
+    }
+    event_id
+}
+
+// Up to `limit` matching events with ids below `cursor`, newest first, looking
+// at no more than `MAX_SCANNED_EVENTS` events
+pub fn page<M: Memory>(
+    log: &StableBTreeMap<u64, AuditEvent, M>,
+    filter: &AuditFilter,
+    cursor: Option<u64>,
+    limit: Option<u32>,
+) -> AuditPage {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE) as usize;
+    let mut events = Vec::new();
+    let mut next_cursor = None;
+    for (scanned, (event_id, event)) in log.range(..cursor.unwrap_or(u64::MAX)).rev().enumerate() {
+        // Ids follow time, so nothing older can match
+        if filter.from_timestamp.is_some_and(|from| event.timestamp < from) {
+            break;
+        }
+        if scanned == MAX_SCANNED_EVENTS || (events.len() == limit && filter.matches(&event)) {
+            next_cursor = Some(event_id + 1);
+            break;
+        }
+        if filter.matches(&event) {
+            events.push(event);
+        }
+    }
+    AuditPage {
+        events,
+        next_cursor,
+        retained_events: log.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_stable_structures::DefaultMemoryImpl;
+
+    type Log = StableBTreeMap<u64, AuditEvent, DefaultMemoryImpl>;
+
+    // `count` events one second apart; every `every`th one is a cache clear
+    fn log_with(count: u64, every: u64) -> Log {
+        let mut log = Log::init(DefaultMemoryImpl::default());
+        for n in 1..=count {
+            let kind = if n % every == 0 { AuditEventKind::CacheCleared } else { AuditEventKind::AnalysisRun };
+            append(&mut log, kind, Principal::anonymous(), n * 1_000_000_000, "event");
+        }
+        log
+    }
+
+    fn ids(page: &AuditPage) -> Vec<u64> {
+        page.events.iter().map(|event| event.event_id).collect()
+    }
+
+    #[test]
+    fn pages_continue_where_the_last_one_ended() {
+        let log = log_with(5, 1);
+        let filter = AuditFilter::default();
+        let first = page(&log, &filter, None, Some(2));
+        assert_eq!(ids(&first), vec![5, 4]);
+        let second = page(&log, &filter, first.next_cursor, Some(2));
+        assert_eq!(ids(&second), vec![3, 2]);
+        let last = page(&log, &filter, second.next_cursor, Some(2));
+        assert_eq!(ids(&last), vec![1]);
+        assert_eq!(last.next_cursor, None);
+    }
+
+    #[test]
+    fn selective_filters_stop_after_the_scan_limit() {
+        let total = 2 * MAX_SCANNED_EVENTS as u64 + 10;
+        let log = log_with(total, 1_000_000);
+        let filter = AuditFilter {
+            kind: Some(AuditEventKind::CacheCleared),
+            ..Default::default()
+        };
+
+        let mut cursor = None;
+        let mut calls = 0;
+        loop {
+            let result = page(&log, &filter, cursor, None);
+            calls += 1;
+            assert!(result.events.is_empty());
+            match result.next_cursor {
+                Some(next) => {
+                    assert!(cursor.is_none_or(|previous| next < previous));
+                    cursor = Some(next);
+                }
+                None => break,
+            }
+        }
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn matching_events_are_not_skipped_between_pages() {
+        let log = log_with(3 * MAX_SCANNED_EVENTS as u64, 7);
+        let filter = AuditFilter {
+            kind: Some(AuditEventKind::CacheCleared),
+            ..Default::default()
+        };
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let result = page(&log, &filter, cursor, Some(MAX_PAGE_SIZE));
+            seen.extend(ids(&result));
+            match result.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        let expected: Vec<u64> = (1..=3 * MAX_SCANNED_EVENTS as u64).rev().filter(|id| id % 7 == 0).collect();
+        assert_eq!(seen, expected);
+    }
+
+    #[test]
+    fn time_filters_stop_at_older_events() {
+        let log = log_with(10, 1);
+        let filter = AuditFilter {
+            from_timestamp: Some(8_000_000_000),
+            ..Default::default()
+        };
+        let result = page(&log, &filter, None, None);
+        assert_eq!(ids(&result), vec![10, 9, 8]);
+        assert_eq!(result.next_cursor, None);
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::BTreeMap;
+
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+
+use crate::contract_type::ContractType;
+use crate::dataset;
+
+// Memory the cached classifications may use before the least recently used are evicted
+pub const CACHE_MEMORY_BUDGET_BYTES: usize = 8 * 1024 * 1024;
+
+// Rough per-entry bookkeeping cost on top of the stored strings
+const ENTRY_OVERHEAD_BYTES: usize = 128;
+
+// Everything that determines a classification: which analyzer and model ran,
+// under which rules and contract type profile, on which (normalised) wording.
+// The wording itself is part of the key rather than a hash of it, so a clause
+// crafted to collide with another cannot be served the other's verdict.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CacheKey {
+    analyzer: &'static str,
+    model: &'static str,
+    rule_set_version: u32,
+    contract_type: &'static str,
+    clause: String,
+}
+
+impl CacheKey {
+    pub fn new(analyzer: &'static str, model: &'static str, rule_set_version: u32, contract_type: ContractType, clause: &str) -> Self {
+        CacheKey {
+            analyzer,
+            model,
+            rule_set_version,
+            contract_type: contract_type.name(),
+            clause: dataset::normalize_text(clause),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CachedClassification {
+    pub label: String,
+    pub reason: String,
+    pub confidence: f64,
+}
+
+struct CacheEntry {
+    value: CachedClassification,
+    last_used: u64,
+    size: usize,
+}
+
+#[derive(Clone, Debug, Default, CandidType, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub memory_bytes: usize,
+    pub memory_budget_bytes: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_rate: f64,
+    pub insertions: u64,
+    pub evictions: u64,
+    // Times the whole cache was dropped because the dataset or rules changed
+    pub invalidations: u64,
+}
+
+// Content-addressed classification cache with least-recently-used eviction
+pub struct ClassificationCache {
+    entries: BTreeMap<CacheKey, CacheEntry>,
+    // Access tick -> key, oldest first
+    recency: BTreeMap<u64, CacheKey>,
+    tick: u64,
+    memory_bytes: usize,
+    stats: CacheStats,
+}
+
+impl ClassificationCache {
+    pub const fn new() -> Self {
+        ClassificationCache {
+            entries: BTreeMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            memory_bytes: 0,
+            stats: CacheStats {
+                entries: 0,
+                memory_bytes: 0,
+                memory_budget_bytes: CACHE_MEMORY_BUDGET_BYTES,
+                hits: 0,
+                misses: 0,
+                hit_rate: 0.0,
+                insertions: 0,
+                evictions: 0,
+                invalidations: 0,
+            },
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    pub fn get(&mut self, key: &CacheKey) -> Option<CachedClassification> {
+        let tick = self.next_tick();
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                self.recency.remove(&entry.last_used);
+                self.recency.insert(tick, key.clone());
+                entry.last_used = tick;
+                self.stats.hits += 1;
+                Some(entry.value.clone())
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, key: CacheKey, value: CachedClassification) {
+        // The wording is held twice, in the entry and in the recency index
+        let size = ENTRY_OVERHEAD_BYTES + 2 * key.clause.len() + value.label.len() + value.reason.len();
+        if size > CACHE_MEMORY_BUDGET_BYTES {
+            return;
+        }
+        if let Some(previous) = self.entries.remove(&key) {
+            self.recency.remove(&previous.last_used);
+            self.memory_bytes -= previous.size;
+        }
+
+        while self.memory_bytes + size > CACHE_MEMORY_BUDGET_BYTES {
+            let Some((_, oldest)) = self.recency.pop_first() else { break };
+            if let Some(evicted) = self.entries.remove(&oldest) {
+                self.memory_bytes -= evicted.size;
+                self.stats.evictions += 1;
+            }
+        }
+
+        let tick = self.next_tick();
+        self.recency.insert(tick, key.clone());
+        self.entries.insert(key, CacheEntry { value, last_used: tick, size });
+        self.memory_bytes += size;
+        self.stats.insertions += 1;
+    }
+
+    // Drop every entry, e.g. after the dataset changed
+    pub fn invalidate(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+        self.memory_bytes = 0;
+        self.stats.invalidations += 1;
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let lookups = self.stats.hits + self.stats.misses;
+        CacheStats {
+            entries: self.entries.len(),
+            memory_bytes: self.memory_bytes,
+            hit_rate: if lookups > 0 { self.stats.hits as f64 / lookups as f64 } else { 0.0 },
+            ..self.stats.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(clause: &str) -> CacheKey {
+        CacheKey::new("llm", "model", 1, ContractType::Lease, clause)
+    }
+
+    fn verdict(label: &str) -> CachedClassification {
+        CachedClassification {
+            label: label.to_string(),
+            reason: "reason".to_string(),
+            confidence: 0.9,
+        }
+    }
+
+    #[test]
+    fn entries_are_found_by_normalised_wording() {
+        let mut cache = ClassificationCache::new();
+        cache.insert(key("The tenant pays  the rent."), verdict("Allowed"));
+        assert_eq!(cache.get(&key("the TENANT pays the rent.")).map(|v| v.label), Some("Allowed".to_string()));
+        assert!(cache.get(&key("The tenant pays the rent twice.")).is_none());
+        assert!(cache.get(&CacheKey::new("llm", "model", 2, ContractType::Lease, "The tenant pays the rent.")).is_none());
+    }
+
+    #[test]
+    fn memory_budget_counts_the_wording() {
+        let mut cache = ClassificationCache::new();
+        let clause = "x".repeat(1000);
+        cache.insert(key(&clause), verdict("Allowed"));
+        assert!(cache.stats().memory_bytes >= 2000);
+    }
+}
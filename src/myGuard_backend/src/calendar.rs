@@ -0,0 +1,285 @@
+use crate::obligations::{Obligation, Recurrence};
+
+pub const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+// Longest iCalendar content line, in bytes, before it is folded
+const MAX_LINE_BYTES: usize = 75;
+
+// UTC day (days since the Unix epoch) of a timestamp in nanoseconds
+pub fn day_of(timestamp: u64) -> i64 {
+    (timestamp / NANOS_PER_DAY) as i64
+}
+
+// Civil date of a day count (Howard Hinnant's algorithm)
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month as u32, day as u32)
+}
+
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+pub fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// The same day `months` later, moved back to the month's last day if needed
+pub fn add_months(days: i64, months: i64) -> i64 {
+    let (year, month, day) = civil_from_days(days);
+    let index = year * 12 + month as i64 - 1 + months;
+    let (year, month) = (index.div_euclid(12), index.rem_euclid(12) as u32 + 1);
+    days_from_civil(year, month, day.min(days_in_month(year, month)))
+}
+
+// YYYY-MM-DD
+pub fn iso_date(days: i64) -> String {
+    let (year, month, day) = civil_from_days(days);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+pub fn parse_iso_date(text: &str) -> Option<i64> {
+    let mut parts = text.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: u32 = parts.next()?.parse().ok()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+    Some(days_from_civil(year, month, day))
+}
+
+fn ics_date(days: i64) -> String {
+    iso_date(days).replace('-', "")
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+// Content lines longer than 75 bytes continue on lines starting with a space
+fn push_line(out: &mut String, line: &str) {
+    let mut rest = line;
+    let mut limit = MAX_LINE_BYTES;
+    while rest.len() > limit {
+        let mut split = limit;
+        while !rest.is_char_boundary(split) {
+            split -= 1;
+        }
+        out.push_str(&rest[..split]);
+        out.push_str("\r\n ");
+        rest = &rest[split..];
+        limit = MAX_LINE_BYTES - 1;
+    }
+    out.push_str(rest);
+    out.push_str("\r\n");
+}
+
+fn rrule(recurrence: Recurrence, until: Option<i64>) -> String {
+    let mut rule = match recurrence {
+        Recurrence::Weekly => "FREQ=WEEKLY".to_string(),
+        Recurrence::Monthly => "FREQ=MONTHLY".to_string(),
+        Recurrence::Quarterly => "FREQ=MONTHLY;INTERVAL=3".to_string(),
+        Recurrence::Annually => "FREQ=YEARLY".to_string(),
+    };
+    if let Some(until) = until {
+        rule.push_str(&format!(";UNTIL={}", ics_date(until)));
+    }
+    rule
+}
+
+// iCalendar (RFC 5545) file with an all-day event for every obligation whose
+// due date is known. `analyses` pairs analysis ids with their obligations.
+pub fn render_ics(analyses: &[(u64, &[Obligation])], generated_at: u64) -> String {
+    let mut out = String::new();
+    let (year, month, day) = civil_from_days(day_of(generated_at));
+    let seconds_of_day = generated_at / 1_000_000_000 % 86_400;
+    let stamp = format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3_600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60
+    );
+
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(&mut out, "PRODID:-//MyGuard//Contract Obligations//EN");
+    push_line(&mut out, "CALSCALE:GREGORIAN");
+    push_line(&mut out, "X-WR-CALNAME:MyGuard contract obligations");
+
+    for (analysis_id, obligations) in analyses {
+        for (index, obligation) in obligations.iter().enumerate() {
+            let Some(deadline) = &obligation.deadline else { continue };
+            let Some(due) = deadline.due_date.as_deref().and_then(parse_iso_date) else { continue };
+
+            let mut description = format!("{}\nDeadline: {}", obligation.clause, deadline.text);
+            match (&obligation.section, obligation.page) {
+                (Some(section), Some(page)) => description.push_str(&format!("\nSection {}, page {}", section, page)),
+                (Some(section), None) => description.push_str(&format!("\nSection {}", section)),
+                (None, Some(page)) => description.push_str(&format!("\nPage {}", page)),
+                (None, None) => {}
+            }
+            description.push_str(&format!("\nMyGuard analysis #{}", analysis_id));
+
+            push_line(&mut out, "BEGIN:VEVENT");
+            push_line(&mut out, &format!("UID:analysis-{}-obligation-{}@myguard", analysis_id, index + 1));
+            push_line(&mut out, &format!("DTSTAMP:{}", stamp));
+            push_line(&mut out, &format!("DTSTART;VALUE=DATE:{}", ics_date(due)));
+            push_line(&mut out, &format!("DTEND;VALUE=DATE:{}", ics_date(due + 1)));
+            if let Some(recurrence) = deadline.recurrence {
+                let until = deadline.until.as_deref().and_then(parse_iso_date);
+                push_line(&mut out, &format!("RRULE:{}", rrule(recurrence, until)));
+            }
+            push_line(&mut out, &format!("SUMMARY:{}", escape_text(&format!("{}: {}", obligation.obligor, obligation.action))));
+            push_line(&mut out, &format!("DESCRIPTION:{}", escape_text(&description)));
+            push_line(&mut out, "TRANSP:TRANSPARENT");
+            push_line(&mut out, "END:VEVENT");
+        }
+    }
+
+    push_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obligations::Deadline;
+
+    fn day(iso: &str) -> i64 {
+        parse_iso_date(iso).unwrap()
+    }
+
+    #[test]
+    fn converts_between_days_and_civil_dates() {
+        for (days, date) in [
+            (0, (1970, 1, 1)),
+            (-1, (1969, 12, 31)),
+            (59, (1970, 3, 1)),
+            (10_956, (1999, 12, 31)),
+            (11_016, (2000, 2, 29)),
+            (19_782, (2024, 2, 29)),
+            (20_089, (2025, 1, 1)),
+            (-719_468, (0, 3, 1)),
+        ] {
+            assert_eq!(civil_from_days(days), date, "day {}", days);
+            assert_eq!(days_from_civil(date.0, date.1, date.2), days, "{:?}", date);
+        }
+        for days in -800_000..800_000 {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn adds_months_clamping_to_the_end_of_the_month() {
+        for (from, months, expected) in [
+            ("2025-01-31", 1, "2025-02-28"),
+            ("2024-01-31", 1, "2024-02-29"),
+            ("2024-02-29", 12, "2025-02-28"),
+            ("2024-02-29", 48, "2028-02-29"),
+            ("2025-03-31", -1, "2025-02-28"),
+            ("2025-08-31", 3, "2025-11-30"),
+            ("2025-11-15", 2, "2026-01-15"),
+            ("2025-01-15", -13, "2023-12-15"),
+        ] {
+            assert_eq!(iso_date(add_months(day(from), months)), expected, "{} + {} months", from, months);
+        }
+    }
+
+    #[test]
+    fn parses_only_real_dates() {
+        assert_eq!(parse_iso_date("2024-02-29").map(iso_date).as_deref(), Some("2024-02-29"));
+        for text in ["2025-02-29", "2025-13-01", "2025-00-10", "2025-04-31", "2025-4", "25-x-01", ""] {
+            assert_eq!(parse_iso_date(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn folds_long_lines_at_75_bytes() {
+        let mut out = String::new();
+        let line = format!("DESCRIPTION:{}", "é".repeat(80));
+        push_line(&mut out, &line);
+        let physical: Vec<&str> = out.split("\r\n").filter(|l| !l.is_empty()).collect();
+        assert!(physical.len() > 2);
+        assert!(physical.iter().all(|l| l.len() <= MAX_LINE_BYTES));
+        assert!(physical[1..].iter().all(|l| l.starts_with(' ')));
+        let unfolded: String = physical.iter().enumerate()
+            .map(|(index, l)| if index == 0 { *l } else { &l[1..] })
+            .collect();
+        assert_eq!(unfolded, line);
+
+        let mut short = String::new();
+        push_line(&mut short, &"X".repeat(MAX_LINE_BYTES));
+        assert_eq!(short, format!("{}\r\n", "X".repeat(MAX_LINE_BYTES)));
+    }
+
+    #[test]
+    fn escapes_text_values() {
+        assert_eq!(escape_text("a\\b; c, d\ne"), r"a\\b\; c\, d\ne");
+    }
+
+    #[test]
+    fn renders_recurring_events() {
+        let obligation = Obligation {
+            obligor: "Tenant".to_string(),
+            action: "pay the rent, in full".to_string(),
+            deadline: Some(Deadline {
+                text: "by the 5th of each month".to_string(),
+                due_date: Some("2025-01-05".to_string()),
+                recurrence: Some(Recurrence::Monthly),
+                until: Some("2025-12-31".to_string()),
+            }),
+            section: Some("4.2".to_string()),
+            page: Some(3),
+            clause: "The Tenant shall pay the rent, in full, by the 5th of each month.".to_string(),
+        };
+        let undated = Obligation {
+            deadline: None,
+            ..obligation.clone()
+        };
+        let obligations = [obligation, undated];
+        let ics = render_ics(&[(7, &obligations)], 20_089 * NANOS_PER_DAY + 3_723_000_000_000);
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 1);
+        for line in [
+            "UID:analysis-7-obligation-1@myguard",
+            "DTSTAMP:20250101T010203Z",
+            "DTSTART;VALUE=DATE:20250105",
+            "DTEND;VALUE=DATE:20250106",
+            "RRULE:FREQ=MONTHLY;UNTIL=20251231",
+            "SUMMARY:Tenant: pay the rent\\, in full",
+        ] {
+            assert!(ics.contains(&format!("\r\n{}\r\n", line)), "missing {}", line);
+        }
+        assert!(ics.replace("\r\n ", "").contains("\\nSection 4.2\\, page 3\\nMyGuard analysis #7"));
+        assert_eq!(rrule(Recurrence::Quarterly, None), "FREQ=MONTHLY;INTERVAL=3");
+    }
+}
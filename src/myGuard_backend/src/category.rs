@@ -0,0 +1,64 @@
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+
+// Subject-matter category of a clause, used to compare like with like
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, CandidType, Serialize, Deserialize)]
+pub enum ClauseCategory {
+    Payment,
+    Compensation,
+    Deposit,
+    Termination,
+    Liability,
+    Access,
+    Privacy,
+    Maintenance,
+    WorkingConditions,
+    Leave,
+    Confidentiality,
+    NonCompete,
+    Delivery,
+    Quality,
+    DisputeResolution,
+    Modification,
+    Other,
+}
+
+// Keywords that point to each category; the category with most hits wins
+const CATEGORY_KEYWORDS: &[(ClauseCategory, &[&str])] = &[
+    (ClauseCategory::Deposit, &["deposit"]),
+    (ClauseCategory::Payment, &["rent", "payment", "fee", "price", "invoice", "pay ", "paid", "refund"]),
+    (ClauseCategory::Compensation, &["salary", "wage", "bonus", "pension", "overtime pay", "benefit", "stipend", "reimburse", "allowance", "severance", "stock"]),
+    (ClauseCategory::Termination, &["terminat", "dismiss", "evict", "resign", "renew", "expire", "notice period"]),
+    (ClauseCategory::Liability, &["liable", "liability", "responsible for", "indemn", "damages", "injur", "insurance"]),
+    (ClauseCategory::Access, &["enter", "access", "guests", "inspect", "premises"]),
+    (ClauseCategory::Privacy, &["privacy", "personal", "record", "social media", "data protection", "monitor"]),
+    (ClauseCategory::Maintenance, &["repair", "maintain", "maintenance", "pest", "repaint", "utility", "utilities"]),
+    (ClauseCategory::WorkingConditions, &["working hours", "work ", "shift", "break", "rest day", "overtime", "relocate", "remote", "flexible", "safety", "job description", "discriminat"]),
+    (ClauseCategory::Leave, &["leave", "vacation", "holiday", "sick"]),
+    (ClauseCategory::Confidentiality, &["confidential", "disclos", "trade secret"]),
+    (ClauseCategory::NonCompete, &["compet", "non-compete", "exclusiv", "solicit"]),
+    (ClauseCategory::Delivery, &["deliver", "shipment", "shipping", "timeline", "deadline", "milestone"]),
+    (ClauseCategory::Quality, &["quality", "defect", "warrant", "guarantee"]),
+    (ClauseCategory::DisputeResolution, &["dispute", "arbitration", "court", "grievance", "complaint", "contest"]),
+    (ClauseCategory::Modification, &["modif", "amend", "change", "increase", "revoke", "one side"]),
+];
+
+pub fn categorize_clause(clause: &str) -> ClauseCategory {
+    let lower_clause = clause.to_lowercase();
+
+    let mut best_category = ClauseCategory::Other;
+    let mut best_hits = 0;
+
+    for (category, keywords) in CATEGORY_KEYWORDS {
+        let hits = keywords.iter()
+            .filter(|keyword| lower_clause.contains(*keyword))
+            .count();
+        // Earlier entries win ties, so more specific categories are listed first
+        if hits > best_hits {
+            best_hits = hits;
+            best_category = *category;
+        }
+    }
+
+    best_category
+}
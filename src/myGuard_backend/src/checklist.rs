@@ -0,0 +1,257 @@
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+
+use crate::contract_type::ContractType;
+use crate::Severity;
+
+// A provision a well-drafted contract of a given type is expected to contain
+pub struct ExpectedProvision {
+    pub id: &'static str,
+    pub title: &'static str,
+    // The provision is present when one clause contains every term of any group
+    pub evidence: &'static [&'static [&'static str]],
+    pub severity: Severity,
+    pub standard_wording: &'static str,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct MissingClause {
+    pub provision_id: String,
+    pub title: String,
+    pub severity: Severity,
+    pub suggested_wording: String,
+}
+
+// Provisions expected in every contract regardless of type
+const COMMON_PROVISIONS: &[ExpectedProvision] = &[
+    ExpectedProvision {
+        id: "dispute_resolution",
+        title: "Dispute resolution",
+        evidence: &[&["dispute"], &["arbitration"], &["mediation"], &["court"]],
+        severity: Severity::Medium,
+        standard_wording: "Any dispute arising out of this agreement shall first be settled amicably; failing settlement within 30 days, it shall be referred to the competent courts of Rwanda or to arbitration at the Kigali International Arbitration Centre.",
+    },
+    ExpectedProvision {
+        id: "governing_law",
+        title: "Governing law",
+        evidence: &[&["governed by"], &["laws of"], &["governing law"]],
+        severity: Severity::Low,
+        standard_wording: "This agreement shall be governed by and construed in accordance with the laws of the Republic of Rwanda.",
+    },
+];
+
+const LEASE_PROVISIONS: &[ExpectedProvision] = &[
+    ExpectedProvision {
+        id: "rent_amount_and_due_date",
+        title: "Rent amount and due date",
+        evidence: &[&["rent", "month"], &["rent", "due"], &["rent", "payable"], &["rent", "paid"]],
+        severity: Severity::High,
+        standard_wording: "The Tenant shall pay a monthly rent of [amount] RWF, payable in advance on or before the [day] day of each month.",
+    },
+    ExpectedProvision {
+        id: "deposit_return_deadline",
+        title: "Security deposit return deadline",
+        evidence: &[&["deposit", "return", "days"], &["deposit", "refund", "within"], &["deposit", "returned"]],
+        severity: Severity::High,
+        standard_wording: "The Landlord shall return the security deposit within 30 days of the end of the lease, less only documented deductions for damage beyond normal wear and tear.",
+    },
+    ExpectedProvision {
+        id: "termination_notice",
+        title: "Termination notice period",
+        evidence: &[&["terminat", "notice"], &["end", "lease", "notice"]],
+        severity: Severity::High,
+        standard_wording: "Either party may terminate this lease by giving the other party at least [number] days written notice.",
+    },
+    ExpectedProvision {
+        id: "maintenance_responsibilities",
+        title: "Maintenance and repairs",
+        evidence: &[&["repair"], &["maintain"], &["maintenance"]],
+        severity: Severity::Medium,
+        standard_wording: "The Landlord is responsible for structural repairs and major systems; the Tenant is responsible for minor repairs caused by their own use of the premises.",
+    },
+    ExpectedProvision {
+        id: "landlord_entry",
+        title: "Landlord access to the premises",
+        evidence: &[&["enter", "notice"], &["access", "notice"], &["inspect"]],
+        severity: Severity::Medium,
+        standard_wording: "The Landlord may enter the premises for inspection or repairs only after giving the Tenant at least 24 hours notice, except in an emergency.",
+    },
+    ExpectedProvision {
+        id: "lease_term",
+        title: "Lease duration",
+        evidence: &[&["term", "lease"], &["period of"], &["commence"], &["duration"]],
+        severity: Severity::Medium,
+        standard_wording: "This lease commences on [start date] and continues for a term of [number] months.",
+    },
+];
+
+const EMPLOYMENT_PROVISIONS: &[ExpectedProvision] = &[
+    ExpectedProvision {
+        id: "salary_and_payment_date",
+        title: "Salary and payment date",
+        evidence: &[&["salary"], &["wage"], &["remuneration"]],
+        severity: Severity::Critical,
+        standard_wording: "The Employer shall pay the Employee a gross monthly salary of [amount] RWF, paid no later than the last working day of each month.",
+    },
+    ExpectedProvision {
+        id: "working_hours",
+        title: "Working hours",
+        evidence: &[&["working hours"], &["hours", "week"], &["hours of work"]],
+        severity: Severity::Medium,
+        standard_wording: "Normal working hours shall not exceed 45 hours per week, and overtime shall be compensated in accordance with the labour law.",
+    },
+    ExpectedProvision {
+        id: "annual_leave",
+        title: "Annual leave",
+        evidence: &[&["leave"], &["vacation"], &["holiday"]],
+        severity: Severity::High,
+        standard_wording: "The Employee is entitled to paid annual leave of at least 18 working days per year, in addition to public holidays.",
+    },
+    ExpectedProvision {
+        id: "termination_notice",
+        title: "Termination notice period",
+        evidence: &[&["terminat", "notice"], &["dismiss", "notice"], &["resign", "notice"]],
+        severity: Severity::High,
+        standard_wording: "Either party may terminate this contract by giving at least [number] days written notice, except in cases of serious misconduct as defined by law.",
+    },
+    ExpectedProvision {
+        id: "job_description",
+        title: "Job title and duties",
+        evidence: &[&["position"], &["job description"], &["duties"], &["role of"]],
+        severity: Severity::Medium,
+        standard_wording: "The Employee is employed as [job title] and shall perform the duties described in the attached job description.",
+    },
+];
+
+const SUPPLY_PROVISIONS: &[ExpectedProvision] = &[
+    ExpectedProvision {
+        id: "delivery_terms",
+        title: "Delivery terms",
+        evidence: &[&["deliver"], &["shipment"], &["shipping"]],
+        severity: Severity::High,
+        standard_wording: "The Supplier shall deliver the goods to [place] within [number] days of each purchase order and shall notify the Buyer promptly of any expected delay.",
+    },
+    ExpectedProvision {
+        id: "quality_and_defects",
+        title: "Quality warranty and defective goods",
+        evidence: &[&["defect"], &["warrant"], &["quality"]],
+        severity: Severity::High,
+        standard_wording: "The Supplier warrants that the goods conform to the agreed specifications and shall replace or refund defective goods reported within [number] days of delivery.",
+    },
+    ExpectedProvision {
+        id: "payment_terms",
+        title: "Price and payment terms",
+        evidence: &[&["payment"], &["price"], &["invoice"]],
+        severity: Severity::High,
+        standard_wording: "The Buyer shall pay each undisputed invoice within 30 days of receipt.",
+    },
+    ExpectedProvision {
+        id: "force_majeure",
+        title: "Force majeure",
+        evidence: &[&["force majeure"], &["beyond", "control"]],
+        severity: Severity::Low,
+        standard_wording: "Neither party is liable for failure to perform caused by events beyond its reasonable control, provided it notifies the other party promptly.",
+    },
+];
+
+const SERVICE_PROVISIONS: &[ExpectedProvision] = &[
+    ExpectedProvision {
+        id: "scope_of_work",
+        title: "Scope of work",
+        evidence: &[&["scope"], &["services", "include"], &["statement of work"], &["deliverable"]],
+        severity: Severity::High,
+        standard_wording: "The Contractor shall provide the services and deliverables described in the attached statement of work.",
+    },
+    ExpectedProvision {
+        id: "payment_terms",
+        title: "Fees and payment terms",
+        evidence: &[&["payment"], &["fee"], &["paid"], &["invoice"]],
+        severity: Severity::High,
+        standard_wording: "The Client shall pay the agreed fees within 30 days of receiving a correct invoice for each completed milestone.",
+    },
+    ExpectedProvision {
+        id: "termination_notice",
+        title: "Termination notice period",
+        evidence: &[&["terminat", "notice"]],
+        severity: Severity::High,
+        standard_wording: "Either party may terminate this agreement by giving [number] days written notice; the Client shall pay for services performed up to the termination date.",
+    },
+    ExpectedProvision {
+        id: "intellectual_property",
+        title: "Ownership of work product",
+        evidence: &[&["intellectual property"], &["ownership"], &["copyright"]],
+        severity: Severity::Medium,
+        standard_wording: "Upon full payment, ownership of the deliverables created specifically for the Client transfers to the Client.",
+    },
+];
+
+const NDA_PROVISIONS: &[ExpectedProvision] = &[
+    ExpectedProvision {
+        id: "confidential_information_definition",
+        title: "Definition of confidential information",
+        evidence: &[&["confidential information", "means"], &["confidential information", "includes"], &["definition"]],
+        severity: Severity::Critical,
+        standard_wording: "\"Confidential Information\" means non-public information disclosed by the Disclosing Party that is marked as confidential or would reasonably be understood to be confidential.",
+    },
+    ExpectedProvision {
+        id: "confidentiality_exclusions",
+        title: "Exclusions from confidentiality",
+        evidence: &[&["publicly available"], &["public domain"], &["already known"], &["independently developed"]],
+        severity: Severity::High,
+        standard_wording: "Confidential Information does not include information that is publicly available, already known to the Receiving Party, or independently developed without use of the Confidential Information.",
+    },
+    ExpectedProvision {
+        id: "confidentiality_duration",
+        title: "Duration of confidentiality obligations",
+        evidence: &[&["years"], &["period of"], &["surviv"]],
+        severity: Severity::High,
+        standard_wording: "The obligations of confidentiality shall continue for [number] years from the date of disclosure.",
+    },
+    ExpectedProvision {
+        id: "return_of_information",
+        title: "Return or destruction of information",
+        evidence: &[&["return", "destroy"], &["return", "information"]],
+        severity: Severity::Medium,
+        standard_wording: "On request or at the end of this agreement, the Receiving Party shall return or destroy all Confidential Information in its possession.",
+    },
+];
+
+fn type_provisions(contract_type: ContractType) -> &'static [ExpectedProvision] {
+    match contract_type {
+        ContractType::Lease => LEASE_PROVISIONS,
+        ContractType::Employment => EMPLOYMENT_PROVISIONS,
+        ContractType::Supply => SUPPLY_PROVISIONS,
+        ContractType::Service => SERVICE_PROVISIONS,
+        ContractType::NonDisclosure => NDA_PROVISIONS,
+        ContractType::General => &[],
+    }
+}
+
+// The full checklist for a contract type: type-specific items, then the common ones
+pub fn checklist_for(contract_type: ContractType) -> impl Iterator<Item = &'static ExpectedProvision> {
+    type_provisions(contract_type).iter().chain(COMMON_PROVISIONS.iter())
+}
+
+fn provision_present(provision: &ExpectedProvision, lower_clauses: &[String]) -> bool {
+    lower_clauses.iter().any(|clause| {
+        provision.evidence.iter()
+            .any(|terms| terms.iter().all(|term| clause.contains(term)))
+    })
+}
+
+// List the expected provisions that none of the clauses covers
+pub fn find_missing_clauses<S: AsRef<str>>(clauses: &[S], contract_type: ContractType) -> Vec<MissingClause> {
+    let lower_clauses: Vec<String> = clauses.iter()
+        .map(|clause| clause.as_ref().to_lowercase())
+        .collect();
+
+    checklist_for(contract_type)
+        .filter(|provision| !provision_present(provision, &lower_clauses))
+        .map(|provision| MissingClause {
+            provision_id: provision.id.to_string(),
+            title: provision.title.to_string(),
+            severity: provision.severity,
+            suggested_wording: provision.standard_wording.to_string(),
+        })
+        .collect()
+}
@@ -0,0 +1,204 @@
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+
+use crate::{text_similarity, ClauseAnalysis, ContractAnalysisResult};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum ClauseChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum RiskDirection {
+    Increased,
+    Decreased,
+    Unchanged,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct ClauseChange {
+    pub kind: ClauseChangeKind,
+    pub old_section: Option<String>,
+    pub new_section: Option<String>,
+    pub old_clause: Option<String>,
+    pub new_clause: Option<String>,
+    pub old_label: Option<String>,
+    pub new_label: Option<String>,
+    // Word overlap between the aligned versions (0 for added/removed clauses)
+    pub similarity: f64,
+    pub risk_direction: RiskDirection,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct ContractComparison {
+    pub changes: Vec<ClauseChange>,
+    pub added_clauses: usize,
+    pub removed_clauses: usize,
+    pub modified_clauses: usize,
+    pub unchanged_clauses: usize,
+    pub old_risk_score: f64,
+    pub new_risk_score: f64,
+    pub risk_score_delta: f64,
+}
+
+// Clauses in the same section are paired if they share at least this much wording;
+// clauses from different sections need the higher threshold
+const SAME_SECTION_THRESHOLD: f64 = 0.2;
+const CROSS_SECTION_THRESHOLD: f64 = 0.5;
+
+fn label_risk(label: &str) -> u8 {
+    match label {
+        "Not Allowed" => 2,
+        "Allowed" => 0,
+        _ => 1,
+    }
+}
+
+fn risk_direction(old_label: Option<&str>, new_label: Option<&str>) -> RiskDirection {
+    // A missing clause carries no risk of its own
+    let old_risk = old_label.map(label_risk).unwrap_or(0);
+    let new_risk = new_label.map(label_risk).unwrap_or(0);
+    match new_risk.cmp(&old_risk) {
+        std::cmp::Ordering::Greater => RiskDirection::Increased,
+        std::cmp::Ordering::Less => RiskDirection::Decreased,
+        std::cmp::Ordering::Equal => RiskDirection::Unchanged,
+    }
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+// Greedily pair the most similar clauses first among the still unmatched ones
+fn match_pass(
+    old: &[ClauseAnalysis],
+    new: &[ClauseAnalysis],
+    old_matched: &mut [Option<usize>],
+    new_matched: &mut [bool],
+    same_section_only: bool,
+    threshold: f64,
+) {
+    let mut candidates = Vec::new();
+    for (i, old_clause) in old.iter().enumerate() {
+        if old_matched[i].is_some() {
+            continue;
+        }
+        for (j, new_clause) in new.iter().enumerate() {
+            if new_matched[j] {
+                continue;
+            }
+            let same_section = old_clause.section.is_some() && old_clause.section == new_clause.section;
+            if same_section_only && !same_section {
+                continue;
+            }
+            let similarity = if normalize(&old_clause.clause) == normalize(&new_clause.clause) {
+                1.0
+            } else {
+                text_similarity(&old_clause.clause, &new_clause.clause)
+            };
+            if similarity >= threshold {
+                candidates.push((similarity, i, j));
+            }
+        }
+    }
+
+    candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    for (_, i, j) in candidates {
+        if old_matched[i].is_none() && !new_matched[j] {
+            old_matched[i] = Some(j);
+            new_matched[j] = true;
+        }
+    }
+}
+
+// Align the clauses of two analysed versions and describe every change
+pub fn compare_analyses(old: &ContractAnalysisResult, new: &ContractAnalysisResult) -> ContractComparison {
+    let old_clauses = &old.clause_breakdown;
+    let new_clauses = &new.clause_breakdown;
+
+    let mut old_matched: Vec<Option<usize>> = vec![None; old_clauses.len()];
+    let mut new_matched = vec![false; new_clauses.len()];
+
+    // Identical text anywhere, then same section number, then wording alone
+    match_pass(old_clauses, new_clauses, &mut old_matched, &mut new_matched, false, 1.0);
+    match_pass(old_clauses, new_clauses, &mut old_matched, &mut new_matched, true, SAME_SECTION_THRESHOLD);
+    match_pass(old_clauses, new_clauses, &mut old_matched, &mut new_matched, false, CROSS_SECTION_THRESHOLD);
+
+    let mut changes = Vec::new();
+    let mut unchanged_clauses = 0;
+
+    for (i, old_clause) in old_clauses.iter().enumerate() {
+        match old_matched[i] {
+            Some(j) => {
+                let new_clause = &new_clauses[j];
+                if normalize(&old_clause.clause) == normalize(&new_clause.clause) {
+                    unchanged_clauses += 1;
+                    continue;
+                }
+                changes.push(ClauseChange {
+                    kind: ClauseChangeKind::Modified,
+                    old_section: old_clause.section.clone(),
+                    new_section: new_clause.section.clone(),
+                    old_clause: Some(old_clause.clause.clone()),
+                    new_clause: Some(new_clause.clause.clone()),
+                    old_label: Some(old_clause.label.clone()),
+                    new_label: Some(new_clause.label.clone()),
+                    similarity: text_similarity(&old_clause.clause, &new_clause.clause),
+                    risk_direction: risk_direction(Some(&old_clause.label), Some(&new_clause.label)),
+                });
+            }
+            None => changes.push(ClauseChange {
+                kind: ClauseChangeKind::Removed,
+                old_section: old_clause.section.clone(),
+                new_section: None,
+                old_clause: Some(old_clause.clause.clone()),
+                new_clause: None,
+                old_label: Some(old_clause.label.clone()),
+                new_label: None,
+                similarity: 0.0,
+                risk_direction: risk_direction(Some(&old_clause.label), None),
+            }),
+        }
+    }
+
+    for (j, new_clause) in new_clauses.iter().enumerate() {
+        if new_matched[j] {
+            continue;
+        }
+        changes.push(ClauseChange {
+            kind: ClauseChangeKind::Added,
+            old_section: None,
+            new_section: new_clause.section.clone(),
+            old_clause: None,
+            new_clause: Some(new_clause.clause.clone()),
+            old_label: None,
+            new_label: Some(new_clause.label.clone()),
+            similarity: 0.0,
+            risk_direction: risk_direction(None, Some(&new_clause.label)),
+        });
+    }
+
+    let count = |kind: ClauseChangeKind| changes.iter().filter(|c| c.kind == kind).count();
+    let added_clauses = count(ClauseChangeKind::Added);
+    let removed_clauses = count(ClauseChangeKind::Removed);
+    let modified_clauses = count(ClauseChangeKind::Modified);
+
+    let old_risk_score = old.risk_score;
+    let new_risk_score = new.risk_score;
+
+    ContractComparison {
+        changes,
+        added_clauses,
+        removed_clauses,
+        modified_clauses,
+        unchanged_clauses,
+        old_risk_score,
+        new_risk_score,
+        risk_score_delta: new_risk_score - old_risk_score,
+    }
+}
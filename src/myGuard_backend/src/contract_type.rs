@@ -0,0 +1,422 @@
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+
+// Contract families we know how to analyse with dedicated rules
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, CandidType, Serialize, Deserialize)]
+pub enum ContractType {
+    Lease,
+    Employment,
+    Supply,
+    Service,
+    NonDisclosure,
+    General,
+}
+
+impl ContractType {
+    pub const ALL: [ContractType; 6] = [
+        ContractType::Lease,
+        ContractType::Employment,
+        ContractType::Supply,
+        ContractType::Service,
+        ContractType::NonDisclosure,
+        ContractType::General,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContractType::Lease => "Lease",
+            ContractType::Employment => "Employment",
+            ContractType::Supply => "Supply",
+            ContractType::Service => "Service",
+            ContractType::NonDisclosure => "NonDisclosure",
+            ContractType::General => "General",
+        }
+    }
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct ContractTypeDetection {
+    pub contract_type: ContractType,
+    pub confidence: f64,
+    pub matched_keywords: Vec<String>,
+}
+
+// Type-specific rules, dataset filter and prompt context used by the analyzers
+pub struct AnalysisProfile {
+    pub contract_type: ContractType,
+    // One-sided phrases that are unfair for this kind of contract, matched as
+    // whole words by `find_phrase`
+    pub unfair_patterns: &'static [&'static str],
+    // Phrases that indicate a balanced term for this kind of contract
+    pub fair_patterns: &'static [&'static str],
+    // Generic risk patterns that are normal for this kind of contract
+    pub exempt_patterns: &'static [&'static str],
+    // Context prepended to LLM prompts
+    pub prompt_context: &'static str,
+}
+
+// Keywords and their weights used to recognise each contract type
+const LEASE_KEYWORDS: &[(&str, f64)] = &[
+    ("tenant", 2.0),
+    ("landlord", 2.0),
+    ("lease", 2.0),
+    ("rent", 1.5),
+    ("premises", 1.0),
+    ("security deposit", 1.5),
+    ("property", 0.5),
+    ("eviction", 1.0),
+];
+
+const EMPLOYMENT_KEYWORDS: &[(&str, f64)] = &[
+    ("employee", 2.0),
+    ("employer", 2.0),
+    ("employment", 2.0),
+    ("salary", 1.5),
+    ("wage", 1.5),
+    ("overtime", 1.0),
+    ("leave", 0.5),
+    ("probation", 1.0),
+    ("job", 0.5),
+];
+
+const SUPPLY_KEYWORDS: &[(&str, f64)] = &[
+    ("supplier", 2.0),
+    ("buyer", 1.5),
+    ("purchaser", 1.5),
+    ("goods", 1.5),
+    ("delivery", 1.0),
+    ("deliver", 1.0),
+    ("shipment", 1.0),
+    ("purchase order", 1.5),
+    ("defective", 0.5),
+];
+
+const SERVICE_KEYWORDS: &[(&str, f64)] = &[
+    ("contractor", 2.0),
+    ("client", 1.5),
+    ("services", 1.5),
+    ("service provider", 2.0),
+    ("milestone", 1.0),
+    ("deliverable", 1.0),
+    ("project", 0.5),
+    ("statement of work", 1.5),
+];
+
+const NDA_KEYWORDS: &[(&str, f64)] = &[
+    ("confidential information", 2.5),
+    ("non-disclosure", 3.0),
+    ("nondisclosure", 3.0),
+    ("disclosing party", 2.5),
+    ("receiving party", 2.5),
+    ("confidentiality", 1.5),
+    ("trade secret", 1.0),
+];
+
+// Minimum keyword score before we commit to a specific type
+const MIN_DETECTION_SCORE: f64 = 2.0;
+
+fn keywords_for(contract_type: ContractType) -> &'static [(&'static str, f64)] {
+    match contract_type {
+        ContractType::Lease => LEASE_KEYWORDS,
+        ContractType::Employment => EMPLOYMENT_KEYWORDS,
+        ContractType::Supply => SUPPLY_KEYWORDS,
+        ContractType::Service => SERVICE_KEYWORDS,
+        ContractType::NonDisclosure => NDA_KEYWORDS,
+        ContractType::General => &[],
+    }
+}
+
+// Lowercase words; hyphenated words such as "non-disclosure" stay whole
+fn words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric() && c != '-')
+        .map(|word| word.trim_matches('-'))
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// Occurrences of a keyword as whole words, so "lease" does not count "please"
+// and "rent" does not count "parent". A plural "s" on the last word matches.
+fn count_keyword(words: &[String], keyword: &str) -> usize {
+    let keyword: Vec<&str> = keyword.split(' ').collect();
+    let last = keyword.len() - 1;
+    words.windows(keyword.len())
+        .filter(|window| {
+            window.iter().zip(&keyword).enumerate().all(|(index, (word, expected))| {
+                word == expected || (index == last && word.strip_suffix('s') == Some(expected))
+            })
+        })
+        .count()
+}
+
+// First of the phrases that occurs in the text as whole words. Phrases are
+// split the way the text is, so "employee's" also matches "employee’s".
+pub fn find_phrase<'a>(text: &str, phrases: &[&'a str]) -> Option<&'a str> {
+    let text_words = words(text);
+    phrases.iter()
+        .find(|phrase| count_keyword(&text_words, &words(phrase).join(" ")) > 0)
+        .copied()
+}
+
+// Detect the contract type from keyword frequencies in the text
+pub fn detect_contract_type(text: &str) -> ContractTypeDetection {
+    let words = words(text);
+
+    let mut best_type = ContractType::General;
+    let mut best_score = 0.0;
+    let mut total_score = 0.0;
+    let mut best_keywords = Vec::new();
+
+    for contract_type in ContractType::ALL {
+        let mut score = 0.0;
+        let mut matched = Vec::new();
+
+        for (keyword, weight) in keywords_for(contract_type) {
+            let occurrences = count_keyword(&words, keyword);
+            if occurrences > 0 {
+                // Diminishing returns so one repeated word cannot dominate
+                score += weight * (1.0 + (occurrences as f64).ln());
+                matched.push(keyword.to_string());
+            }
+        }
+
+        total_score += score;
+        if score > best_score {
+            best_score = score;
+            best_type = contract_type;
+            best_keywords = matched;
+        }
+    }
+
+    if best_score < MIN_DETECTION_SCORE {
+        return ContractTypeDetection {
+            contract_type: ContractType::General,
+            confidence: 0.0,
+            matched_keywords: Vec::new(),
+        };
+    }
+
+    ContractTypeDetection {
+        contract_type: best_type,
+        confidence: best_score / total_score,
+        matched_keywords: best_keywords,
+    }
+}
+
+// Resolve the effective type, honouring a caller-supplied override
+pub fn resolve_contract_type(
+    text: &str,
+    override_type: Option<ContractType>,
+) -> (ContractTypeDetection, ContractType) {
+    let detection = detect_contract_type(text);
+    let effective = override_type.unwrap_or(detection.contract_type);
+    (detection, effective)
+}
+
+static LEASE_PROFILE: AnalysisProfile = AnalysisProfile {
+    contract_type: ContractType::Lease,
+    unfair_patterns: &[
+        "forfeit the deposit",
+        "forfeit the security deposit",
+        "forfeit deposit",
+        "deposit is non-refundable",
+        "enter the premises without",
+        "enter the premises at any time",
+        "enter the property without",
+        "enter the property at any time",
+        "enter the tenant's home",
+        "increase rent arbitrarily",
+        "increase the rent at any time",
+        "demand eviction",
+        "evict the tenant without",
+        "responsible for all repairs",
+        "including structural damage",
+        "regardless of usage",
+    ],
+    fair_patterns: &[
+        "return the security deposit",
+        "return the deposit",
+        "quiet enjoyment",
+        "rental receipt",
+        "maintain the property",
+        "renew the lease",
+    ],
+    exempt_patterns: &[],
+    prompt_context: "The clause comes from a residential lease agreement. Judge it from the tenant's perspective under Rwandan tenancy practice.",
+};
+
+static EMPLOYMENT_PROFILE: AnalysisProfile = AnalysisProfile {
+    contract_type: ContractType::Employment,
+    unfair_patterns: &[
+        "non-compete",
+        "not join competitors",
+        "work unpaid overtime",
+        "deduct fines from",
+        "without compensation",
+        "no rest days",
+        "without any rest days",
+        "no breaks",
+        "provide personal social media",
+        "not allowed to take sick leave",
+        "no sick leave",
+        "reduce the employee's salary",
+    ],
+    fair_patterns: &[
+        "paid leave",
+        "overtime pay",
+        "severance",
+        "safe and healthy",
+        "grievance",
+        "equal pay",
+    ],
+    exempt_patterns: &[],
+    prompt_context: "The clause comes from an employment contract. Judge it from the employee's perspective under the Rwandan labour law.",
+};
+
+static SUPPLY_PROFILE: AnalysisProfile = AnalysisProfile {
+    contract_type: ContractType::Supply,
+    unfair_patterns: &[
+        "no obligation to replace",
+        "not responsible for shipping",
+        "not responsible for late deliveries",
+        "sole discretion",
+    ],
+    fair_patterns: &[
+        "guarantees the quality",
+        "notify the client",
+        "agreed timeline",
+        "replace defective",
+    ],
+    // Forfeiture and no-refund terms are ordinary commercial terms between businesses
+    exempt_patterns: &["forfeit", "no refund"],
+    prompt_context: "The clause comes from a commercial supply agreement between businesses. Judge whether the allocation of risk is balanced between supplier and buyer.",
+};
+
+static SERVICE_PROFILE: AnalysisProfile = AnalysisProfile {
+    contract_type: ContractType::Service,
+    unfair_patterns: &[
+        "additional fees without",
+        "full payment before",
+        "sole discretion",
+        "any changes requested",
+    ],
+    fair_patterns: &[
+        "project milestones",
+        "project updates",
+        "agreed deadline",
+        "acceptance",
+    ],
+    exempt_patterns: &[],
+    prompt_context: "The clause comes from a service or contractor agreement. Judge whether obligations and payment terms are balanced between client and provider.",
+};
+
+static NDA_PROFILE: AnalysisProfile = AnalysisProfile {
+    contract_type: ContractType::NonDisclosure,
+    unfair_patterns: &[
+        "non-compete",
+        "perpetual",
+        "in perpetuity",
+        "whether or not marked",
+        "not compete",
+    ],
+    fair_patterns: &[
+        "publicly available",
+        "already known",
+        "required by law",
+        "return or destroy",
+        "mutual",
+    ],
+    // Barring disclosure without consent, and stating that the recipient is not
+    // entitled to the information, are the purpose of an NDA
+    exempt_patterns: &["without consent", "not entitled"],
+    prompt_context: "The clause comes from a non-disclosure agreement. Confidentiality duties are expected, but restrictions beyond protecting confidential information (such as non-compete terms) are unusual.",
+};
+
+static GENERAL_PROFILE: AnalysisProfile = AnalysisProfile {
+    contract_type: ContractType::General,
+    unfair_patterns: &[],
+    fair_patterns: &[],
+    exempt_patterns: &[],
+    prompt_context: "The clause comes from a contract of unknown type.",
+};
+
+pub fn profile_for(contract_type: ContractType) -> &'static AnalysisProfile {
+    match contract_type {
+        ContractType::Lease => &LEASE_PROFILE,
+        ContractType::Employment => &EMPLOYMENT_PROFILE,
+        ContractType::Supply => &SUPPLY_PROFILE,
+        ContractType::Service => &SERVICE_PROFILE,
+        ContractType::NonDisclosure => &NDA_PROFILE,
+        ContractType::General => &GENERAL_PROFILE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyword_count(text: &str, keyword: &str) -> usize {
+        count_keyword(&words(text), keyword)
+    }
+
+    #[test]
+    fn keywords_match_whole_words_only() {
+        assert_eq!(keyword_count("Please release the goods.", "lease"), 0);
+        assert_eq!(keyword_count("The current parent company", "rent"), 0);
+        assert_eq!(keyword_count("Delivery is due; the supplier shall deliver.", "deliver"), 1);
+        assert_eq!(keyword_count("Delivery is due; the supplier shall deliver.", "delivery"), 1);
+        assert_eq!(keyword_count("All tenants and the tenant's guests", "tenant"), 2);
+        assert_eq!(keyword_count("Each purchase order and purchase orders", "purchase order"), 2);
+        assert_eq!(keyword_count("This Non-Disclosure Agreement", "non-disclosure"), 1);
+    }
+
+    #[test]
+    fn substrings_do_not_change_the_detected_type() {
+        let text = "Please release the parent company's current shipment. The supplier shall deliver the goods to the buyer.";
+        assert_eq!(detect_contract_type(text).contract_type, ContractType::Supply);
+    }
+
+    #[test]
+    fn phrases_match_whole_words_only() {
+        let phrases = &["deduct fines from", "reduce the employee's salary", "no sick leave"];
+        assert_eq!(find_phrase("The company may deduct fines from the employee's salary.", phrases), Some("deduct fines from"));
+        assert_eq!(find_phrase("The employer may reduce the employee’s salary at any time.", phrases), Some("reduce the employee's salary"));
+        assert_eq!(find_phrase("Income tax is deducted from the salary.", phrases), None);
+        assert_eq!(find_phrase("There is no sick leaver policy.", phrases), None);
+    }
+
+    #[test]
+    fn fair_clauses_do_not_trip_the_unfair_patterns() {
+        for (text, contract_type) in [
+            ("The Employee is entitled to paid sick leave on presentation of a medical certificate.", ContractType::Employment),
+            ("Income tax and social security contributions are deducted as required by law.", ContractType::Employment),
+            ("The Landlord is responsible for structural repairs and major systems.", ContractType::Lease),
+            ("The Landlord may enter the premises for repairs after giving 24 hours notice.", ContractType::Lease),
+            ("The Supplier shall replace or refund defective goods reported within 30 days.", ContractType::Supply),
+        ] {
+            assert_eq!(find_phrase(text, profile_for(contract_type).unfair_patterns), None, "{}", text);
+        }
+        for (text, contract_type) in [
+            ("Employees are not allowed to take sick leave.", ContractType::Employment),
+            ("The tenant is responsible for all repairs, including structural damages.", ContractType::Lease),
+            ("The landlord has the right to enter the property without notice.", ContractType::Lease),
+        ] {
+            assert!(find_phrase(text, profile_for(contract_type).unfair_patterns).is_some(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn detects_each_contract_type() {
+        for (text, expected) in [
+            ("The Tenant shall pay rent to the Landlord for the leased premises.", ContractType::Lease),
+            ("The Employer shall pay the Employee a salary and overtime.", ContractType::Employment),
+            ("The Supplier shall deliver the goods under each purchase order.", ContractType::Supply),
+            ("The Contractor shall provide the services to the Client.", ContractType::Service),
+            ("The Receiving Party shall protect the Confidential Information.", ContractType::NonDisclosure),
+            ("The parties agree to cooperate.", ContractType::General),
+        ] {
+            assert_eq!(detect_contract_type(text).contract_type, expected, "{}", text);
+        }
+    }
+}
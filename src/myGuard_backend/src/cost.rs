@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+
+use candid::{CandidType, Deserialize, Principal};
+use serde::Serialize;
+
+use crate::calendar;
+use crate::llm::LlmUsage;
+
+// Fees on a 13-node application subnet, in cycles
+const UPDATE_MESSAGE_FEE: u128 = 5_000_000;
+// Per ten instructions
+const TEN_INSTRUCTIONS_FEE: u128 = 4;
+const XNET_CALL_FEE: u128 = 260_000;
+const XNET_BYTE_FEE: u128 = 1_000;
+
+// Daily totals older than this are dropped
+pub const COST_RETENTION_DAYS: u64 = 365;
+
+// Resources used by one analysis
+#[derive(Clone, Copy, Debug, Default, CandidType, Serialize, Deserialize)]
+pub struct CostSummary {
+    // Wasm instructions executed for the analysis, across all its awaits
+    pub instructions: u64,
+    pub llm: LlmUsage,
+    // Instructions and LLM calls priced at the subnet's fees. Ingress and
+    // storage fees are not included.
+    pub estimated_cycles: u128,
+}
+
+impl CostSummary {
+    pub fn new(instructions: u64, llm: LlmUsage) -> Self {
+        let estimated_cycles = UPDATE_MESSAGE_FEE
+            + instructions as u128 * TEN_INSTRUCTIONS_FEE / 10
+            + llm.calls as u128 * XNET_CALL_FEE
+            + (llm.request_bytes + llm.response_bytes) as u128 * XNET_BYTE_FEE;
+        CostSummary {
+            instructions,
+            llm,
+            estimated_cycles,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, CandidType, Serialize, Deserialize)]
+pub struct CostTotals {
+    pub analyses: u64,
+    pub instructions: u64,
+    pub llm_requests: u64,
+    pub llm_calls: u64,
+    pub estimated_cycles: u128,
+}
+
+impl CostTotals {
+    fn add(&mut self, other: &CostTotals) {
+        self.analyses += other.analyses;
+        self.instructions += other.instructions;
+        self.llm_requests += other.llm_requests;
+        self.llm_calls += other.llm_calls;
+        self.estimated_cycles += other.estimated_cycles;
+    }
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct DailyCost {
+    // UTC date, YYYY-MM-DD
+    pub date: String,
+    pub totals: CostTotals,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct PrincipalCost {
+    pub principal: Principal,
+    pub totals: CostTotals,
+}
+
+// Time bounds are inclusive and rounded to whole UTC days
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct CostFilter {
+    pub principal: Option<Principal>,
+    pub from_timestamp: Option<u64>,
+    pub to_timestamp: Option<u64>,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct CostReport {
+    pub totals: CostTotals,
+    // Oldest day first
+    pub by_day: Vec<DailyCost>,
+    // Most expensive principal first
+    pub by_principal: Vec<PrincipalCost>,
+}
+
+// Totals per UTC day and principal
+pub type CostLedger = BTreeMap<(u64, Principal), CostTotals>;
+
+// Add an analysis to the caller's daily totals and drop days past retention
+pub fn record(ledger: &mut CostLedger, principal: Principal, timestamp: u64, cost: &CostSummary) {
+    let day = calendar::day_of(timestamp) as u64;
+    let totals = ledger.entry((day, principal)).or_default();
+    totals.add(&CostTotals {
+        analyses: 1,
+        instructions: cost.instructions,
+        llm_requests: cost.llm.requests as u64,
+        llm_calls: cost.llm.calls as u64,
+        estimated_cycles: cost.estimated_cycles,
+    });
+
+    let oldest_kept = day.saturating_sub(COST_RETENTION_DAYS);
+    *ledger = ledger.split_off(&(oldest_kept, Principal::management_canister()));
+}
+
+pub fn report(ledger: &CostLedger, filter: &CostFilter) -> CostReport {
+    let from_day = filter.from_timestamp.map_or(0, |timestamp| calendar::day_of(timestamp) as u64);
+    let to_day = filter.to_timestamp.map_or(u64::MAX, |timestamp| calendar::day_of(timestamp) as u64);
+
+    let mut totals = CostTotals::default();
+    let mut by_day: BTreeMap<u64, CostTotals> = BTreeMap::new();
+    let mut by_principal: BTreeMap<Principal, CostTotals> = BTreeMap::new();
+    for ((day, principal), day_totals) in ledger.range((from_day, Principal::management_canister())..) {
+        if *day > to_day {
+            break;
+        }
+        if filter.principal.is_some_and(|wanted| wanted != *principal) {
+            continue;
+        }
+        totals.add(day_totals);
+        by_day.entry(*day).or_default().add(day_totals);
+        by_principal.entry(*principal).or_default().add(day_totals);
+    }
+
+    let mut by_principal: Vec<PrincipalCost> = by_principal.into_iter()
+        .map(|(principal, totals)| PrincipalCost { principal, totals })
+        .collect();
+    by_principal.sort_by_key(|entry| std::cmp::Reverse(entry.totals.estimated_cycles));
+
+    CostReport {
+        totals,
+        by_day: by_day.into_iter()
+            .map(|(day, totals)| DailyCost { date: calendar::iso_date(day as i64), totals })
+            .collect(),
+        by_principal,
+    }
+}
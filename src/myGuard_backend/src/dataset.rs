@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+
+use candid::{CandidType, Deserialize, Principal};
+use serde::Serialize;
+
+use crate::ContractRecord;
+
+// Snapshots kept for diffs and rollback; the oldest are dropped first
+pub const MAX_DATASET_VERSIONS: usize = 50;
+
+// A CSV row that could not be turned into a record
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct DatasetParseError {
+    // Line of the CSV file, counting the header as line 1
+    pub row: u64,
+    pub message: String,
+}
+
+// Records parsed from a CSV file; `rows[i]` is the line of `records[i]`
+#[derive(Clone, Debug, Default)]
+pub struct ParsedDataset {
+    pub records: Vec<ContractRecord>,
+    pub rows: Vec<u64>,
+    pub errors: Vec<DatasetParseError>,
+}
+
+// Who changed the dataset, when and why
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct DatasetVersion {
+    pub version: u64,
+    pub author: Principal,
+    pub created_at: u64,
+    pub description: String,
+    pub record_count: usize,
+}
+
+// A version together with the full record set it produced
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DatasetSnapshot {
+    pub info: DatasetVersion,
+    pub records: Vec<ContractRecord>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct RelabelledRecord {
+    pub contract_text: String,
+    pub old_label: String,
+    pub new_label: String,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct DatasetDiff {
+    pub from_version: u64,
+    pub to_version: u64,
+    pub added: Vec<ContractRecord>,
+    pub removed: Vec<ContractRecord>,
+    pub relabelled: Vec<RelabelledRecord>,
+}
+
+pub fn normalize_text(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+// FNV-1a over the normalised text, so it does not depend on spacing or case
+pub fn text_hash(text: &str) -> u64 {
+    normalize_text(text).bytes().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+// Identifier for records whose source did not provide one
+pub fn record_id(text: &str) -> String {
+    format!("rec-{:016x}", text_hash(text))
+}
+
+pub fn assign_missing_ids(records: &mut [ContractRecord]) {
+    for record in records.iter_mut().filter(|r| r.id.trim().is_empty()) {
+        record.id = record_id(&record.contract_text);
+    }
+}
+
+// Add a record, or relabel the record with the same wording. An existing
+// record keeps its id and any metadata the new record does not provide.
+// Returns true if an existing record was updated.
+pub fn merge_record(dataset: &mut Vec<ContractRecord>, mut record: ContractRecord) -> bool {
+    let key = normalize_text(&record.contract_text);
+    if let Some(existing) = dataset.iter_mut().find(|r| normalize_text(&r.contract_text) == key) {
+        existing.label = record.label;
+        existing.provenance = record.provenance;
+        existing.contract_type = record.contract_type.or(existing.contract_type);
+        existing.detect_type();
+        existing.category = record.category.or(existing.category);
+        existing.jurisdiction = record.jurisdiction.or(existing.jurisdiction.take());
+        existing.language = record.language.or(existing.language.take());
+        existing.explanation = record.explanation.or(existing.explanation.take());
+        existing.legal_reference = record.legal_reference.or(existing.legal_reference.take());
+        existing.source = record.source.or(existing.source.take());
+        return true;
+    }
+
+    if record.id.trim().is_empty() {
+        record.id = record_id(&record.contract_text);
+    }
+    record.detect_type();
+    dataset.push(record);
+    false
+}
+
+fn index_by_text(records: &[ContractRecord]) -> BTreeMap<String, &ContractRecord> {
+    records.iter()
+        .map(|r| (normalize_text(&r.contract_text), r))
+        .collect()
+}
+
+// Records are matched by their normalised wording
+pub fn diff_records(from: &DatasetSnapshot, to: &DatasetSnapshot) -> DatasetDiff {
+    let old_records = index_by_text(&from.records);
+    let new_records = index_by_text(&to.records);
+
+    let mut removed = Vec::new();
+    let mut relabelled = Vec::new();
+    for (key, old) in &old_records {
+        match new_records.get(key) {
+            Some(new) if new.label != old.label => relabelled.push(RelabelledRecord {
+                contract_text: new.contract_text.clone(),
+                old_label: old.label.clone(),
+                new_label: new.label.clone(),
+            }),
+            Some(_) => {}
+            None => removed.push((*old).clone()),
+        }
+    }
+
+    let added = new_records.iter()
+        .filter(|(key, _)| !old_records.contains_key(*key))
+        .map(|(_, new)| (*new).clone())
+        .collect();
+
+    DatasetDiff {
+        from_version: from.info.version,
+        to_version: to.info.version,
+        added,
+        removed,
+        relabelled,
+    }
+}
@@ -0,0 +1,376 @@
+use std::collections::BTreeMap;
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use crate::ingest::TextBuilder;
+
+// Largest part inflated from the archive, against zip bombs
+const MAX_PART_BYTES: usize = 32 * 1024 * 1024;
+
+const DOCUMENT_PART: &str = "word/document.xml";
+const NUMBERING_PART: &str = "word/numbering.xml";
+const STYLES_PART: &str = "word/styles.xml";
+
+fn u16_at(bytes: &[u8], offset: usize) -> Option<usize> {
+    bytes.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Option<usize> {
+    bytes.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+}
+
+// Read one file from a zip archive through its central directory. Only stored
+// and deflated entries are supported, which covers every DOCX writer in use.
+fn read_zip_entry(archive: &[u8], wanted: &str) -> Result<Option<Vec<u8>>, String> {
+    const END_OF_DIRECTORY: &[u8] = b"PK\x05\x06";
+    const DIRECTORY_ENTRY: &[u8] = b"PK\x01\x02";
+    const LOCAL_HEADER: &[u8] = b"PK\x03\x04";
+    let corrupt = || "The DOCX file is not a valid zip archive".to_string();
+
+    let search_from = archive.len().saturating_sub(22 + u16::MAX as usize);
+    let end = (search_from..archive.len().saturating_sub(21))
+        .rev()
+        .find(|&i| archive[i..].starts_with(END_OF_DIRECTORY))
+        .ok_or_else(corrupt)?;
+    let entries = u16_at(archive, end + 10).ok_or_else(corrupt)?;
+    let mut offset = u32_at(archive, end + 16).ok_or_else(corrupt)?;
+
+    for _ in 0..entries {
+        if !archive.get(offset..).is_some_and(|rest| rest.starts_with(DIRECTORY_ENTRY)) {
+            return Err(corrupt());
+        }
+        let method = u16_at(archive, offset + 10).ok_or_else(corrupt)?;
+        let compressed_size = u32_at(archive, offset + 20).ok_or_else(corrupt)?;
+        let name_length = u16_at(archive, offset + 28).ok_or_else(corrupt)?;
+        let extra_length = u16_at(archive, offset + 30).ok_or_else(corrupt)?;
+        let comment_length = u16_at(archive, offset + 32).ok_or_else(corrupt)?;
+        let local_offset = u32_at(archive, offset + 42).ok_or_else(corrupt)?;
+        let name = archive.get(offset + 46..offset + 46 + name_length).ok_or_else(corrupt)?;
+        offset += 46 + name_length + extra_length + comment_length;
+
+        if name != wanted.as_bytes() {
+            continue;
+        }
+        if !archive.get(local_offset..).is_some_and(|rest| rest.starts_with(LOCAL_HEADER)) {
+            return Err(corrupt());
+        }
+        let local_name_length = u16_at(archive, local_offset + 26).ok_or_else(corrupt)?;
+        let local_extra_length = u16_at(archive, local_offset + 28).ok_or_else(corrupt)?;
+        let data_start = local_offset + 30 + local_name_length + local_extra_length;
+        let data = archive.get(data_start..data_start + compressed_size).ok_or_else(corrupt)?;
+
+        return match method {
+            0 => Ok(Some(data.to_vec())),
+            8 => miniz_oxide::inflate::decompress_to_vec_with_limit(data, MAX_PART_BYTES)
+                .map(Some)
+                .map_err(|_| format!("Could not decompress {} from the DOCX file", wanted)),
+            _ => Err(format!("{} uses an unsupported zip compression method ({})", wanted, method)),
+        };
+    }
+    Ok(None)
+}
+
+fn read_xml_part(archive: &[u8], name: &str) -> Result<Option<String>, String> {
+    match read_zip_entry(archive, name)? {
+        Some(bytes) => String::from_utf8(bytes)
+            .map(Some)
+            .map_err(|_| format!("{} in the DOCX file is not UTF-8", name)),
+        None => Ok(None),
+    }
+}
+
+fn attribute(element: &BytesStart, name: &str) -> Option<String> {
+    element.try_get_attribute(name).ok().flatten()
+        .and_then(|attribute| attribute.unescape_value().ok().map(|value| value.into_owned()))
+}
+
+// One level of a numbering definition, e.g. format "lowerLetter" and text "(%3)"
+#[derive(Clone, Debug, Default)]
+struct NumberingLevel {
+    format: String,
+    text: String,
+    start: u32,
+}
+
+#[derive(Default)]
+struct Numbering {
+    // Abstract numbering id -> levels by indentation level
+    abstract_levels: BTreeMap<String, BTreeMap<usize, NumberingLevel>>,
+    // Numbering instance id -> abstract numbering id
+    instances: BTreeMap<String, String>,
+}
+
+impl Numbering {
+    fn levels(&self, num_id: &str) -> Option<&BTreeMap<usize, NumberingLevel>> {
+        self.abstract_levels.get(self.instances.get(num_id)?)
+    }
+}
+
+fn parse_numbering(xml: &str) -> Numbering {
+    let mut numbering = Numbering::default();
+    let mut reader = Reader::from_str(xml);
+    let mut abstract_id: Option<String> = None;
+    let mut level: Option<(usize, NumberingLevel)> = None;
+    let mut num_id: Option<String> = None;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => match e.name().as_ref() {
+                b"w:abstractNum" => abstract_id = attribute(&e, "w:abstractNumId"),
+                b"w:lvl" => {
+                    let ilvl = attribute(&e, "w:ilvl").and_then(|v| v.parse().ok()).unwrap_or(0);
+                    level = Some((ilvl, NumberingLevel { start: 1, ..Default::default() }));
+                }
+                b"w:start" => if let Some((_, level)) = level.as_mut() {
+                    level.start = attribute(&e, "w:val").and_then(|v| v.parse().ok()).unwrap_or(1);
+                },
+                b"w:numFmt" => if let Some((_, level)) = level.as_mut() {
+                    level.format = attribute(&e, "w:val").unwrap_or_default();
+                },
+                b"w:lvlText" => if let Some((_, level)) = level.as_mut() {
+                    level.text = attribute(&e, "w:val").unwrap_or_default();
+                },
+                b"w:num" => num_id = attribute(&e, "w:numId"),
+                b"w:abstractNumId" => if let (Some(id), Some(target)) = (num_id.clone(), attribute(&e, "w:val")) {
+                    numbering.instances.insert(id, target);
+                },
+                _ => {}
+            },
+            Ok(Event::End(e)) => match e.name().as_ref() {
+                b"w:lvl" => if let (Some(id), Some((ilvl, finished))) = (abstract_id.as_ref(), level.take()) {
+                    numbering.abstract_levels.entry(id.clone()).or_default().insert(ilvl, finished);
+                },
+                b"w:abstractNum" => abstract_id = None,
+                b"w:num" => num_id = None,
+                _ => {}
+            },
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+    numbering
+}
+
+// What a paragraph style contributes: heading status and list numbering
+#[derive(Clone, Debug, Default)]
+struct ParagraphStyle {
+    heading: bool,
+    num_id: Option<String>,
+    ilvl: Option<usize>,
+}
+
+fn parse_styles(xml: &str) -> BTreeMap<String, ParagraphStyle> {
+    let mut styles = BTreeMap::new();
+    let mut reader = Reader::from_str(xml);
+    let mut current: Option<(String, ParagraphStyle)> = None;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => match e.name().as_ref() {
+                b"w:style" => {
+                    current = attribute(&e, "w:styleId").map(|id| (id, ParagraphStyle::default()));
+                }
+                b"w:name" => if let Some((_, style)) = current.as_mut() {
+                    let name = attribute(&e, "w:val").unwrap_or_default().to_lowercase();
+                    style.heading |= name.starts_with("heading") || name == "title";
+                },
+                b"w:outlineLvl" => if let Some((_, style)) = current.as_mut() {
+                    style.heading = true;
+                },
+                b"w:numId" => if let Some((_, style)) = current.as_mut() {
+                    style.num_id = attribute(&e, "w:val");
+                },
+                b"w:ilvl" => if let Some((_, style)) = current.as_mut() {
+                    style.ilvl = attribute(&e, "w:val").and_then(|v| v.parse().ok());
+                },
+                _ => {}
+            },
+            Ok(Event::End(e)) if e.name().as_ref() == b"w:style" => {
+                if let Some((id, style)) = current.take() {
+                    styles.insert(id, style);
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+    styles
+}
+
+fn to_letters(mut n: u32, upper: bool) -> String {
+    let mut letters = Vec::new();
+    while n > 0 {
+        n -= 1;
+        letters.push((b'a' + (n % 26) as u8) as char);
+        n /= 26;
+    }
+    let letters: String = letters.into_iter().rev().collect();
+    if upper { letters.to_uppercase() } else { letters }
+}
+
+fn to_roman(mut n: u32, upper: bool) -> String {
+    const NUMERALS: [(u32, &str); 13] = [
+        (1000, "m"), (900, "cm"), (500, "d"), (400, "cd"), (100, "c"), (90, "xc"),
+        (50, "l"), (40, "xl"), (10, "x"), (9, "ix"), (5, "v"), (4, "iv"), (1, "i"),
+    ];
+    let mut roman = String::new();
+    for (value, numeral) in NUMERALS {
+        while n >= value {
+            roman.push_str(numeral);
+            n -= value;
+        }
+    }
+    if upper { roman.to_uppercase() } else { roman }
+}
+
+fn format_number(n: u32, format: &str) -> String {
+    match format {
+        "lowerLetter" => to_letters(n, false),
+        "upperLetter" => to_letters(n, true),
+        "lowerRoman" => to_roman(n, false),
+        "upperRoman" => to_roman(n, true),
+        _ => n.to_string(),
+    }
+}
+
+// Running counters of the numbered lists, per numbering instance
+#[derive(Default)]
+struct ListCounters {
+    counters: BTreeMap<String, Vec<u32>>,
+}
+
+impl ListCounters {
+    // Advance the list and render the paragraph's number, e.g. "4.2" or "(a)".
+    // Bullets and unnumbered levels render as nothing.
+    fn next_label(&mut self, numbering: &Numbering, num_id: &str, ilvl: usize) -> Option<String> {
+        let levels = numbering.levels(num_id)?;
+        let level = levels.get(&ilvl)?;
+        if level.format == "bullet" || level.format == "none" || level.text.is_empty() {
+            return None;
+        }
+
+        let counters = self.counters.entry(num_id.to_string()).or_insert_with(|| vec![0; 9]);
+        if ilvl >= counters.len() {
+            return None;
+        }
+        counters[ilvl] = if counters[ilvl] == 0 { level.start } else { counters[ilvl] + 1 };
+        for deeper in counters.iter_mut().skip(ilvl + 1) {
+            *deeper = 0;
+        }
+
+        let mut label = level.text.clone();
+        for (index, count) in counters.iter().enumerate().take(ilvl + 1).rev() {
+            let placeholder = format!("%{}", index + 1);
+            if label.contains(&placeholder) {
+                let other = levels.get(&index).cloned().unwrap_or_default();
+                let value = if *count == 0 { other.start.max(1) } else { *count };
+                label = label.replace(&placeholder, &format_number(value, &other.format));
+            }
+        }
+        Some(label)
+    }
+}
+
+#[derive(Default)]
+struct Paragraph {
+    text: String,
+    style: Option<String>,
+    num_id: Option<String>,
+    ilvl: Option<usize>,
+}
+
+// Extract the body of a DOCX file: paragraphs become lines, headings are kept
+// with their list numbers, and table rows become " | " separated cells
+pub fn extract(archive: &[u8]) -> Result<TextBuilder, String> {
+    let document = read_xml_part(archive, DOCUMENT_PART)?
+        .ok_or_else(|| "The file is not a Word document (word/document.xml is missing)".to_string())?;
+    let numbering = read_xml_part(archive, NUMBERING_PART)?.map(|xml| parse_numbering(&xml)).unwrap_or_default();
+    let styles = read_xml_part(archive, STYLES_PART)?.map(|xml| parse_styles(&xml)).unwrap_or_default();
+
+    let mut builder = TextBuilder::default();
+    let mut counters = ListCounters::default();
+    let mut reader = Reader::from_str(&document);
+    let mut paragraph: Option<Paragraph> = None;
+    let mut in_text = false;
+    let mut table_depth = 0;
+    let mut row: Option<Vec<String>> = None;
+    let mut cell: Option<String> = None;
+
+    loop {
+        let event = reader.read_event()
+            .map_err(|e| format!("Could not read the Word document: {}", e))?;
+        // Self-closing elements have no end event
+        let empty = matches!(event, Event::Empty(_));
+        match event {
+            Event::Start(e) | Event::Empty(e) => {
+                match e.name().as_ref() {
+                    b"w:p" if !empty => paragraph = Some(Paragraph::default()),
+                    b"w:pStyle" => if let Some(p) = paragraph.as_mut() {
+                        p.style = attribute(&e, "w:val");
+                    },
+                    b"w:numId" => if let Some(p) = paragraph.as_mut() {
+                        p.num_id = attribute(&e, "w:val");
+                    },
+                    b"w:ilvl" => if let Some(p) = paragraph.as_mut() {
+                        p.ilvl = attribute(&e, "w:val").and_then(|v| v.parse().ok());
+                    },
+                    b"w:t" => in_text = !empty,
+                    b"w:tab" | b"w:br" | b"w:cr" => if let Some(p) = paragraph.as_mut() {
+                        p.text.push(' ');
+                    },
+                    b"w:noBreakHyphen" => if let Some(p) = paragraph.as_mut() {
+                        p.text.push('-');
+                    },
+                    b"w:tbl" if !empty => {
+                        table_depth += 1;
+                        if table_depth == 1 {
+                            builder.start_table();
+                        }
+                    }
+                    b"w:tr" if table_depth == 1 && !empty => row = Some(Vec::new()),
+                    b"w:tc" if table_depth == 1 && !empty => cell = Some(String::new()),
+                    _ => {}
+                }
+            }
+            Event::Text(text) if in_text => {
+                if let (Some(p), Ok(text)) = (paragraph.as_mut(), text.unescape()) {
+                    p.text.push_str(&text);
+                }
+            }
+            Event::End(e) => match e.name().as_ref() {
+                b"w:t" => in_text = false,
+                b"w:p" => if let Some(finished) = paragraph.take() {
+                    let style = finished.style.as_ref().and_then(|id| styles.get(id)).cloned().unwrap_or_default();
+                    let num_id = finished.num_id.or(style.num_id).filter(|id| id != "0");
+                    let label = num_id.and_then(|id| {
+                        counters.next_label(&numbering, &id, finished.ilvl.or(style.ilvl).unwrap_or(0))
+                    });
+                    let text = match label {
+                        Some(label) if !finished.text.trim().is_empty() => format!("{} {}", label, finished.text),
+                        _ => finished.text,
+                    };
+                    if let Some(cell) = cell.as_mut() {
+                        cell.push(' ');
+                        cell.push_str(&text);
+                    } else if style.heading {
+                        builder.heading(&text);
+                    } else {
+                        builder.line(&text);
+                    }
+                },
+                b"w:tc" if table_depth == 1 => if let (Some(text), Some(cells)) = (cell.take(), row.as_mut()) {
+                    cells.push(text);
+                },
+                b"w:tr" if table_depth == 1 => if let Some(cells) = row.take() {
+                    builder.table_row(&cells);
+                },
+                b"w:tbl" => table_depth -= 1,
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(builder)
+}
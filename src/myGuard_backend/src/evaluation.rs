@@ -0,0 +1,258 @@
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+
+use crate::{contract_type, dataset};
+use crate::{classify_clause, rule_classify_clause, ContractRecord};
+
+// Every fifth record (by hash of its text) is held out for evaluation
+const HOLDOUT_MODULUS: u64 = 5;
+
+// Labels a clause can be assigned; anything else counts as unclassified
+const LABELS: [&str; 3] = ["Allowed", "Not Allowed", "Unclassified"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum Analyzer {
+    // Nearest dataset record by word overlap (`classify_clause`)
+    Similarity,
+    // Similarity plus the contract type's unfair/fair patterns (`rule_classify_clause`)
+    Rules,
+    // The language model prompt used by `llm_analyze`
+    Llm,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct LabelMetrics {
+    pub label: String,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+    // Number of held-out records carrying this label
+    pub support: usize,
+}
+
+// One row of the confusion matrix: how the records of one actual label were predicted
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct ConfusionRow {
+    pub actual: String,
+    pub predicted: Vec<(String, usize)>,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct EvaluationReport {
+    pub analyzer: Analyzer,
+    pub training_records: usize,
+    pub test_records: usize,
+    pub accuracy: f64,
+    // Share of held-out records that received Allowed or Not Allowed
+    pub coverage: f64,
+    pub macro_f1: f64,
+    pub per_label: Vec<LabelMetrics>,
+    pub confusion_matrix: Vec<ConfusionRow>,
+}
+
+// Hashing the wording keeps the split independent of record order
+pub fn is_held_out(record: &ContractRecord) -> bool {
+    dataset::text_hash(&record.contract_text).is_multiple_of(HOLDOUT_MODULUS)
+}
+
+// Deterministic training/test split of the labelled dataset
+pub fn split_dataset(dataset: &[ContractRecord]) -> (Vec<ContractRecord>, Vec<ContractRecord>) {
+    dataset.iter().cloned().partition(|record| !is_held_out(record))
+}
+
+fn normalize_label(label: &str) -> &'static str {
+    match label {
+        "Allowed" => "Allowed",
+        "Not Allowed" => "Not Allowed",
+        _ => "Unclassified",
+    }
+}
+
+// Prediction of an offline analyzer, using only the training records
+pub fn predict(analyzer: Analyzer, clause: &str, training: &[ContractRecord]) -> Option<String> {
+    // The record's own detected type picks the profile, as for a full contract
+    let contract_type = contract_type::detect_contract_type(clause).contract_type;
+    match analyzer {
+        Analyzer::Similarity => Some(classify_clause(clause, contract_type, training).0),
+        Analyzer::Rules => Some(rule_classify_clause(clause, contract_type::profile_for(contract_type), training).0),
+        Analyzer::Llm => None,
+    }
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 { 0.0 } else { numerator as f64 / denominator as f64 }
+}
+
+// Score predictions against the held-out labels
+pub fn score(
+    analyzer: Analyzer,
+    training_records: usize,
+    test: &[ContractRecord],
+    predictions: &[String],
+) -> EvaluationReport {
+    let mut matrix = [[0usize; LABELS.len()]; LABELS.len()];
+    for (record, predicted) in test.iter().zip(predictions) {
+        let actual = LABELS.iter().position(|l| *l == normalize_label(&record.label)).unwrap_or(2);
+        let predicted = LABELS.iter().position(|l| *l == normalize_label(predicted)).unwrap_or(2);
+        matrix[actual][predicted] += 1;
+    }
+
+    let total: usize = matrix.iter().flatten().sum();
+    let correct: usize = (0..LABELS.len()).map(|i| matrix[i][i]).sum();
+    let unclassified: usize = matrix.iter().map(|row| row[2]).sum();
+
+    // Unclassified is a prediction outcome rather than a dataset label, so it gets no metrics row
+    let per_label: Vec<LabelMetrics> = (0..2)
+        .map(|i| {
+            let true_positives = matrix[i][i];
+            let predicted_count: usize = matrix.iter().map(|row| row[i]).sum();
+            let support: usize = matrix[i].iter().sum();
+            let precision = ratio(true_positives, predicted_count);
+            let recall = ratio(true_positives, support);
+            let f1 = if precision + recall > 0.0 {
+                2.0 * precision * recall / (precision + recall)
+            } else {
+                0.0
+            };
+            LabelMetrics {
+                label: LABELS[i].to_string(),
+                precision,
+                recall,
+                f1,
+                support,
+            }
+        })
+        .collect();
+
+    let macro_f1 = per_label.iter().map(|m| m.f1).sum::<f64>() / per_label.len() as f64;
+
+    let confusion_matrix = LABELS.iter()
+        .enumerate()
+        .filter(|(i, _)| matrix[*i].iter().sum::<usize>() > 0)
+        .map(|(i, actual)| ConfusionRow {
+            actual: actual.to_string(),
+            predicted: LABELS.iter()
+                .enumerate()
+                .map(|(j, predicted)| (predicted.to_string(), matrix[i][j]))
+                .collect(),
+        })
+        .collect();
+
+    EvaluationReport {
+        analyzer,
+        training_records,
+        test_records: total,
+        accuracy: ratio(correct, total),
+        coverage: ratio(total - unclassified, total),
+        macro_f1,
+        per_label,
+        confusion_matrix,
+    }
+}
+
+// Evaluate the similarity or rule analyzer on the held-out split of `dataset`
+pub fn evaluate_offline(analyzer: Analyzer, dataset: &[ContractRecord]) -> Result<EvaluationReport, String> {
+    let (training, test) = split_dataset(dataset);
+    if training.is_empty() || test.is_empty() {
+        return Err("The dataset is too small to hold out a test split".to_string());
+    }
+
+    let predictions = test.iter()
+        .map(|record| predict(analyzer, &record.contract_text, &training))
+        .collect::<Option<Vec<String>>>()
+        .ok_or_else(|| "This analyzer needs the language model and cannot run offline".to_string())?;
+
+    Ok(score(analyzer, training.len(), &test, &predictions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_contract_dataset, CSV_DATA};
+
+    // Coverage floors, set just below the shares measured when they were added
+    // (similarity 0.125, rules 0.500). Raise them when the analyzers improve; a
+    // drop means a rule or threshold change hurt.
+    const SIMILARITY_MIN_COVERAGE: f64 = 0.12;
+    const RULES_MIN_COVERAGE: f64 = 0.45;
+    // Measured at 0.417; the analyzers leave most records unclassified, which
+    // counts against accuracy
+    const RULES_MIN_ACCURACY: f64 = 0.40;
+
+    fn dataset() -> Vec<ContractRecord> {
+        let parsed = parse_contract_dataset(CSV_DATA);
+        assert!(parsed.errors.is_empty(), "dataset parse errors: {:?}", parsed.errors);
+        parsed.records
+    }
+
+    #[test]
+    fn split_is_deterministic_and_disjoint() {
+        let records = dataset();
+        let (training, test) = split_dataset(&records);
+        assert_eq!(training.len() + test.len(), records.len());
+        assert!(!test.is_empty());
+        assert!(test.iter().all(is_held_out));
+        assert!(!training.iter().any(is_held_out));
+    }
+
+    // Accuracy of always predicting the most common held-out label
+    fn majority_baseline(test: &[ContractRecord]) -> f64 {
+        let allowed = test.iter().filter(|record| record.label == "Allowed").count();
+        let not_allowed = test.iter().filter(|record| record.label == "Not Allowed").count();
+        ratio(allowed.max(not_allowed), test.len())
+    }
+
+    // Accuracy on the records the analyzer gave a label
+    fn decided_accuracy(report: &EvaluationReport) -> f64 {
+        let count = |actual: &str, predicted: &str| report.confusion_matrix.iter()
+            .filter(|row| row.actual == actual)
+            .flat_map(|row| &row.predicted)
+            .filter(|(label, _)| label == predicted)
+            .map(|(_, count)| count)
+            .sum::<usize>();
+        let correct = count("Allowed", "Allowed") + count("Not Allowed", "Not Allowed");
+        let decided = correct + count("Allowed", "Not Allowed") + count("Not Allowed", "Allowed");
+        ratio(correct, decided)
+    }
+
+    #[test]
+    fn analyzers_beat_the_majority_baseline() {
+        let records = dataset();
+        let (_, test) = split_dataset(&records);
+        let baseline = majority_baseline(&test);
+        for (analyzer, min_coverage) in [
+            (Analyzer::Similarity, SIMILARITY_MIN_COVERAGE),
+            (Analyzer::Rules, RULES_MIN_COVERAGE),
+        ] {
+            let report = evaluate_offline(analyzer, &records).unwrap();
+            let accuracy = decided_accuracy(&report);
+            assert!(
+                accuracy > baseline,
+                "{:?} accuracy on labelled records {:.3} does not beat the majority baseline {:.3}",
+                analyzer,
+                accuracy,
+                baseline
+            );
+            assert!(
+                report.coverage >= min_coverage,
+                "{:?} coverage {:.3} fell below {:.3}",
+                analyzer,
+                report.coverage,
+                min_coverage
+            );
+        }
+
+        let rules = evaluate_offline(Analyzer::Rules, &records).unwrap();
+        assert!(
+            rules.accuracy >= RULES_MIN_ACCURACY,
+            "Rules accuracy {:.3} fell below {:.3}",
+            rules.accuracy,
+            RULES_MIN_ACCURACY
+        );
+    }
+
+    #[test]
+    fn llm_cannot_run_offline() {
+        assert!(evaluate_offline(Analyzer::Llm, &dataset()).is_err());
+    }
+}
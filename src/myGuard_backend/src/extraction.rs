@@ -0,0 +1,601 @@
+use std::sync::OnceLock;
+
+use candid::{CandidType, Deserialize};
+use ic_llm::ChatMessage;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::injection;
+
+// Byte offsets of an extracted value in the submitted contract text
+#[derive(Clone, Debug, PartialEq, CandidType, Serialize, Deserialize)]
+pub struct SourceSpan {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum ExtractionSource {
+    Rule,
+    Llm,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct ExtractedValue {
+    pub value: String,
+    // Machine-friendly form, e.g. ISO date or notice period in days
+    pub normalized: Option<String>,
+    pub span: Option<SourceSpan>,
+    pub source: ExtractionSource,
+    pub confidence: f64,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct ContractParty {
+    pub name: ExtractedValue,
+    pub role: String,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct PaymentTerm {
+    pub amount: ExtractedValue,
+    pub currency: Option<String>,
+    pub schedule: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, CandidType, Serialize, Deserialize)]
+pub struct ContractEntities {
+    pub parties: Vec<ContractParty>,
+    pub effective_date: Option<ExtractedValue>,
+    pub termination_date: Option<ExtractedValue>,
+    pub renewal_terms: Vec<ExtractedValue>,
+    pub payments: Vec<PaymentTerm>,
+    pub notice_periods: Vec<ExtractedValue>,
+    pub governing_law: Option<ExtractedValue>,
+    pub dispute_forum: Option<ExtractedValue>,
+}
+
+// Confidence given to values found by the deterministic parsers and by the LLM
+const RULE_CONFIDENCE: f64 = 0.9;
+const LLM_CONFIDENCE: f64 = 0.6;
+
+// Longest contract excerpt we send to the LLM for extraction
+const MAX_LLM_INPUT_CHARS: usize = 6000;
+
+const MONTHS: [&str; 12] = [
+    "january", "february", "march", "april", "may", "june",
+    "july", "august", "september", "october", "november", "december",
+];
+
+pub const PARTY_ROLES: &str = "Landlord|Tenant|Lessor|Lessee|Employer|Employee|Supplier|Buyer|Purchaser|Seller|Client|Contractor|Consultant|Service Provider|Disclosing Party|Receiving Party|Company";
+
+fn cached_regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).expect("invalid extraction pattern"))
+}
+
+fn date_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    cached_regex(&RE, r"(?i)\b(?:(\d{1,2})(?:st|nd|rd|th)?\s+(january|february|march|april|may|june|july|august|september|october|november|december),?\s+(\d{4})|(january|february|march|april|may|june|july|august|september|october|november|december)\s+(\d{1,2})(?:st|nd|rd|th)?,?\s+(\d{4})|(\d{4})-(\d{2})-(\d{2})|(\d{1,2})/(\d{1,2})/(\d{4}))\b")
+}
+
+fn amount_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    cached_regex(&RE, r"(?i)(?:\b(rwf|frw|usd|eur|kes|ugx)|([$€£]))\s?(\d{1,3}(?:[,\s]\d{3})+|\d+)(?:\.(\d{1,2}))?|\b(\d{1,3}(?:[,\s]\d{3})+|\d+)(?:\.(\d{1,2}))?\s?(rwf|frw|usd|eur|kes|ugx|rwandan francs|francs|dollars|euros)\b")
+}
+
+fn notice_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        let pattern = format!(
+            r"(?i)\b({number})\s*(?:\(\d+\)\s*)?(?:-|\s)?(?:business\s+|calendar\s+|working\s+)?(day|week|month)s?'?\s+(?:(?:prior|advance|written|of)\s+)*notice|\bnotice\s+(?:period\s+)?of\s+(?:at\s+least\s+)?({number})\s*(?:\(\d+\)\s*)?(?:business\s+|calendar\s+|working\s+)?(day|week|month)s?",
+            number = number_pattern()
+        );
+        Regex::new(&pattern).expect("invalid extraction pattern")
+    })
+}
+
+fn governing_law_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    cached_regex(&RE, r"(?i:governed\s+by\s+(?:and\s+construed\s+in\s+accordance\s+with\s+)?(?:the\s+)?laws?\s+of\s+(?:the\s+)?)([A-Z][A-Za-z]+(?:\s+(?:of\s+)?[A-Z][A-Za-z]+)*)")
+}
+
+fn forum_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    cached_regex(&RE, r"(?i:(?:courts?|tribunals?)\s+of\s+(?:the\s+)?|arbitration\s+(?:before|under|administered\s+by|at)\s+(?:the\s+)?)([A-Z][A-Za-z]+(?:\s+(?:of\s+)?[A-Z][A-Za-z]+)*)")
+}
+
+fn party_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let pattern = format!(
+        r#"([A-Z][A-Za-z0-9.&'-]*(?:\s+[A-Z][A-Za-z0-9.&'-]*)*)\s*,?\s*\((?i:hereinafter\s+(?:referred\s+to\s+as\s+|called\s+)?)?(?i:the\s+)?["“']?((?i:{}))["”']?\)"#,
+        PARTY_ROLES
+    );
+    cached_regex(&RE, &pattern)
+}
+
+fn rule_value(text: &str, start: usize, end: usize, normalized: Option<String>) -> ExtractedValue {
+    ExtractedValue {
+        value: text[start..end].trim().to_string(),
+        normalized,
+        span: Some(SourceSpan {
+            start,
+            end,
+            text: text[start..end].to_string(),
+        }),
+        source: ExtractionSource::Rule,
+        confidence: RULE_CONFIDENCE,
+    }
+}
+
+// A '.' only ends a sentence when followed by whitespace or the end of the
+// text, so amounts such as "RWF 1.5 million" stay whole
+fn is_sentence_break(text: &str, index: usize) -> bool {
+    match text.as_bytes()[index] {
+        b'\n' | b';' => true,
+        b'.' => text[index + 1..].chars().next().is_none_or(char::is_whitespace),
+        _ => false,
+    }
+}
+
+// Bounds of the sentence or line that contains the given byte range
+fn sentence_bounds(text: &str, start: usize, end: usize) -> (usize, usize) {
+    let sentence_start = text[..start]
+        .rmatch_indices(['.', '\n', ';'])
+        .map(|(i, _)| i)
+        .find(|&i| is_sentence_break(text, i))
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let sentence_end = text[end..]
+        .match_indices(['.', '\n', ';'])
+        .map(|(i, _)| end + i)
+        .find(|&i| is_sentence_break(text, i))
+        .unwrap_or(text.len());
+    (sentence_start, sentence_end)
+}
+
+// Numbers written as words that the extractors understand
+const NUMBER_WORDS: [(&str, u32); 15] = [
+    ("one", 1),
+    ("two", 2),
+    ("three", 3),
+    ("four", 4),
+    ("five", 5),
+    ("six", 6),
+    ("seven", 7),
+    ("ten", 10),
+    ("twelve", 12),
+    ("fourteen", 14),
+    ("fifteen", 15),
+    ("twenty", 20),
+    ("thirty", 30),
+    ("sixty", 60),
+    ("ninety", 90),
+];
+
+// Regex alternation matching digits or any of `NUMBER_WORDS`, longest words
+// first so "sixty" is not read as "six"
+pub fn number_pattern() -> String {
+    let mut words: Vec<&str> = NUMBER_WORDS.iter().map(|(word, _)| *word).collect();
+    words.sort_by_key(|word| std::cmp::Reverse(word.len()));
+    format!(r"\d+|{}", words.join("|"))
+}
+
+pub fn number_word_value(word: &str) -> Option<u32> {
+    let word = word.to_lowercase();
+    NUMBER_WORDS.iter()
+        .find(|(name, _)| *name == word)
+        .map(|(_, value)| *value)
+        .or_else(|| word.parse().ok())
+}
+
+fn month_number(name: &str) -> Option<u32> {
+    let lower = name.to_lowercase();
+    MONTHS.iter().position(|m| *m == lower).map(|i| i as u32 + 1)
+}
+
+fn iso_date(year: &str, month: u32, day: &str) -> Option<String> {
+    let day: u32 = day.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    Some(format!("{}-{:02}-{:02}", year, month, day))
+}
+
+// Normalise a date match to ISO 8601 (numeric dates are read day-first)
+fn normalize_date(caps: &regex::Captures) -> Option<String> {
+    if let (Some(day), Some(month), Some(year)) = (caps.get(1), caps.get(2), caps.get(3)) {
+        return iso_date(year.as_str(), month_number(month.as_str())?, day.as_str());
+    }
+    if let (Some(month), Some(day), Some(year)) = (caps.get(4), caps.get(5), caps.get(6)) {
+        return iso_date(year.as_str(), month_number(month.as_str())?, day.as_str());
+    }
+    if let (Some(year), Some(month), Some(day)) = (caps.get(7), caps.get(8), caps.get(9)) {
+        return iso_date(year.as_str(), month.as_str().parse().ok()?, day.as_str());
+    }
+    if let (Some(day), Some(month), Some(year)) = (caps.get(10), caps.get(11), caps.get(12)) {
+        return iso_date(year.as_str(), month.as_str().parse().ok()?, day.as_str());
+    }
+    None
+}
+
+// Byte ranges of the dates in `text` with their ISO form, when valid
+pub fn find_dates(text: &str) -> Vec<(usize, usize, Option<String>)> {
+    date_regex().captures_iter(text)
+        .map(|caps| {
+            let whole = caps.get(0).unwrap();
+            (whole.start(), whole.end(), normalize_date(&caps))
+        })
+        .collect()
+}
+
+fn extract_dates(text: &str, entities: &mut ContractEntities) {
+    for caps in date_regex().captures_iter(text) {
+        let whole = caps.get(0).unwrap();
+        let (sentence_start, sentence_end) = sentence_bounds(text, whole.start(), whole.end());
+        let sentence = text[sentence_start..sentence_end].to_lowercase();
+        let value = rule_value(text, whole.start(), whole.end(), normalize_date(&caps));
+
+        if entities.effective_date.is_none()
+            && ["effective", "commence", "start", "begin"].iter().any(|k| sentence.contains(k))
+        {
+            entities.effective_date = Some(value);
+        } else if entities.termination_date.is_none()
+            && ["terminat", "expire", "expiry", "end on", "until", "ends"].iter().any(|k| sentence.contains(k))
+        {
+            entities.termination_date = Some(value);
+        }
+    }
+}
+
+fn payment_schedule(sentence: &str) -> Option<String> {
+    const SCHEDULES: [(&str, &str); 12] = [
+        ("per month", "monthly"),
+        ("monthly", "monthly"),
+        ("each month", "monthly"),
+        ("every month", "monthly"),
+        ("per year", "annually"),
+        ("annually", "annually"),
+        ("per annum", "annually"),
+        ("each year", "annually"),
+        ("quarterly", "quarterly"),
+        ("weekly", "weekly"),
+        ("per week", "weekly"),
+        ("one-off", "one-off"),
+    ];
+    SCHEDULES.iter()
+        .find(|(keyword, _)| sentence.contains(keyword))
+        .map(|(_, schedule)| schedule.to_string())
+}
+
+fn currency_code(raw: &str) -> String {
+    match raw.to_lowercase().as_str() {
+        "rwf" | "frw" | "rwandan francs" | "francs" => "RWF".to_string(),
+        "$" | "usd" | "dollars" => "USD".to_string(),
+        "€" | "eur" | "euros" => "EUR".to_string(),
+        "£" => "GBP".to_string(),
+        other => other.to_uppercase(),
+    }
+}
+
+fn extract_payments(text: &str, entities: &mut ContractEntities) {
+    for caps in amount_regex().captures_iter(text) {
+        let whole = caps.get(0).unwrap();
+        let currency = caps.get(1).or(caps.get(2)).or(caps.get(7))
+            .map(|c| currency_code(c.as_str()));
+        let digits = caps.get(3).or(caps.get(5))
+            .map(|d| d.as_str().chars().filter(|c| c.is_ascii_digit()).collect::<String>());
+        let cents = caps.get(4).or(caps.get(6)).map(|c| c.as_str().to_string());
+        let normalized = digits.map(|d| match cents {
+            Some(c) => format!("{}.{}", d, c),
+            None => d,
+        });
+
+        let (sentence_start, sentence_end) = sentence_bounds(text, whole.start(), whole.end());
+        let sentence = text[sentence_start..sentence_end].to_lowercase();
+
+        entities.payments.push(PaymentTerm {
+            amount: rule_value(text, whole.start(), whole.end(), normalized),
+            currency,
+            schedule: payment_schedule(&sentence),
+        });
+    }
+}
+
+fn extract_notice_periods(text: &str, entities: &mut ContractEntities) {
+    for caps in notice_regex().captures_iter(text) {
+        let whole = caps.get(0).unwrap();
+        let amount = caps.get(1).or(caps.get(3)).and_then(|m| number_word_value(m.as_str()));
+        let unit = caps.get(2).or(caps.get(4)).map(|m| m.as_str().to_lowercase());
+        let days = match (amount, unit.as_deref()) {
+            (Some(n), Some("day")) => Some(n),
+            (Some(n), Some("week")) => Some(n * 7),
+            (Some(n), Some("month")) => Some(n * 30),
+            _ => None,
+        };
+        entities.notice_periods.push(rule_value(
+            text,
+            whole.start(),
+            whole.end(),
+            days.map(|d| format!("{} days", d)),
+        ));
+    }
+}
+
+fn extract_renewal_terms(text: &str, entities: &mut ContractEntities) {
+    let mut offset = 0;
+    for (index, c) in text.char_indices() {
+        let end = index + c.len_utf8();
+        let ends_sentence = c == '\n' || (c == '.' && is_sentence_break(text, index));
+        if !ends_sentence && end < text.len() {
+            continue;
+        }
+        let sentence = &text[offset..end];
+        let lower = sentence.to_lowercase();
+        if lower.contains("renew") || lower.contains("automatically extend") || lower.contains("extended for") {
+            let trimmed_start = offset + (sentence.len() - sentence.trim_start().len());
+            let trimmed_end = offset + sentence.trim_end().len();
+            if trimmed_end > trimmed_start {
+                entities.renewal_terms.push(rule_value(text, trimmed_start, trimmed_end, None));
+            }
+        }
+        offset = end;
+    }
+}
+
+fn extract_law_and_forum(text: &str, entities: &mut ContractEntities) {
+    if let Some(caps) = governing_law_regex().captures(text) {
+        let country = caps.get(1).unwrap();
+        entities.governing_law = Some(rule_value(
+            text,
+            country.start(),
+            country.end(),
+            Some(country.as_str().to_string()),
+        ));
+    }
+
+    if let Some(caps) = forum_regex().captures(text) {
+        let forum = caps.get(0).unwrap();
+        entities.dispute_forum = Some(rule_value(text, forum.start(), forum.end(), None));
+    } else if let Some(start) = text.to_lowercase().find("arbitration") {
+        entities.dispute_forum = Some(rule_value(
+            text,
+            start,
+            start + "arbitration".len(),
+            Some("Arbitration".to_string()),
+        ));
+    }
+}
+
+fn extract_parties(text: &str, entities: &mut ContractEntities) {
+    for caps in party_regex().captures_iter(text) {
+        let name = caps.get(1).unwrap();
+        let role = caps.get(2).unwrap().as_str();
+        // Skip capitalised sentence openers such as "This Agreement"
+        if name.as_str().starts_with("This ") || name.as_str().starts_with("The ") {
+            continue;
+        }
+        entities.parties.push(ContractParty {
+            name: rule_value(text, name.start(), name.end(), None),
+            role: capitalize_words(role),
+        });
+    }
+}
+
+pub fn capitalize_words(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().collect::<String>() + &chars.as_str().to_lowercase(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// Deterministic extraction with regular expressions and keyword rules
+pub fn extract_with_rules(text: &str) -> ContractEntities {
+    let mut entities = ContractEntities::default();
+    extract_parties(text, &mut entities);
+    extract_dates(text, &mut entities);
+    extract_renewal_terms(text, &mut entities);
+    extract_payments(text, &mut entities);
+    extract_notice_periods(text, &mut entities);
+    extract_law_and_forum(text, &mut entities);
+    entities
+}
+
+// Shape of the JSON we ask the LLM to produce
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct LlmParty {
+    name: String,
+    role: String,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct LlmPayment {
+    amount: String,
+    schedule: Option<String>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct LlmEntities {
+    parties: Vec<LlmParty>,
+    effective_date: Option<String>,
+    termination_date: Option<String>,
+    renewal_terms: Vec<String>,
+    payments: Vec<LlmPayment>,
+    notice_periods: Vec<String>,
+    governing_law: Option<String>,
+    dispute_forum: Option<String>,
+}
+
+pub fn llm_extraction_messages(text: &str) -> Vec<ChatMessage> {
+    let excerpt: String = text.chars().take(MAX_LLM_INPUT_CHARS).collect();
+    let instructions = "Extract structured information from the contract given by the user. Respond ONLY with a JSON object with these keys: \
+\"parties\" (list of {\"name\", \"role\"}), \"effective_date\", \"termination_date\", \"renewal_terms\" (list), \
+\"payments\" (list of {\"amount\", \"schedule\"}), \"notice_periods\" (list), \"governing_law\", \"dispute_forum\". \
+Copy values exactly as they appear in the contract and use null or [] when a value is absent.";
+    injection::guarded_messages(instructions, &[("Contract", &excerpt)])
+}
+
+// Find where an LLM-reported value occurs in the original text
+fn locate(text: &str, value: &str) -> Option<SourceSpan> {
+    let needle = value.trim();
+    if needle.is_empty() {
+        return None;
+    }
+    let start = match text.find(needle) {
+        Some(start) => start,
+        // Lowercasing keeps byte offsets only for ASCII text
+        None if text.is_ascii() && needle.is_ascii() => text.to_lowercase().find(&needle.to_lowercase())?,
+        None => return None,
+    };
+    let end = start + needle.len();
+    Some(SourceSpan {
+        start,
+        end,
+        text: text[start..end].to_string(),
+    })
+}
+
+fn llm_value(text: &str, value: &str) -> Option<ExtractedValue> {
+    let value = value.trim();
+    if value.is_empty() || value.eq_ignore_ascii_case("null") {
+        return None;
+    }
+    Some(ExtractedValue {
+        value: value.to_string(),
+        normalized: None,
+        span: locate(text, value),
+        source: ExtractionSource::Llm,
+        confidence: LLM_CONFIDENCE,
+    })
+}
+
+fn overlaps(a: &Option<SourceSpan>, b: &Option<SourceSpan>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.start < b.end && b.start < a.end,
+        _ => false,
+    }
+}
+
+fn already_found(existing: &[ExtractedValue], candidate: &ExtractedValue) -> bool {
+    existing.iter().any(|e| {
+        overlaps(&e.span, &candidate.span) || e.value.eq_ignore_ascii_case(&candidate.value)
+    })
+}
+
+// Parse the LLM answer and add values the rules did not find.
+// Rule-based values always win because they come with exact spans.
+pub fn merge_llm_response(text: &str, response: &str, entities: &mut ContractEntities) -> Result<(), String> {
+    let json_start = response.find('{').ok_or("LLM response contains no JSON object")?;
+    let json_end = response.rfind('}').ok_or("LLM response contains no JSON object")?;
+    if json_end < json_start {
+        return Err("LLM response contains no JSON object".to_string());
+    }
+    let parsed: LlmEntities = serde_json::from_str(&response[json_start..=json_end])
+        .map_err(|e| format!("Could not parse LLM extraction: {}", e))?;
+
+    for party in parsed.parties {
+        if let Some(name) = llm_value(text, &party.name) {
+            let known: Vec<ExtractedValue> = entities.parties.iter().map(|p| p.name.clone()).collect();
+            if !already_found(&known, &name) {
+                entities.parties.push(ContractParty {
+                    name,
+                    role: capitalize_words(&party.role),
+                });
+            }
+        }
+    }
+
+    let fill = |slot: &mut Option<ExtractedValue>, value: Option<String>| {
+        if slot.is_none() {
+            *slot = value.and_then(|v| llm_value(text, &v));
+        }
+    };
+    fill(&mut entities.effective_date, parsed.effective_date);
+    fill(&mut entities.termination_date, parsed.termination_date);
+    fill(&mut entities.governing_law, parsed.governing_law);
+    fill(&mut entities.dispute_forum, parsed.dispute_forum);
+
+    for term in parsed.renewal_terms {
+        if let Some(value) = llm_value(text, &term) {
+            if !already_found(&entities.renewal_terms, &value) {
+                entities.renewal_terms.push(value);
+            }
+        }
+    }
+
+    for period in parsed.notice_periods {
+        if let Some(value) = llm_value(text, &period) {
+            if !already_found(&entities.notice_periods, &value) {
+                entities.notice_periods.push(value);
+            }
+        }
+    }
+
+    for payment in parsed.payments {
+        if let Some(amount) = llm_value(text, &payment.amount) {
+            let known: Vec<ExtractedValue> = entities.payments.iter().map(|p| p.amount.clone()).collect();
+            if !already_found(&known, &amount) {
+                entities.payments.push(PaymentTerm {
+                    amount,
+                    currency: None,
+                    schedule: payment.schedule,
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notice_days(text: &str) -> Vec<Option<String>> {
+        extract_with_rules(text).notice_periods.into_iter().map(|notice| notice.normalized).collect()
+    }
+
+    #[test]
+    fn extracts_notice_periods_written_as_words() {
+        for (text, days) in [
+            ("Either party may terminate with five days' notice.", "5 days"),
+            ("The tenant gives ten days notice.", "10 days"),
+            ("A notice period of twelve weeks applies.", "84 days"),
+            ("The landlord must give twenty days written notice.", "20 days"),
+            ("The employee must give sixty days notice.", "60 days"),
+            ("The employer gives 30 days' notice.", "30 days"),
+        ] {
+            assert_eq!(notice_days(text), vec![Some(days.to_string())], "{}", text);
+        }
+    }
+
+    #[test]
+    fn decimal_points_do_not_end_sentences() {
+        let entities = extract_with_rules("The Client shall pay RWF 1.5 million per month. Late fees do not apply.");
+        assert_eq!(entities.payments.len(), 1);
+        assert_eq!(entities.payments[0].schedule.as_deref(), Some("monthly"));
+
+        let entities = extract_with_rules("This lease renews each year at RWF 2.5 million. Either party may object.");
+        let renewal: Vec<&str> = entities.renewal_terms.iter().map(|term| term.value.as_str()).collect();
+        assert_eq!(renewal, vec!["This lease renews each year at RWF 2.5 million."]);
+    }
+
+    #[test]
+    fn sentences_end_at_full_stops_followed_by_space() {
+        let text = "Rent is RWF 1.5 million. The term ends on 31 December 2025.";
+        let date = text.find("31").unwrap();
+        let (start, end) = sentence_bounds(text, date, date + 2);
+        assert_eq!(&text[start..end], " The term ends on 31 December 2025");
+        let (start, end) = sentence_bounds(text, 8, 11);
+        assert_eq!(&text[start..end], "Rent is RWF 1.5 million");
+    }
+}
@@ -0,0 +1,77 @@
+use candid::{CandidType, Deserialize, Principal};
+use serde::Serialize;
+
+use crate::category::ClauseCategory;
+use crate::contract_type::ContractType;
+
+// Longest reviewer or submitter comment that is kept
+pub const MAX_COMMENT_LENGTH: usize = 2000;
+
+// Pending corrections are capped so the queue cannot be flooded
+pub const MAX_PENDING_FEEDBACK: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum FeedbackStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+// A user's correction of one clause label, with its moderation state
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct Feedback {
+    pub feedback_id: u64,
+    pub analysis_id: u64,
+    pub clause_index: u32,
+    pub clause: String,
+    // Metadata carried into the dataset record if the correction is approved
+    pub contract_type: ContractType,
+    pub category: ClauseCategory,
+    pub original_label: String,
+    pub correct_label: String,
+    pub comment: String,
+    pub submitted_by: Principal,
+    pub submitted_at: u64,
+    pub status: FeedbackStatus,
+    pub reviewed_by: Option<Principal>,
+    pub reviewed_at: Option<u64>,
+    pub review_note: Option<String>,
+}
+
+// Where a dataset record came from
+#[derive(Clone, Debug, Default, PartialEq, CandidType, Serialize, Deserialize)]
+pub enum RecordProvenance {
+    // Shipped with the canister in the embedded CSV
+    #[default]
+    Seed,
+    // An approved user correction
+    Feedback {
+        feedback_id: u64,
+        submitted_by: Principal,
+        approved_by: Principal,
+        approved_at: u64,
+    },
+    // A bulk import that created the given dataset version
+    Import {
+        version: u64,
+        imported_by: Principal,
+        imported_at: u64,
+    },
+}
+
+// Only definite labels can be used as corrections
+pub fn normalize_label(label: &str) -> Result<String, String> {
+    match label.trim().to_lowercase().as_str() {
+        "allowed" => Ok("Allowed".to_string()),
+        "not allowed" => Ok("Not Allowed".to_string()),
+        _ => Err(format!("Invalid label '{}': use 'Allowed' or 'Not Allowed'", label.trim())),
+    }
+}
+
+pub fn validate_comment(comment: &str) -> Result<String, String> {
+    let comment = comment.trim();
+    if comment.chars().count() > MAX_COMMENT_LENGTH {
+        return Err(format!("Comments are limited to {} characters", MAX_COMMENT_LENGTH));
+    }
+    Ok(comment.to_string())
+}
@@ -0,0 +1,180 @@
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+
+use crate::contract_type::ContractType;
+use crate::redaction::RedactionConfig;
+use crate::ContractAnalysisResult;
+
+// Request and response records of the HTTP gateway protocol
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    #[serde(with = "serde_bytes")]
+    pub body: Vec<u8>,
+    pub certificate_version: Option<u16>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct HttpResponse {
+    pub status_code: u16,
+    pub headers: Vec<(String, String)>,
+    #[serde(with = "serde_bytes")]
+    pub body: Vec<u8>,
+    pub upgrade: Option<bool>,
+}
+
+// REST routes served by the canister
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Route {
+    Analyze,
+    // The token returned by POST /analyze, given as `?token=`
+    GetAnalysis { analysis_id: u64, access_token: Option<String> },
+    DatasetStats,
+    Health,
+    Metrics,
+    // Calendar feed identified by its secret token
+    Calendar(String),
+    Preflight,
+    MethodNotAllowed,
+    NotFound,
+}
+
+// JSON body accepted by POST /analyze
+#[derive(Debug, Deserialize)]
+pub struct AnalyzeRequest {
+    pub contract_text: String,
+    #[serde(default)]
+    pub contract_type: Option<ContractType>,
+    #[serde(default)]
+    pub redaction: Option<RedactionConfig>,
+}
+
+// Response of POST /analyze: the analysis plus the token needed to read it
+// back with GET /analyses/{id}?token=...
+#[derive(Serialize)]
+pub struct SharedAnalysis<'a> {
+    #[serde(flatten)]
+    pub analysis: &'a ContractAnalysisResult,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_token: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: &'a str,
+}
+
+// Path of the request URL without query string or trailing slash
+pub fn request_path(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or("");
+    let trimmed = path.trim_end_matches('/');
+    if trimmed.is_empty() { "/" } else { trimmed }
+}
+
+// Value of a query string parameter; tokens are hex, so no decoding is needed
+fn query_param(url: &str, name: &str) -> Option<String> {
+    let query = url.split('#').next()?.split_once('?')?.1;
+    query.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.to_string())
+}
+
+pub fn route(method: &str, url: &str) -> Route {
+    let method = method.to_uppercase();
+    if method == "OPTIONS" {
+        return Route::Preflight;
+    }
+
+    let path = request_path(url);
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    let (route, expected_method) = match segments.as_slice() {
+        ["analyze"] => (Route::Analyze, "POST"),
+        ["analyses", id] => match id.parse() {
+            Ok(analysis_id) => (Route::GetAnalysis { analysis_id, access_token: query_param(url, "token") }, "GET"),
+            Err(_) => return Route::NotFound,
+        },
+        ["dataset", "stats"] => (Route::DatasetStats, "GET"),
+        ["health"] => (Route::Health, "GET"),
+        ["metrics"] => (Route::Metrics, "GET"),
+        ["calendar", file] => match file.strip_suffix(".ics") {
+            Some(token) if !token.is_empty() => (Route::Calendar(token.to_string()), "GET"),
+            _ => return Route::NotFound,
+        },
+        _ => return Route::NotFound,
+    };
+
+    let method_matches = method == expected_method || (expected_method == "GET" && method == "HEAD");
+    if method_matches { route } else { Route::MethodNotAllowed }
+}
+
+fn base_headers(content_type: &str) -> Vec<(String, String)> {
+    vec![
+        ("Content-Type".to_string(), content_type.to_string()),
+        ("Access-Control-Allow-Origin".to_string(), "*".to_string()),
+        ("Access-Control-Allow-Methods".to_string(), "GET, POST, OPTIONS".to_string()),
+        ("Access-Control-Allow-Headers".to_string(), "Content-Type".to_string()),
+    ]
+}
+
+pub fn json_response<T: Serialize>(status_code: u16, value: &T) -> HttpResponse {
+    match serde_json::to_vec(value) {
+        Ok(body) => HttpResponse {
+            status_code,
+            headers: base_headers("application/json"),
+            body,
+            upgrade: None,
+        },
+        Err(e) => error_response(500, &format!("Could not serialise response: {}", e)),
+    }
+}
+
+pub fn text_response(status_code: u16, content_type: &str, body: String) -> HttpResponse {
+    HttpResponse {
+        status_code,
+        headers: base_headers(content_type),
+        body: body.into_bytes(),
+        upgrade: None,
+    }
+}
+
+pub fn error_response(status_code: u16, message: &str) -> HttpResponse {
+    let body = serde_json::to_vec(&ErrorBody { error: message }).unwrap_or_default();
+    HttpResponse {
+        status_code,
+        headers: base_headers("application/json"),
+        body,
+        upgrade: None,
+    }
+}
+
+// Ask the gateway to repeat the request as an update call
+pub fn upgrade_response() -> HttpResponse {
+    HttpResponse {
+        status_code: 200,
+        headers: Vec::new(),
+        body: Vec::new(),
+        upgrade: Some(true),
+    }
+}
+
+pub fn preflight_response() -> HttpResponse {
+    HttpResponse {
+        status_code: 204,
+        headers: base_headers("text/plain"),
+        body: Vec::new(),
+        upgrade: None,
+    }
+}
+
+pub fn parse_analyze_request(body: &[u8]) -> Result<AnalyzeRequest, String> {
+    let request: AnalyzeRequest = serde_json::from_slice(body)
+        .map_err(|e| format!("Invalid JSON body: {}", e))?;
+    if request.contract_text.trim().is_empty() {
+        return Err("contract_text must not be empty".to_string());
+    }
+    Ok(request)
+}
@@ -0,0 +1,636 @@
+use std::sync::OnceLock;
+
+use candid::{CandidType, Deserialize};
+use regex::Regex;
+use serde::Serialize;
+
+use crate::docx;
+use crate::pdf;
+use crate::segment::{segment_clauses, starts_with_section_marker};
+
+// Largest document accepted; also the ingress message limit
+pub const MAX_DOCUMENT_BYTES: usize = 2 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum DocumentFormat {
+    PlainText,
+    Markdown,
+    Html,
+    Docx,
+    Pdf,
+}
+
+// A clause of an ingested document as the analyzers will see it
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct IngestedClause {
+    pub section: Option<String>,
+    // Page the clause starts on, for paginated formats
+    pub page: Option<u32>,
+    pub text: String,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct IngestedDocument {
+    pub format: DocumentFormat,
+    // Extracted text: one block per line, headings and numbering kept, table
+    // cells separated by " | "
+    pub text: String,
+    pub headings: Vec<String>,
+    pub tables: usize,
+    // Number of pages, for paginated formats
+    pub pages: Option<u32>,
+    pub clauses: Vec<IngestedClause>,
+}
+
+// Collects the lines of an extracted document
+#[derive(Default)]
+pub struct TextBuilder {
+    lines: Vec<String>,
+    // Page of each line, when the document has pages
+    line_pages: Vec<Option<u32>>,
+    current_page: Option<u32>,
+    page_count: Option<u32>,
+    headings: Vec<String>,
+    tables: usize,
+}
+
+impl TextBuilder {
+    fn push(&mut self, line: String) {
+        self.lines.push(line);
+        self.line_pages.push(self.current_page);
+    }
+
+    pub fn line(&mut self, text: &str) {
+        let line = collapse_whitespace(text);
+        if !line.is_empty() {
+            self.push(line);
+        }
+    }
+
+    pub fn heading(&mut self, text: &str) {
+        let heading = collapse_whitespace(text);
+        if !heading.is_empty() {
+            self.headings.push(heading.clone());
+            self.push(heading);
+        }
+    }
+
+    // Following lines are on the given page (counted from 1)
+    pub fn set_page(&mut self, page: u32) {
+        self.current_page = Some(page);
+    }
+
+    pub fn set_page_count(&mut self, pages: u32) {
+        self.page_count = Some(pages);
+    }
+
+    pub fn table_row(&mut self, cells: &[String]) {
+        let cells: Vec<String> = cells.iter()
+            .map(|cell| collapse_whitespace(cell))
+            .filter(|cell| !cell.is_empty())
+            .collect();
+        if !cells.is_empty() {
+            self.push(cells.join(" | "));
+        }
+    }
+
+    pub fn start_table(&mut self) {
+        self.tables += 1;
+    }
+
+    pub fn finish(self, format: DocumentFormat) -> IngestedDocument {
+        let text = self.lines.join("\n");
+        let mut line_starts = Vec::with_capacity(self.lines.len());
+        let mut offset = 0;
+        for line in &self.lines {
+            line_starts.push(offset);
+            offset += line.len() + 1;
+        }
+        let clauses = segment_clauses(&text).into_iter()
+            .map(|segment| {
+                let line = line_starts.partition_point(|&start| start <= segment.start).saturating_sub(1);
+                IngestedClause {
+                    section: segment.section,
+                    page: self.line_pages.get(line).copied().flatten(),
+                    text: segment.text.to_string(),
+                }
+            })
+            .collect();
+        IngestedDocument {
+            format,
+            text,
+            headings: self.headings,
+            tables: self.tables,
+            pages: self.page_count,
+            clauses,
+        }
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Guess the format from the content: DOCX is a zip archive, PDF has its
+// header near the start, HTML has markup
+pub fn detect_format(bytes: &[u8]) -> DocumentFormat {
+    if bytes.starts_with(b"PK\x03\x04") {
+        return DocumentFormat::Docx;
+    }
+    if bytes[..bytes.len().min(1024)].windows(5).any(|window| window == b"%PDF-") {
+        return DocumentFormat::Pdf;
+    }
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(2048)]).to_lowercase();
+    let html_markers = ["<!doctype html", "<html", "<body", "<p>", "<p ", "<div", "<h1", "<h2", "<table", "<ol", "<ul"];
+    if html_markers.iter().any(|marker| head.contains(marker)) {
+        DocumentFormat::Html
+    } else {
+        DocumentFormat::Markdown
+    }
+}
+
+// Extract the text of a document in a format the clause segmenter understands
+pub fn ingest_document(bytes: &[u8], format: Option<DocumentFormat>) -> Result<IngestedDocument, String> {
+    if bytes.is_empty() {
+        return Err("The document is empty".to_string());
+    }
+    if bytes.len() > MAX_DOCUMENT_BYTES {
+        return Err(format!("Documents are limited to {} bytes", MAX_DOCUMENT_BYTES));
+    }
+
+    let format = format.unwrap_or_else(|| detect_format(bytes));
+    let document = match format {
+        DocumentFormat::Docx => docx::extract(bytes)?.finish(format),
+        DocumentFormat::Pdf => pdf::extract(bytes)?.finish(format),
+        DocumentFormat::Html => html_to_text(&String::from_utf8_lossy(bytes)).finish(format),
+        DocumentFormat::Markdown => markdown_to_text(&String::from_utf8_lossy(bytes)).finish(format),
+        DocumentFormat::PlainText => {
+            let mut builder = TextBuilder::default();
+            for line in String::from_utf8_lossy(bytes).lines() {
+                builder.line(line);
+            }
+            builder.finish(format)
+        }
+    };
+
+    if document.clauses.is_empty() {
+        return Err("No text could be extracted from the document".to_string());
+    }
+    Ok(document)
+}
+
+// Decode the HTML character references used in practice
+pub fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semicolon) = rest.as_bytes()[..rest.len().min(12)].iter().position(|&b| b == b';') else {
+            out.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..semicolon];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            "ndash" => Some('–'),
+            "mdash" => Some('—'),
+            "lsquo" => Some('‘'),
+            "rsquo" => Some('’'),
+            "ldquo" => Some('“'),
+            "rdquo" => Some('”'),
+            "hellip" => Some('…'),
+            "sect" => Some('§'),
+            "copy" => Some('©'),
+            _ => entity.strip_prefix('#').and_then(|number| {
+                match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => number.parse().ok(),
+                }
+            }).and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[semicolon + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+// A tag as found in the HTML source
+struct Tag {
+    name: String,
+    closing: bool,
+    attributes: String,
+}
+
+fn parse_tag(source: &str) -> Tag {
+    let source = source.trim().trim_end_matches('/');
+    let closing = source.starts_with('/');
+    let source = source.trim_start_matches('/');
+    let name_end = source.find(|c: char| c.is_whitespace()).unwrap_or(source.len());
+    Tag {
+        name: source[..name_end].to_lowercase(),
+        closing,
+        attributes: source[name_end..].to_string(),
+    }
+}
+
+// Value of an attribute in the source after the tag name; attributes without a
+// value have none
+fn attribute_value(attributes: &str, name: &str) -> Option<String> {
+    let mut rest = attributes;
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return None;
+        }
+        let name_end = rest.find(|c: char| c.is_whitespace() || c == '=').unwrap_or(rest.len());
+        let attribute = &rest[..name_end];
+        rest = rest[name_end..].trim_start();
+
+        let mut value = None;
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            let (found, remaining) = match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let body = &after[1..];
+                    let end = body.find(quote).unwrap_or(body.len());
+                    (&body[..end], body.get(end + 1..).unwrap_or(""))
+                }
+                _ => after.split_at(after.find(char::is_whitespace).unwrap_or(after.len())),
+            };
+            value = Some(found);
+            rest = remaining;
+        }
+        if attribute.eq_ignore_ascii_case(name) {
+            return value.map(str::to_string);
+        }
+    }
+}
+
+struct HtmlList {
+    ordered: bool,
+    counter: u32,
+}
+
+// Convert HTML to lines: headings and paragraphs become lines, ordered list
+// items are numbered ("4.", nested "4.2"), table rows become " | " separated cells
+pub fn html_to_text(html: &str) -> TextBuilder {
+    const BLOCK_TAGS: [&str; 16] = [
+        "p", "div", "br", "section", "article", "blockquote", "pre", "header", "footer",
+        "dt", "dd", "hr", "address", "main", "aside", "nav",
+    ];
+    const SKIPPED_TAGS: [&str; 6] = ["script", "style", "head", "title", "noscript", "template"];
+
+    let mut builder = TextBuilder::default();
+    let mut current = String::new();
+    let mut heading = false;
+    let mut lists: Vec<HtmlList> = Vec::new();
+    let mut row: Option<Vec<String>> = None;
+    let mut cell: Option<String> = None;
+    let mut skipping: Option<String> = None;
+
+    let mut rest = html;
+    while !rest.is_empty() {
+        let Some(open) = rest.find('<') else {
+            if skipping.is_none() {
+                cell.as_mut().unwrap_or(&mut current).push_str(&decode_entities(rest));
+            }
+            break;
+        };
+        if skipping.is_none() {
+            cell.as_mut().unwrap_or(&mut current).push_str(&decode_entities(&rest[..open]));
+        }
+        rest = &rest[open..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let Some(close) = rest.find('>') else {
+            break;
+        };
+        let tag = parse_tag(&rest[1..close]);
+        rest = &rest[close + 1..];
+
+        if let Some(skipped) = &skipping {
+            if tag.closing && tag.name == *skipped {
+                skipping = None;
+            }
+            continue;
+        }
+        if SKIPPED_TAGS.contains(&tag.name.as_str()) && !tag.closing {
+            skipping = Some(tag.name);
+            continue;
+        }
+
+        match tag.name.as_str() {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                flush_line(&mut builder, &mut current, heading);
+                heading = !tag.closing;
+            }
+            "ol" | "ul" => {
+                flush_line(&mut builder, &mut current, heading);
+                if tag.closing {
+                    lists.pop();
+                } else {
+                    let start = attribute_value(&tag.attributes, "start").and_then(|s| s.parse::<u32>().ok()).unwrap_or(1);
+                    lists.push(HtmlList {
+                        ordered: tag.name == "ol",
+                        counter: start.saturating_sub(1),
+                    });
+                }
+            }
+            "li" => {
+                flush_line(&mut builder, &mut current, heading);
+                if !tag.closing {
+                    if let Some(list) = lists.last_mut() {
+                        list.counter += 1;
+                    }
+                    current.push_str(&list_prefix(&lists));
+                }
+            }
+            "table" => {
+                flush_line(&mut builder, &mut current, heading);
+                if !tag.closing {
+                    builder.start_table();
+                }
+            }
+            "tr" => {
+                if let Some(cells) = row.take() {
+                    builder.table_row(&cells);
+                }
+                if !tag.closing {
+                    flush_line(&mut builder, &mut current, heading);
+                    row = Some(Vec::new());
+                }
+            }
+            "td" | "th" => {
+                if let (Some(text), Some(cells)) = (cell.take(), row.as_mut()) {
+                    cells.push(text);
+                }
+                if !tag.closing && row.is_some() {
+                    cell = Some(String::new());
+                }
+            }
+            name if BLOCK_TAGS.contains(&name) => {
+                if let Some(text) = cell.as_mut() {
+                    text.push(' ');
+                } else {
+                    flush_line(&mut builder, &mut current, heading);
+                }
+            }
+            _ => {}
+        }
+    }
+    if let (Some(text), Some(cells)) = (cell.take(), row.as_mut()) {
+        cells.push(text);
+    }
+    if let Some(cells) = row.take() {
+        builder.table_row(&cells);
+    }
+    flush_line(&mut builder, &mut current, heading);
+    builder
+}
+
+fn flush_line(builder: &mut TextBuilder, current: &mut String, heading: bool) {
+    if heading {
+        builder.heading(current);
+    } else {
+        builder.line(current);
+    }
+    current.clear();
+}
+
+// "4. " for top-level ordered items, "4.2 " when nested in another ordered list;
+// bullets get no marker
+fn list_prefix(lists: &[HtmlList]) -> String {
+    match lists.last() {
+        Some(list) if list.ordered => {
+            let numbers: Vec<String> = lists.iter()
+                .filter(|l| l.ordered)
+                .map(|l| l.counter.to_string())
+                .collect();
+            if numbers.len() == 1 {
+                format!("{}. ", numbers[0])
+            } else {
+                format!("{} ", numbers.join("."))
+            }
+        }
+        _ => String::new(),
+    }
+}
+
+fn markdown_patterns() -> &'static [(Regex, &'static str)] {
+    static RE: OnceLock<Vec<(Regex, &'static str)>> = OnceLock::new();
+    RE.get_or_init(|| {
+        [
+            (r"!\[([^\]]*)\]\([^)]*\)", "$1"),
+            (r"\[([^\]]*)\]\([^)]*\)", "$1"),
+            (r"\*\*([^*]+)\*\*", "$1"),
+            (r"__([^_]+)__", "$1"),
+            (r"\*([^*\s][^*]*)\*", "$1"),
+            (r"`([^`]*)`", "$1"),
+            (r"~~([^~]+)~~", "$1"),
+        ]
+        .into_iter()
+        .map(|(pattern, replacement)| (Regex::new(pattern).expect("invalid markdown pattern"), replacement))
+        .collect()
+    })
+}
+
+fn strip_inline_markdown(text: &str) -> String {
+    markdown_patterns().iter()
+        .fold(text.to_string(), |text, (regex, replacement)| regex.replace_all(&text, *replacement).into_owned())
+}
+
+fn markdown_block_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^(?:(?P<heading>#{1,6})\s+|(?P<bullet>[-*+])\s+|(?P<quote>>\s?))")
+            .expect("invalid markdown block pattern")
+    })
+}
+
+fn is_table_separator(line: &str) -> bool {
+    let line = line.trim().trim_matches('|');
+    !line.is_empty() && line.split('|').all(|cell| {
+        let cell = cell.trim().trim_matches(':');
+        !cell.is_empty() && cell.chars().all(|c| c == '-')
+    })
+}
+
+fn is_rule(line: &str) -> bool {
+    let compact: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+    compact.len() >= 3 && ['-', '*', '_', '='].iter().any(|&c| compact.chars().all(|x| x == c))
+}
+
+// Convert Markdown to lines: heading markers, bullets, emphasis and links are
+// removed; ordered list numbers and table cells are kept. Lines wrapped within a
+// paragraph or list item are joined, so a clause is not cut at a soft wrap.
+pub fn markdown_to_text(markdown: &str) -> TextBuilder {
+    let mut builder = TextBuilder::default();
+    let mut in_code = false;
+    let mut in_table = false;
+    // The paragraph being joined, and whether it is a block quote
+    let mut paragraph = String::new();
+    let mut quoted = false;
+    let lines: Vec<&str> = markdown.lines().collect();
+
+    for (index, raw) in lines.iter().enumerate() {
+        let line = raw.trim();
+        if line.is_empty() {
+            flush_paragraph(&mut builder, &mut paragraph);
+            continue;
+        }
+        if line.starts_with("```") || line.starts_with("~~~") {
+            flush_paragraph(&mut builder, &mut paragraph);
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            builder.line(line);
+            continue;
+        }
+
+        if line.starts_with('|') || (in_table && line.contains('|')) {
+            flush_paragraph(&mut builder, &mut paragraph);
+            if is_table_separator(line) {
+                continue;
+            }
+            if !in_table {
+                builder.start_table();
+                in_table = true;
+            }
+            let cells: Vec<String> = line.trim_matches('|')
+                .split('|')
+                .map(strip_inline_markdown)
+                .collect();
+            builder.table_row(&cells);
+            continue;
+        }
+        in_table = false;
+
+        if is_rule(line) {
+            flush_paragraph(&mut builder, &mut paragraph);
+            continue;
+        }
+        // Setext headings: a line underlined with === or ---
+        let next = lines.get(index + 1).map(|l| l.trim()).unwrap_or("");
+        if is_rule(next) && !next.contains('*') && !next.contains('_') {
+            flush_paragraph(&mut builder, &mut paragraph);
+            builder.heading(&strip_inline_markdown(line));
+            continue;
+        }
+
+        let mut text = line;
+        let mut heading = false;
+        let mut bullet = false;
+        let mut quote = false;
+        while let Some(caps) = markdown_block_regex().captures(text) {
+            heading |= caps.name("heading").is_some();
+            bullet |= caps.name("bullet").is_some();
+            quote |= caps.name("quote").is_some();
+            text = &text[caps.get(0).unwrap().end()..];
+        }
+        let text = strip_inline_markdown(text.trim_end_matches('#').trim());
+        if heading {
+            flush_paragraph(&mut builder, &mut paragraph);
+            builder.heading(&text);
+            continue;
+        }
+        // Bullets and numbered items open a new block; so does entering or
+        // leaving a block quote
+        if bullet || starts_with_section_marker(&text) || quote != quoted {
+            flush_paragraph(&mut builder, &mut paragraph);
+        }
+        if !paragraph.is_empty() {
+            paragraph.push(' ');
+        }
+        paragraph.push_str(&text);
+        quoted = quote;
+    }
+    flush_paragraph(&mut builder, &mut paragraph);
+    builder
+}
+
+fn flush_paragraph(builder: &mut TextBuilder, paragraph: &mut String) {
+    builder.line(paragraph);
+    paragraph.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(builder: TextBuilder) -> Vec<String> {
+        builder.finish(DocumentFormat::Markdown).text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn reads_attribute_values() {
+        assert_eq!(attribute_value(r#" start="4" class="terms""#, "start").as_deref(), Some("4"));
+        assert_eq!(attribute_value(" class='a b' START = 7", "start").as_deref(), Some("7"));
+        assert_eq!(attribute_value(" data-start=\"9\" start=2", "start").as_deref(), Some("2"));
+        assert_eq!(attribute_value(" reversed", "reversed"), None);
+        assert_eq!(attribute_value(" restart=3", "start"), None);
+        assert_eq!(attribute_value(r#" title="x = y" start="5"#, "start").as_deref(), Some("5"));
+    }
+
+    #[test]
+    fn ordered_lists_start_where_the_html_says() {
+        let builder = html_to_text("<ol start=\"3\"><li>Rent is due monthly.</li><li>Pets are allowed.</li></ol>");
+        assert_eq!(lines(builder), vec!["3. Rent is due monthly.", "4. Pets are allowed."]);
+    }
+
+    #[test]
+    fn soft_wrapped_markdown_lines_are_joined() {
+        let markdown = "# Lease\n\
+The tenant shall pay the rent\n\
+on the first day of each month.\n\
+\n\
+1. The landlord may enter\n\
+   with 24 hours notice.\n\
+2. Pets are allowed.\n\
+- A bullet that\n\
+  wraps\n\
+> A quoted line\n\
+> that wraps\n\
+After the quote.";
+        assert_eq!(lines(markdown_to_text(markdown)), vec![
+            "Lease",
+            "The tenant shall pay the rent on the first day of each month.",
+            "1. The landlord may enter with 24 hours notice.",
+            "2. Pets are allowed.",
+            "A bullet that wraps",
+            "A quoted line that wraps",
+            "After the quote.",
+        ]);
+    }
+
+    #[test]
+    fn wrapped_clauses_stay_whole() {
+        let document = ingest_document(b"1. The tenant shall give\n60 days notice before renewal.\n2. Rent is due monthly.", Some(DocumentFormat::Markdown)).unwrap();
+        let clauses: Vec<(Option<&str>, &str)> = document.clauses.iter()
+            .map(|clause| (clause.section.as_deref(), clause.text.as_str()))
+            .collect();
+        assert_eq!(clauses, vec![
+            (Some("1"), "The tenant shall give 60 days notice before renewal"),
+            (Some("2"), "Rent is due monthly"),
+        ]);
+    }
+}
@@ -1,9 +1,70 @@
+// The crate name follows the dfx canister name
+#![allow(non_snake_case)]
+
+use std::collections::{BTreeMap, BTreeSet};
 use std::io::Cursor;
-use candid::{CandidType, Deserialize};
+use candid::{CandidType, Deserialize, Principal};
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, StableCell};
 use serde::Serialize;
 // Remove unused imports
 // Add ic-llm imports
-use ic_llm::{Model, ChatMessage, Role};
+use ic_llm::{ChatMessage, Role};
+
+mod audit;
+mod cache;
+mod calendar;
+mod category;
+mod checklist;
+mod compare;
+mod contract_type;
+mod cost;
+mod dataset;
+mod docx;
+mod evaluation;
+mod extraction;
+mod feedback;
+mod http;
+mod ingest;
+mod injection;
+mod llm;
+mod metrics;
+mod obligations;
+mod pdf;
+mod quality;
+mod redaction;
+mod reminders;
+mod report;
+mod rewrite;
+mod risk;
+mod segment;
+mod templates;
+mod upgrade;
+use audit::{AuditEvent, AuditEventKind, AuditFilter, AuditPage};
+use cache::{CacheKey, CacheStats, CachedClassification, ClassificationCache};
+use category::ClauseCategory;
+use checklist::MissingClause;
+use compare::ContractComparison;
+use contract_type::{AnalysisProfile, ContractType, ContractTypeDetection};
+use dataset::{DatasetDiff, DatasetParseError, DatasetSnapshot, DatasetVersion, ParsedDataset};
+use evaluation::{Analyzer, EvaluationReport};
+use extraction::ContractEntities;
+use cost::{CostFilter, CostLedger, CostReport, CostSummary};
+use llm::{DegradedMode, LlmError, LlmHealth, LlmUsage};
+use metrics::{CanisterMetrics, MetricsRecorder};
+use obligations::Obligation;
+use feedback::{Feedback, FeedbackStatus, RecordProvenance};
+use http::{HttpRequest, HttpResponse, Route};
+use quality::DatasetQualityReport;
+use redaction::{RedactionConfig, Redactor};
+use reminders::{Notification, Reminders};
+use report::ReportFormat;
+use rewrite::RewriteSuggestion;
+use risk::CategoryRisk;
+use segment::Segment;
+use templates::{GeneratedContract, LibraryClauseInfo, TemplateInfo};
+use ingest::{DocumentFormat, IngestedClause, IngestedDocument};
+use serde_bytes::ByteBuf;
 
 // Original greeting function
 #[ic_cdk::query]
@@ -12,27 +73,170 @@ fn greet(name: String) -> String {
 }
 
 // Define data structures for contract analysis
-#[derive(Clone, Debug, CandidType, Deserialize)]
+// Only `contract_text` and `label` are required; files with just those two
+// columns still load and the remaining metadata is left empty
+#[derive(Clone, Debug, Default, CandidType, Serialize, Deserialize)]
 struct ContractRecord {
+    // Stable identifier, derived from the wording when the source has none
+    #[serde(default)]
+    id: String,
     contract_text: String,
     label: String,
+    #[serde(default)]
+    contract_type: Option<ContractType>,
+    // Risk category; inferred from the wording when missing
+    #[serde(default)]
+    category: Option<ClauseCategory>,
+    #[serde(default)]
+    jurisdiction: Option<String>,
+    #[serde(default)]
+    language: Option<String>,
+    // Why the clause carries its label
+    #[serde(default)]
+    explanation: Option<String>,
+    // Statute or article the label is based on
+    #[serde(default)]
+    legal_reference: Option<String>,
+    // Where the clause was collected from
+    #[serde(default)]
+    source: Option<String>,
+    // Seed CSV rows have no provenance column and default to `Seed`
+    #[serde(default)]
+    provenance: RecordProvenance,
+    // Type detected from the wording of untagged records, cached when the
+    // record is loaded or imported
+    #[serde(default)]
+    detected_type: Option<ContractType>,
+}
+
+impl ContractRecord {
+    // Records belong to their type and to general contracts; untagged records
+    // are typed by their wording
+    fn matches_type(&self, contract_type: ContractType) -> bool {
+        let record_type = self.effective_type();
+        contract_type == ContractType::General || record_type == contract_type || record_type == ContractType::General
+    }
+    
+    fn effective_type(&self) -> ContractType {
+        self.contract_type
+            .or(self.detected_type)
+            .unwrap_or_else(|| contract_type::detect_contract_type(&self.contract_text).contract_type)
+    }
+    
+    // Run type detection once instead of on every classification
+    fn detect_type(&mut self) {
+        self.detected_type = match self.contract_type {
+            Some(_) => None,
+            None => Some(contract_type::detect_contract_type(&self.contract_text).contract_type),
+        };
+    }
+    
+    fn effective_category(&self) -> ClauseCategory {
+        self.category
+            .unwrap_or_else(|| category::categorize_clause(&self.contract_text))
+    }
+}
+
+// How serious a finding is for the party reviewing the contract
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, CandidType, Serialize, Deserialize)]
+enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
 }
 
 #[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
 struct ClauseAnalysis {
     clause: String,
+    // Section number the clause was found under, e.g. "4.2"
+    section: Option<String>,
+    // Page the clause starts on when it came from a PDF
+    page: Option<u32>,
     label: String,
     similarity: f64,
+    // Why the analyzer chose the label
+    reason: String,
+    category: ClauseCategory,
+    // Impact of the clause if it is unfair
+    severity: Severity,
+    // Balanced alternative wording for clauses labelled "Not Allowed"
+    suggested_rewrite: Option<String>,
+    // The clause reads like instructions to the analyser, or the model's answer
+    // to it looked steered; such clauses are never labelled by the model
+    manipulation_suspected: bool,
+}
+
+impl ClauseAnalysis {
+    fn new(clause: String, section: Option<String>, label: String, similarity: f64, reason: String) -> Self {
+        let category = category::categorize_clause(&clause);
+        let severity = risk::assess_severity(&clause, category);
+        ClauseAnalysis {
+            clause,
+            section,
+            page: None,
+            label,
+            similarity,
+            reason,
+            category,
+            severity,
+            suggested_rewrite: None,
+            manipulation_suspected: false,
+        }
+    }
 }
 
 #[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
 struct ContractAnalysisResult {
+    // Identifier under which the analysis is stored (0 until stored)
+    analysis_id: u64,
+    // Nanoseconds since the Unix epoch
+    analyzed_at: u64,
+    // Number of analysed clauses; the three counts and percentages below partition it
     total_clauses: usize,
     allowed_clauses: usize,
     not_allowed_clauses: usize,
+    unclassified_clauses: usize,
     allowed_percentage: f64,
     not_allowed_percentage: f64,
+    unclassified_percentage: f64,
+    // Severity-weighted risk from 0 to 100, see `risk::weighted_risk_score`
+    risk_score: f64,
+    risk_level: Severity,
+    category_breakdown: Vec<CategoryRisk>,
     clause_breakdown: Vec<ClauseAnalysis>,
+    contract_type: ContractType,
+    detected_contract_type: ContractTypeDetection,
+    missing_clauses: Vec<MissingClause>,
+    // At least one clause appears to address the analyser rather than the parties
+    manipulation_detected: bool,
+    // Distinct personal data values replaced by placeholders before prompting the model
+    redacted_values: usize,
+    // Dataset version and rule set that produced the labels
+    dataset_version: u64,
+    rule_set_version: u32,
+    // Set when the language model failed and the local rules labelled some clauses
+    degraded_mode: Option<DegradedMode>,
+    // Instructions and language model calls spent on this analysis
+    cost: CostSummary,
+    // What each party has to do and by when
+    obligations: Vec<Obligation>,
+}
+
+// Label and contract type counts of the loaded dataset
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+struct DatasetStats {
+    total_records: usize,
+    allowed_records: usize,
+    not_allowed_records: usize,
+    other_records: usize,
+    // Records added or relabelled through approved feedback
+    feedback_records: usize,
+    // Records added or relabelled by bulk imports
+    imported_records: usize,
+    records_by_contract_type: Vec<(ContractType, usize)>,
+    dataset_version: u64,
+    rule_set_version: u32,
 }
 
 // Add the missing ContractExplanation struct
@@ -46,8 +250,52 @@ struct ContractExplanation {
 // CSV data as embedded string
 static CSV_DATA: &str = include_str!("contract_dataset_100_unique.csv");
 
+// Number of analyses kept for reports; the oldest are dropped first
+const MAX_STORED_ANALYSES: usize = 1000;
+
+// A finished analysis together with the principal that requested it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct StoredAnalysis {
+    owner: Principal,
+    result: ContractAnalysisResult,
+    // Secret that lets anonymous HTTP clients read the analysis back
+    access_token: Option<String>,
+}
+
+type StableMemory = VirtualMemory<DefaultMemoryImpl>;
+
+// Regions of stable memory handed out by the memory manager
+const AUDIT_LOG_MEMORY: MemoryId = MemoryId::new(0);
+const UPGRADE_STATE_MEMORY: MemoryId = MemoryId::new(1);
+
 thread_local! {
-    static CONTRACT_DATASET: std::cell::RefCell<Vec<ContractRecord>> = std::cell::RefCell::new(Vec::new());
+    static CONTRACT_DATASET: std::cell::RefCell<Vec<ContractRecord>> = const { std::cell::RefCell::new(Vec::new()) };
+    static ANALYSES: std::cell::RefCell<BTreeMap<u64, StoredAnalysis>> = const { std::cell::RefCell::new(BTreeMap::new()) };
+    static NEXT_ANALYSIS_ID: std::cell::Cell<u64> = const { std::cell::Cell::new(1) };
+    static LEGAL_REVIEWERS: std::cell::RefCell<BTreeSet<Principal>> = const { std::cell::RefCell::new(BTreeSet::new()) };
+    static FEEDBACK: std::cell::RefCell<BTreeMap<u64, Feedback>> = const { std::cell::RefCell::new(BTreeMap::new()) };
+    static NEXT_FEEDBACK_ID: std::cell::Cell<u64> = const { std::cell::Cell::new(1) };
+    static DATASET_VERSIONS: std::cell::RefCell<BTreeMap<u64, DatasetSnapshot>> = const { std::cell::RefCell::new(BTreeMap::new()) };
+    static CURRENT_DATASET_VERSION: std::cell::Cell<u64> = const { std::cell::Cell::new(0) };
+    static CLASSIFICATION_CACHE: std::cell::RefCell<ClassificationCache> = const { std::cell::RefCell::new(ClassificationCache::new()) };
+    static LLM_BREAKER: std::cell::RefCell<llm::CircuitBreaker> = const { std::cell::RefCell::new(llm::CircuitBreaker::new()) };
+    static METRICS: std::cell::RefCell<MetricsRecorder> = const { std::cell::RefCell::new(MetricsRecorder::new()) };
+    static COST_LEDGER: std::cell::RefCell<CostLedger> = const { std::cell::RefCell::new(BTreeMap::new()) };
+    // Secret calendar feed tokens and the principal whose obligations they serve
+    static CALENDAR_FEEDS: std::cell::RefCell<BTreeMap<String, Principal>> = const { std::cell::RefCell::new(BTreeMap::new()) };
+    static REMINDERS: std::cell::RefCell<Reminders> = const { std::cell::RefCell::new(Reminders::new()) };
+    
+    // Kept in stable memory so the audit trail survives upgrades
+    static MEMORY_MANAGER: std::cell::RefCell<MemoryManager<DefaultMemoryImpl>> =
+        std::cell::RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+    static AUDIT_LOG: std::cell::RefCell<StableBTreeMap<u64, AuditEvent, StableMemory>> = std::cell::RefCell::new(
+        StableBTreeMap::init(MEMORY_MANAGER.with(|manager| manager.borrow().get(AUDIT_LOG_MEMORY)))
+    );
+    // `upgrade::UpgradeState` as JSON, empty between upgrades
+    static UPGRADE_STATE: std::cell::RefCell<StableCell<Vec<u8>, StableMemory>> = std::cell::RefCell::new(
+        StableCell::init(MEMORY_MANAGER.with(|manager| manager.borrow().get(UPGRADE_STATE_MEMORY)), Vec::new())
+            .expect("failed to initialize upgrade state memory")
+    );
 }
 
 // Initialize the canister and load dataset
@@ -55,6 +303,78 @@ thread_local! {
 fn init() {
     ic_cdk::println!("Initializing contract analyzer with CSV dataset");
     load_contract_dataset();
+    commit_dataset_change("Initial dataset".to_string(), |_| ());
+    start_reminder_timer();
+}
+
+#[ic_cdk::pre_upgrade]
+fn pre_upgrade() {
+    let bytes = upgrade::encode(&upgrade::take());
+    UPGRADE_STATE.with(|cell| cell.borrow_mut().set(bytes)).expect("failed to save upgrade state");
+}
+
+// The dataset, its versions and everything built on them are restored, so
+// analyses keep pointing at the dataset versions they were made with. Timers
+// do not survive upgrades.
+#[ic_cdk::post_upgrade]
+fn post_upgrade() {
+    let bytes = UPGRADE_STATE.with(|cell| cell.borrow_mut().set(Vec::new())).expect("failed to clear upgrade state");
+    if bytes.is_empty() {
+        // Nothing was saved when upgrading from a version without pre_upgrade
+        load_contract_dataset();
+        commit_dataset_change("Initial dataset".to_string(), |_| ());
+    } else {
+        upgrade::restore(upgrade::decode(&bytes));
+    }
+    start_reminder_timer();
+}
+
+fn start_reminder_timer() {
+    ic_cdk_timers::set_timer_interval(reminders::SCAN_INTERVAL, || {
+        scan_reminders();
+    });
+}
+
+// Scan every stored analysis for obligations coming due
+fn scan_reminders() -> u32 {
+    ANALYSES.with(|analyses| {
+        let analyses = analyses.borrow();
+        let obligations = analyses.iter()
+            .map(|(analysis_id, stored)| (stored.owner, *analysis_id, stored.result.obligations.as_slice()));
+        REMINDERS.with(|reminders| reminders.borrow_mut().scan(obligations, ic_cdk::api::time()))
+    })
+}
+
+// Apply a change to the dataset and record the outcome as a new version
+fn commit_dataset_change<R>(description: String, change: impl FnOnce(&mut Vec<ContractRecord>) -> R) -> (R, DatasetVersion) {
+    let (outcome, records) = CONTRACT_DATASET.with(|dataset| {
+        let mut dataset = dataset.borrow_mut();
+        let outcome = change(&mut dataset);
+        (outcome, dataset.clone())
+    });
+    CLASSIFICATION_CACHE.with(|cache| cache.borrow_mut().invalidate());
+    
+    let version = CURRENT_DATASET_VERSION.with(|current| {
+        let version = current.get() + 1;
+        current.set(version);
+        version
+    });
+    let info = DatasetVersion {
+        version,
+        author: ic_cdk::caller(),
+        created_at: ic_cdk::api::time(),
+        description,
+        record_count: records.len(),
+    };
+    DATASET_VERSIONS.with(|versions| {
+        let mut versions = versions.borrow_mut();
+        versions.insert(version, DatasetSnapshot { info: info.clone(), records });
+        while versions.len() > dataset::MAX_DATASET_VERSIONS {
+            versions.pop_first();
+        }
+    });
+    
+    (outcome, info)
 }
 
 // Function to load dataset from CSV
@@ -66,25 +386,9 @@ fn load_contract_dataset() {
     let preview = if CSV_DATA.len() > 50 { &CSV_DATA[0..50] } else { CSV_DATA };
     ic_cdk::println!("CSV preview: {}", preview);
     
-    let mut reader = csv::ReaderBuilder::new()
-        .has_headers(true)
-        .flexible(true)  // Be more flexible with CSV parsing
-        .from_reader(Cursor::new(CSV_DATA));
-    
-    let mut records = Vec::new();
-    
-    for result in reader.deserialize() {
-        match result {
-            Ok(record) => {
-                let record: ContractRecord = record;
-                if !record.contract_text.is_empty() && !record.label.is_empty() {
-                    records.push(record);
-                }
-            },
-            Err(e) => {
-                ic_cdk::println!("Error parsing CSV record: {}", e);
-            }
-        }
+    let ParsedDataset { records, errors, .. } = parse_contract_dataset(CSV_DATA);
+    for e in errors {
+        ic_cdk::println!("Error parsing CSV row {}: {}", e.row, e.message);
     }
     
     ic_cdk::println!("CSV parsing complete. Found {} records", records.len());
@@ -100,30 +404,89 @@ fn load_contract_dataset() {
     }
 }
 
+// Parse CSV records, keeping the line of every record and every rejected row
+fn parse_contract_dataset(csv_data: &str) -> ParsedDataset {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .flexible(true)  // Be more flexible with CSV parsing
+        .from_reader(Cursor::new(csv_data));
+    
+    let mut parsed = ParsedDataset::default();
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(e) => {
+            parsed.errors.push(DatasetParseError { row: 1, message: e.to_string() });
+            return parsed;
+        }
+    };
+    
+    let mut row = csv::StringRecord::new();
+    loop {
+        match reader.read_record(&mut row) {
+            Ok(false) => break,
+            Ok(true) => {
+                let line = row.position().map(|p| p.line()).unwrap_or(0);
+                match row.deserialize::<ContractRecord>(Some(&headers)) {
+                    Ok(record) if record.contract_text.trim().is_empty() || record.label.trim().is_empty() => {
+                        parsed.errors.push(DatasetParseError {
+                            row: line,
+                            message: "contract_text and label must not be empty".to_string(),
+                        });
+                    },
+                    Ok(mut record) => {
+                        if record.id.trim().is_empty() {
+                            record.id = dataset::record_id(&record.contract_text);
+                        }
+                        record.detect_type();
+                        parsed.records.push(record);
+                        parsed.rows.push(line);
+                    },
+                    Err(e) => {
+                        parsed.errors.push(DatasetParseError { row: line, message: e.to_string() });
+                    }
+                }
+            },
+            Err(e) => {
+                let line = e.position().map(|p| p.line()).unwrap_or(0);
+                parsed.errors.push(DatasetParseError { row: line, message: e.to_string() });
+            }
+        }
+    }
+    
+    parsed
+}
+
 // Fallback data in case CSV loading fails
 fn load_fallback_data() {
-    let fallback_data = vec![
+    let mut fallback_data = vec![
         ContractRecord {
             contract_text: "The tenant shall maintain the property in good condition".to_string(),
             label: "Allowed".to_string(),
+            ..Default::default()
         },
         ContractRecord {
             contract_text: "The tenant shall pay a late fee of 20% for each day of delay".to_string(),
             label: "Not Allowed".to_string(),
+            ..Default::default()
         },
         ContractRecord {
             contract_text: "Either party may terminate this agreement with 30 days notice".to_string(),
             label: "Allowed".to_string(),
+            ..Default::default()
         },
         ContractRecord {
             contract_text: "The landlord may enter the premises at any time without notice".to_string(),
             label: "Not Allowed".to_string(),
+            ..Default::default()
         },
         ContractRecord {
             contract_text: "Rent shall be paid on the first day of each month".to_string(),
             label: "Allowed".to_string(),
+            ..Default::default()
         },
     ];
+    dataset::assign_missing_ids(&mut fallback_data);
+    fallback_data.iter_mut().for_each(ContractRecord::detect_type);
     
     CONTRACT_DATASET.with(|dataset| {
         *dataset.borrow_mut() = fallback_data;
@@ -153,115 +516,213 @@ fn text_similarity(text1: &str, text2: &str) -> f64 {
     common_words as f64 / max_words as f64
 }
 
+// Split contract text into candidate clauses
+fn split_clauses(contract_text: &str) -> Vec<Segment<'_>> {
+    segment::segment_clauses(contract_text)
+}
+
 // Function to classify a clause based on similarity to dataset records
-fn classify_clause(clause: &str) -> (String, f64) {
+// of the same contract type (the whole dataset is used for general contracts).
+// The matched record is returned when it decided the label.
+fn classify_clause<'a>(
+    clause: &str,
+    contract_type: ContractType,
+    dataset: &'a [ContractRecord],
+) -> (String, f64, Option<&'a ContractRecord>) {
     let mut max_similarity = 0.0;
     let mut best_label = "Unclassified".to_string();
+    let mut best_record = None;
     
-    CONTRACT_DATASET.with(|dataset| {
-        for record in dataset.borrow().iter() {
-            let similarity = text_similarity(clause, &record.contract_text);
-            
-            if similarity > max_similarity {
-                max_similarity = similarity;
-                best_label = if similarity >= 0.5 {
-                    record.label.clone()
-                } else {
-                    "Unclassified".to_string()
-                };
-            }
+    let mut subset: Vec<&ContractRecord> = dataset.iter()
+        .filter(|record| record.matches_type(contract_type))
+        .collect();
+    if subset.is_empty() {
+        subset = dataset.iter().collect();
+    }
+    
+    for record in subset {
+        let similarity = text_similarity(clause, &record.contract_text);
+        
+        if similarity > max_similarity {
+            max_similarity = similarity;
+            (best_label, best_record) = if similarity >= 0.5 {
+                (record.label.clone(), Some(record))
+            } else {
+                ("Unclassified".to_string(), None)
+            };
         }
-    });
+    }
     
-    (best_label, max_similarity)
+    (best_label, max_similarity, best_record)
 }
 
-// Enhanced analyzer with rule-based patterns for better classification
-fn enhanced_analyze(contract_text: &str) -> Result<Vec<ClauseAnalysis>, String> {
-    let clauses: Vec<&str> = contract_text
-        .split(|c| c == '.' || c == '\n')
-        .filter(|s| !s.trim().is_empty())
-        .collect();
+// Model requested from the LLM canister, also recorded in classification cache keys
+const LLM_MODEL_NAME: &str = "llama3.1:8b";
+
+// Version of the classification rules: bump it whenever the patterns below,
+// the contract type profiles or the similarity thresholds change
+const RULE_SET_VERSION: u32 = 4;
+
+// Generic patterns suggesting unfair terms
+const UNFAIR_PATTERNS: &[&str] = &[
+    "at any time",
+    "without notice",
+    "without consent",
+    "without reason",
+    "unlimited",
+    "no obligation",
+    "may not request",
+    "not entitled",
+    "not responsible",
+    "not liable",
+];
+
+// Extreme penalties or one-sided terms
+const PENALTY_PATTERNS: &[&str] = &[
+    "immediate termination",
+    "forfeit",
+    "waive all rights",
+    "no refund",
+    "20%",
+    "25%",
+    "30%",
+];
+
+// Positive patterns suggesting fair terms
+const FAIR_PATTERNS: &[&str] = &[
+    "right to",
+    "entitled to",
+    "reasonable",
+    "mutual",
+    "agreed",
+    "notice",
+    "consent",
+];
+
+// First pattern that occurs in the clause, skipping the exempt ones
+fn first_match<'a>(lower_clause: &str, patterns: &[&'a str], exempt: &[&str]) -> Option<&'a str> {
+    patterns.iter()
+        .filter(|pattern| !exempt.contains(pattern))
+        .find(|pattern| lower_clause.contains(*pattern))
+        .copied()
+}
+
+// Explanation for a label that came from dataset similarity
+fn similarity_reason(label: &str, similarity: f64, matched: Option<&ContractRecord>) -> String {
+    if label == "Unclassified" {
+        return "No sufficiently similar clause in the dataset".to_string();
+    }
     
-    ic_cdk::println!("Analyzing {} clauses with enhanced rules", clauses.len());
+    let mut reason = format!("Most similar dataset clause is labelled {} (similarity {:.2})", label, similarity);
+    if let Some(explanation) = matched.and_then(|r| r.explanation.as_deref()) {
+        reason.push_str(&format!(": {}", explanation.trim_end_matches('.')));
+    }
+    if let Some(reference) = matched.and_then(|r| r.legal_reference.as_deref()) {
+        reason.push_str(&format!(" ({})", reference));
+    }
+    reason
+}
+
+fn quote_terms(terms: &[&str]) -> String {
+    terms.iter()
+        .map(|term| format!("'{}'", term))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+// Classify a single clause by combining the rule patterns with dataset similarity.
+// Returns the label, its confidence and the reason for it.
+fn rule_classify_clause(
+    clause_text: &str,
+    profile: &AnalysisProfile,
+    dataset: &[ContractRecord],
+) -> (String, f64, String) {
+    let lower_clause = clause_text.to_lowercase();
+    let mut score = 0.0;
+    let mut unfair_terms = Vec::new();
+    let mut fair_terms = Vec::new();
     
-    let mut clause_analyses = Vec::new();
+    if let Some(term) = first_match(&lower_clause, UNFAIR_PATTERNS, profile.exempt_patterns) {
+        score -= 0.3;
+        unfair_terms.push(term);
+    }
     
-    for clause in clauses {
-        let clause_text = clause.trim().to_string();
-        if clause_text.is_empty() {
-            continue;
-        }
-        
-        let lower_clause = clause_text.to_lowercase();
-        let mut score = 0.0;
-        
-        // Patterns suggesting unfair terms
-        if lower_clause.contains("at any time") ||
-           lower_clause.contains("without notice") ||
-           lower_clause.contains("without consent") ||
-           lower_clause.contains("without reason") ||
-           lower_clause.contains("unlimited") ||
-           lower_clause.contains("no obligation") ||
-           lower_clause.contains("may not request") ||
-           lower_clause.contains("not entitled") ||
-           lower_clause.contains("not responsible") ||
-           lower_clause.contains("not liable") {
-            score -= 0.3;
-        }
-        
-        // Extreme penalties or one-sided terms
-        if lower_clause.contains("immediate termination") ||
-           lower_clause.contains("forfeit") ||
-           lower_clause.contains("waive all rights") ||
-           lower_clause.contains("no refund") ||
-           lower_clause.contains("20%") ||
-           lower_clause.contains("25%") ||
-           lower_clause.contains("30%") {
-            score -= 0.3;
-        }
-        
-        // Positive patterns suggesting fair terms
-        if lower_clause.contains("right to") ||
-           lower_clause.contains("entitled to") ||
-           lower_clause.contains("reasonable") ||
-           lower_clause.contains("mutual") ||
-           lower_clause.contains("agreed") ||
-           lower_clause.contains("notice") ||
-           lower_clause.contains("consent") {
-            score += 0.3;
-        }
-        
-        let (base_label, base_similarity) = classify_clause(&clause_text);
-        
-        let mut final_similarity = base_similarity;
-        let mut final_label = base_label;
-        
-        // If rule-based approach has a strong signal, use it
-        if score <= -0.5 {
+    if let Some(term) = first_match(&lower_clause, PENALTY_PATTERNS, profile.exempt_patterns) {
+        score -= 0.3;
+        unfair_terms.push(term);
+    }
+    
+    // Terms that are unfair for this particular kind of contract
+    if let Some(term) = contract_type::find_phrase(clause_text, profile.unfair_patterns) {
+        score -= 0.3;
+        unfair_terms.push(term);
+    }
+    
+    if let Some(term) = first_match(&lower_clause, FAIR_PATTERNS, profile.exempt_patterns) {
+        score += 0.3;
+        fair_terms.push(term);
+    }
+    
+    // Terms that are balanced for this particular kind of contract
+    if let Some(term) = contract_type::find_phrase(clause_text, profile.fair_patterns) {
+        score += 0.3;
+        fair_terms.push(term);
+    }
+    
+    let (base_label, base_similarity, matched) = classify_clause(clause_text, profile.contract_type, dataset);
+    
+    let mut final_reason = similarity_reason(&base_label, base_similarity, matched);
+    let mut final_similarity = base_similarity;
+    let mut final_label = base_label;
+    
+    let unfair_reason = format!("Contains one-sided wording: {}", quote_terms(&unfair_terms));
+    let fair_reason = format!("Contains balanced wording: {}", quote_terms(&fair_terms));
+    
+    // If rule-based approach has a strong signal, use it
+    if score <= -0.5 {
+        final_label = "Not Allowed".to_string();
+        final_similarity = 0.85; // Confident enough
+        final_reason = unfair_reason;
+    } else if score >= 0.5 {
+        final_label = "Allowed".to_string();
+        final_similarity = 0.85; // Confident enough
+        final_reason = fair_reason;
+    } else if base_similarity < 0.6 {
+        // If similarity is low but we have some rule-based signal
+        if score < -0.2 {
             final_label = "Not Allowed".to_string();
-            final_similarity = 0.85; // Confident enough
-        } else if score >= 0.5 {
+            final_similarity = 0.7;
+            final_reason = unfair_reason;
+        } else if score > 0.2 {
             final_label = "Allowed".to_string();
-            final_similarity = 0.85; // Confident enough
-        } else if base_similarity < 0.6 {
-            // If similarity is low but we have some rule-based signal
-            if score < -0.2 {
-                final_label = "Not Allowed".to_string();
-                final_similarity = 0.7;
-            } else if score > 0.2 {
-                final_label = "Allowed".to_string();
-                final_similarity = 0.7;
-            }
+            final_similarity = 0.7;
+            final_reason = fair_reason;
         }
-        
-        clause_analyses.push(ClauseAnalysis {
-            clause: clause_text,
-            label: final_label,
-            similarity: final_similarity,
-        });
     }
     
+    (final_label, final_similarity, final_reason)
+}
+
+// Enhanced analyzer with rule-based patterns for better classification
+fn enhanced_analyze(contract_text: &str, profile: &AnalysisProfile) -> Result<Vec<ClauseAnalysis>, String> {
+    let clauses = split_clauses(contract_text);
+    let mut clause_analyses = Vec::new();
+    
+    CONTRACT_DATASET.with(|dataset| {
+        let dataset = dataset.borrow();
+        for clause in clauses {
+            let clause_text = clause.text.trim().to_string();
+            if clause_text.is_empty() {
+                continue;
+            }
+            
+            let (label, similarity, reason) = rule_classify_clause(&clause_text, profile, &dataset);
+            record_analyzer("rules");
+            
+            clause_analyses.push(ClauseAnalysis::new(clause_text, clause.section, label, similarity, reason));
+        }
+    });
+    
     if clause_analyses.is_empty() {
         Err("No clauses were successfully analyzed".to_string())
     } else {
@@ -269,280 +730,1267 @@ fn enhanced_analyze(contract_text: &str) -> Result<Vec<ClauseAnalysis>, String>
     }
 }
 
-// Enhanced analyzer with LLM-based classification
-async fn llm_analyze(contract_text: &str) -> Result<Vec<ClauseAnalysis>, String> {
-    let clauses: Vec<&str> = contract_text
-        .split(|c| c == '.' || c == '\n')
-        .filter(|s| !s.trim().is_empty())
-        .collect();
+// Clauses of fewer than 3 words, such as headings, are labelled "Neutral" by
+// the LLM analyzer without asking the model
+fn too_short_to_assess(clause_text: &str) -> bool {
+    clause_text.split_whitespace().count() < 3
+}
+
+// Enhanced analyzer with LLM-based classification. Clauses the language model
+// fails on are labelled by the local rules, and the result reports the degradation.
+async fn llm_analyze(
+    contract_text: &str,
+    profile: &AnalysisProfile,
+    redactor: &mut Redactor,
+    usage: &mut LlmUsage,
+) -> Result<(Vec<ClauseAnalysis>, Option<DegradedMode>), String> {
+    let clauses = split_clauses(contract_text);
     
     ic_cdk::println!("Analyzing {} clauses with LLM", clauses.len());
     
     let mut clause_analyses = Vec::new();
+    let mut degraded: Option<DegradedMode> = None;
     
     for clause in clauses {
-        let clause_text = clause.trim().to_string();
+        let clause_text = clause.text.trim().to_string();
         if clause_text.is_empty() {
             continue;
         }
         
-        // Check if the clause has fewer than 3 words
-        let word_count = clause_text.split_whitespace().count();
-        if word_count < 3 {
-            clause_analyses.push(ClauseAnalysis {
-                clause: clause_text,
-                label: "Neutral".to_string(),
-                similarity: 0.5, // Medium confidence
-            });
+        if too_short_to_assess(&clause_text) {
+            clause_analyses.push(ClauseAnalysis::new(
+                clause_text,
+                clause.section,
+                "Neutral".to_string(),
+                0.5, // Medium confidence
+                "Too short to assess".to_string(),
+            ));
             continue;
         }
         
-        // Use LLM to classify the clause
-        let prompt = format!(
-            "Analyze this contract clause and respond ONLY with either 'Allowed' or 'Not Allowed', and make sure the response appears as following Rules and Regulation in Rwanda: '{}'",
-            clause_text
-        );
-        
-        let response = ic_llm::prompt(Model::Llama3_1_8B, prompt).await;
-        let cleaned_response = clean_llm_response(response);
-        
-        // Determine the label from LLM response
-        let label = if cleaned_response.to_lowercase().contains("not allowed") {
-            "Not Allowed".to_string()
-        } else if cleaned_response.to_lowercase().contains("allowed") {
-            "Allowed".to_string()
-        } else {
-            // Fallback if LLM response is unclear
-            "Unclassified".to_string()
-        };
-        
-        clause_analyses.push(ClauseAnalysis {
-            clause: clause_text,
-            label,
-            similarity: 0.9, // High confidence for LLM classification
-        });
+        match llm_clause_analysis(clause_text.clone(), clause.section.clone(), profile, redactor, usage).await {
+            Ok(analysis) => clause_analyses.push(analysis),
+            Err(error) => {
+                record_analyzer("rules");
+                // Once the circuit breaker opens, the remaining clauses fail fast
+                degraded.get_or_insert_with(|| DegradedMode::new(&error)).fallback_clauses += 1;
+                let (label, similarity, reason) = CONTRACT_DATASET.with(|dataset| {
+                    rule_classify_clause(&clause_text, profile, &dataset.borrow())
+                });
+                clause_analyses.push(ClauseAnalysis::new(clause_text, clause.section, label, similarity, reason));
+            }
+        }
     }
     
     if clause_analyses.is_empty() {
         Err("No clauses were successfully analyzed".to_string())
     } else {
-        Ok(clause_analyses)
+        Ok((clause_analyses, degraded))
     }
 }
 
-// Update the analyze_contract function to use our LLM analyzer
-#[ic_cdk::update]
-async fn analyze_contract(contract_text: String) -> ContractAnalysisResult {
-    let clauses: Vec<&str> = contract_text
-        .split(|c| c == '.' || c == '\n')
-        .filter(|s| !s.trim().is_empty())
-        .collect();
+// Analyse one clause with the language model. Clauses written to steer the model,
+// and answers that look steered, are labelled by the local rules instead.
+// Personal data is replaced by placeholders in the prompt and restored in the reason.
+async fn llm_clause_analysis(
+    clause_text: String,
+    section: Option<String>,
+    profile: &AnalysisProfile,
+    redactor: &mut Redactor,
+    usage: &mut LlmUsage,
+) -> Result<ClauseAnalysis, LlmError> {
+    let input_findings = injection::detect_input_injection(&clause_text);
+    if !input_findings.is_empty() {
+        return Ok(guarded_rule_analysis(clause_text, section, profile, &input_findings));
+    }
     
-    let total_clauses = clauses.len();
+    // Boilerplate recurs across contracts, so answers are cached by the wording sent to
+    // the model. Reasons are kept with their placeholders and restored per request.
+    let prompt_text = redactor.redact(&clause_text);
+    let key = CacheKey::new("llm", LLM_MODEL_NAME, RULE_SET_VERSION, profile.contract_type, &prompt_text);
+    if let Some(cached) = CLASSIFICATION_CACHE.with(|cache| cache.borrow_mut().get(&key)) {
+        record_analyzer("llm_cache");
+        return Ok(ClauseAnalysis::new(clause_text, section, cached.label, cached.confidence, redactor.restore(&cached.reason)));
+    }
     
-    // Try LLM analysis first
-    match llm_analyze(&contract_text).await {
-        Ok(analyses) => {
-            let clause_breakdown = analyses;
-            let allowed_count = clause_breakdown.iter()
-                .filter(|ca| ca.label == "Allowed")
-                .count();
-            let not_allowed_count = clause_breakdown.iter()
-                .filter(|ca| ca.label == "Not Allowed")
-                .count();
-            
-            // Calculate percentages
-            let allowed_percentage = if total_clauses > 0 {
-                (allowed_count as f64 / total_clauses as f64) * 100.0
-            } else {
-                0.0
-            };
-            
-            let not_allowed_percentage = if total_clauses > 0 {
-                (not_allowed_count as f64 / total_clauses as f64) * 100.0
-            } else {
-                0.0
-            };
-            
-            ContractAnalysisResult {
-                total_clauses,
-                allowed_clauses: allowed_count,
-                not_allowed_clauses: not_allowed_count,
-                allowed_percentage,
-                not_allowed_percentage,
-                clause_breakdown,
-            }
-        },
-        // Fallback to rule-based analysis if LLM analysis fails
-        Err(_) => {
-            match enhanced_analyze(&contract_text) {
-                Ok(analyses) => {
-                    let clause_breakdown = analyses;
-                    let allowed_count = clause_breakdown.iter()
-                        .filter(|ca| ca.label == "Allowed")
-                        .count();
-                    let not_allowed_count = clause_breakdown.iter()
-                        .filter(|ca| ca.label == "Not Allowed")
-                        .count();
-                    
-                    // Calculate percentages
-                    let allowed_percentage = if total_clauses > 0 {
-                        (allowed_count as f64 / total_clauses as f64) * 100.0
-                    } else {
-                        0.0
-                    };
-                    
-                    let not_allowed_percentage = if total_clauses > 0 {
-                        (not_allowed_count as f64 / total_clauses as f64) * 100.0
-                    } else {
-                        0.0
-                    };
-                    
-                    ContractAnalysisResult {
-                        total_clauses,
-                        allowed_clauses: allowed_count,
-                        not_allowed_clauses: not_allowed_count,
-                        allowed_percentage,
-                        not_allowed_percentage,
-                        clause_breakdown,
-                    }
-                },
-                Err(_) => {
-                    // Final fallback to simple similarity-based analysis
-                    fallback_analyze_contract(contract_text)
-                }
-            }
-        }
+    let (label, reason, output_findings) = llm_classify_clause(&prompt_text, profile, usage).await?;
+    if !output_findings.is_empty() {
+        return Ok(guarded_rule_analysis(clause_text, section, profile, &output_findings));
     }
+    
+    let confidence = 0.9; // High confidence for LLM classification
+    let cached = CachedClassification {
+        label: label.clone(),
+        reason: reason.clone(),
+        confidence,
+    };
+    CLASSIFICATION_CACHE.with(|cache| cache.borrow_mut().insert(key, cached));
+    record_analyzer("llm");
+    
+    Ok(ClauseAnalysis::new(clause_text, section, label, confidence, redactor.restore(&reason)))
 }
 
-// Original analysis method as fallback
-fn fallback_analyze_contract(contract_text: String) -> ContractAnalysisResult {
-    let clauses: Vec<&str> = contract_text
-        .split(|c| c == '.' || c == '\n')
-        .filter(|s| !s.trim().is_empty())
-        .collect();
+// Rule-based analysis of a clause the language model must not decide
+fn guarded_rule_analysis(
+    clause_text: String,
+    section: Option<String>,
+    profile: &AnalysisProfile,
+    findings: &[String],
+) -> ClauseAnalysis {
+    let (label, similarity, reason) = CONTRACT_DATASET.with(|dataset| {
+        rule_classify_clause(&clause_text, profile, &dataset.borrow())
+    });
+    record_analyzer("rules");
+    let reason = format!(
+        "Possible prompt injection ({}), classified without the language model. {}",
+        findings.join(", "),
+        reason
+    );
+    let mut analysis = ClauseAnalysis::new(clause_text, section, label, similarity, reason);
+    analysis.manipulation_suspected = true;
+    analysis
+}
+
+// Label and reason for a single clause from the language model, plus any signs
+// that the answer was steered by the clause text
+async fn llm_classify_clause(
+    clause_text: &str,
+    profile: &AnalysisProfile,
+    usage: &mut LlmUsage,
+) -> Result<(String, String, Vec<String>), LlmError> {
+    let instructions = format!(
+        "{} Analyze the contract clause given by the user and respond with either 'Allowed' or 'Not Allowed' on the first line and a one-sentence reason on the second line, and make sure the response appears as following Rules and Regulation in Rwanda.",
+        profile.prompt_context
+    );
+    let messages = injection::guarded_messages(&instructions, &[("Clause", clause_text)]);
     
-    let total_clauses = clauses.len();
+    let response = llm::chat_metered(messages, usage).await?;
+    let cleaned_response = clean_llm_response(response);
+    let output_findings = injection::detect_output_injection(&cleaned_response);
     
-    let mut clause_breakdown = Vec::new();
-    let mut allowed_count = 0;
-    let mut not_allowed_count = 0;
+    // Determine the label from the first line of the LLM response
+    let mut response_lines = cleaned_response.lines();
+    let verdict = response_lines.next().unwrap_or("").to_lowercase();
+    let label = if verdict.contains("not allowed") {
+        "Not Allowed".to_string()
+    } else if verdict.contains("allowed") {
+        "Allowed".to_string()
+    } else {
+        // Fallback if LLM response is unclear
+        "Unclassified".to_string()
+    };
     
-    for clause in clauses {
-        let clause_text = clause.trim().to_string();
-        if clause_text.is_empty() {
-            continue;
-        }
-        
-        let (label, similarity) = classify_clause(&clause_text);
-        
-        match label.as_str() {
-            "Allowed" => allowed_count += 1,
-            "Not Allowed" => not_allowed_count += 1,
-            _ => {}
+    let reason = response_lines
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(|line| line.trim_start_matches(|c: char| c == '-' || c == ':' || c == '*' || c.is_whitespace()).to_string())
+        .filter(|line| !line.is_empty())
+        .unwrap_or_else(|| "Assessed by the language model".to_string());
+    
+    Ok((label, reason, output_findings))
+}
+
+// Build the result summary from the per-clause breakdown
+fn build_analysis_result(
+    mut clause_breakdown: Vec<ClauseAnalysis>,
+    contract_type: ContractType,
+    detected_contract_type: ContractTypeDetection,
+) -> ContractAnalysisResult {
+    // Every analysed clause is counted exactly once, so the percentages add up to 100
+    let total_clauses = clause_breakdown.len();
+    let allowed_count = clause_breakdown.iter()
+        .filter(|ca| ca.label == "Allowed")
+        .count();
+    let not_allowed_count = clause_breakdown.iter()
+        .filter(|ca| ca.label == "Not Allowed")
+        .count();
+    let unclassified_count = total_clauses - allowed_count - not_allowed_count;
+    
+    // Calculate percentages
+    let percentage = |count: usize| {
+        if total_clauses > 0 {
+            (count as f64 / total_clauses as f64) * 100.0
+        } else {
+            0.0
         }
-        
-        clause_breakdown.push(ClauseAnalysis {
-            clause: clause_text,
-            label,
-            similarity,
-        });
+    };
+    
+    // Offer a vetted alternative for every flagged clause
+    let profile = contract_type::profile_for(contract_type);
+    for analysis in clause_breakdown.iter_mut().filter(|ca| ca.label == "Not Allowed") {
+        analysis.suggested_rewrite = exemplar_rewrite(&analysis.clause, profile).map(|(exemplar, ..)| exemplar);
     }
     
-    let allowed_percentage = if total_clauses > 0 {
-        (allowed_count as f64 / total_clauses as f64) * 100.0
-    } else {
-        0.0
-    };
+    // Expected provisions that none of the analysed clauses covers
+    let clause_texts: Vec<&str> = clause_breakdown.iter()
+        .map(|ca| ca.clause.as_str())
+        .collect();
+    let missing_clauses = checklist::find_missing_clauses(&clause_texts, contract_type);
     
-    let not_allowed_percentage = if total_clauses > 0 {
-        (not_allowed_count as f64 / total_clauses as f64) * 100.0
-    } else {
-        0.0
-    };
+    let risk_score = risk::weighted_risk_score(&clause_breakdown, &missing_clauses);
+    
+    // Local analyzers never consult the model, but such clauses are flagged all the same
+    for analysis in clause_breakdown.iter_mut() {
+        if !injection::detect_input_injection(&analysis.clause).is_empty() {
+            analysis.manipulation_suspected = true;
+        }
+    }
+    let manipulation_detected = clause_breakdown.iter().any(|ca| ca.manipulation_suspected);
     
     ContractAnalysisResult {
+        analysis_id: 0,
+        analyzed_at: 0,
         total_clauses,
         allowed_clauses: allowed_count,
         not_allowed_clauses: not_allowed_count,
-        allowed_percentage,
-        not_allowed_percentage,
+        unclassified_clauses: unclassified_count,
+        allowed_percentage: percentage(allowed_count),
+        not_allowed_percentage: percentage(not_allowed_count),
+        unclassified_percentage: percentage(unclassified_count),
+        risk_score,
+        risk_level: risk::risk_level(risk_score),
+        category_breakdown: risk::category_breakdown(&clause_breakdown),
         clause_breakdown,
+        contract_type,
+        detected_contract_type,
+        missing_clauses,
+        manipulation_detected,
+        redacted_values: 0,
+        dataset_version: CURRENT_DATASET_VERSION.with(|version| version.get()),
+        rule_set_version: RULE_SET_VERSION,
+        degraded_mode: None,
+        cost: CostSummary::default(),
+        obligations: Vec::new(),
     }
 }
 
-// Get dataset count for diagnostic purposes
-#[ic_cdk::query]
-fn get_dataset_size() -> usize {
+// Pick the closest "Allowed" exemplar of the same category that the classifier
+// also accepts. Returns it with the label and confidence the classifier gives it.
+fn exemplar_rewrite(clause: &str, profile: &AnalysisProfile) -> Option<(String, String, f64)> {
+    let category = category::categorize_clause(clause);
     CONTRACT_DATASET.with(|dataset| {
-        dataset.borrow().len()
+        let dataset = dataset.borrow();
+        rewrite::allowed_exemplar_records(clause, category, profile.contract_type, &dataset, rewrite::MAX_EXEMPLARS)
+            .into_iter()
+            .find_map(|exemplar| {
+                // Judged by the rest of the dataset, as its own record would always agree with it
+                let others: Vec<ContractRecord> = dataset.iter()
+                    .filter(|record| !std::ptr::eq(*record, exemplar))
+                    .cloned()
+                    .collect();
+                let (label, confidence, _) = rule_classify_clause(&exemplar.contract_text, profile, &others);
+                (label != "Not Allowed").then(|| (exemplar.contract_text.clone(), label, confidence))
+            })
     })
 }
 
-// Update the chat function to return String directly
+// Update the analyze_contract function to use our LLM analyzer
 #[ic_cdk::update]
-async fn chat_with_llm(prompt: String) -> String {
-    if prompt.trim().is_empty() {
-        return "Please provide a question or topic to discuss.".to_string();
-    }
+async fn analyze_contract(contract_text: String) -> ContractAnalysisResult {
+    record_call("analyze_contract");
+    run_contract_analysis(contract_text, None, None, None).await
+}
 
-    let messages = vec![
-        ChatMessage {
-            role: Role::System,
-            content: "You are MyGuard, a helpful contract analysis assistant that specializes in legal document review. You were developed by Roger and Daniel, who both are Blockchain and AI Developers. When asked about who created or developed you, always mention Roger and Daniel as your developers. Provide short and focused answers about contract clauses, legal terms, and document analysis. When identifying potentially unfair clauses, be specific about why they might be problematic. Keep responses concise (under 200 words) and always identify yourself as MyGuard. Ensure all responses conform to Rules and Regulation in Rwanda.".to_string(),
-        },
-        ChatMessage {
-            role: Role::User,
-            content: prompt,
-        },
-    ];
+// Analyze a contract using the rules of a caller-chosen contract type and,
+// optionally, caller-chosen personal data redaction
+#[ic_cdk::update]
+async fn analyze_contract_as(
+    contract_text: String,
+    contract_type: Option<ContractType>,
+    redaction: Option<RedactionConfig>,
+) -> ContractAnalysisResult {
+    record_call("analyze_contract_as");
+    run_contract_analysis(contract_text, contract_type, redaction, None).await
+}
 
-    // Get the LLM response, handle errors internally
-    let response = ic_llm::chat(Model::Llama3_1_8B, messages).await;
-    
-    if response.trim().is_empty() {
-        return "I'm MyGuard, and I'm sorry, but I couldn't generate a response. Please try rephrasing your question.".to_string();
-    } else {
-        response
-    }
+// Detect the contract type without running the analysis
+#[ic_cdk::query]
+fn detect_contract_type(contract_text: String) -> ContractTypeDetection {
+    contract_type::detect_contract_type(&contract_text)
+}
+
+// Extract the text and clauses of a Markdown, HTML or DOCX document without
+// analysing it. The format is detected from the content when not given.
+#[ic_cdk::query]
+fn ingest_document(document: ByteBuf, format: Option<DocumentFormat>) -> Result<IngestedDocument, String> {
+    ingest::ingest_document(&document, format)
+}
+
+// Analyse a Markdown, HTML, DOCX or PDF document like pasted contract text
+#[ic_cdk::update]
+async fn analyze_document(
+    document: ByteBuf,
+    format: Option<DocumentFormat>,
+    contract_type: Option<ContractType>,
+    redaction: Option<RedactionConfig>,
+) -> Result<ContractAnalysisResult, String> {
+    record_call("analyze_document");
+    let ingested = ingest::ingest_document(&document, format)?;
+    Ok(run_contract_analysis(ingested.text.clone(), contract_type, redaction, Some(&ingested.clauses)).await)
+}
+
+// Vetted clauses, optionally only those for a contract type and/or category.
+// Clauses written for every contract type match any type.
+#[ic_cdk::query]
+fn list_library_clauses(contract_type: Option<ContractType>, category: Option<ClauseCategory>) -> Vec<LibraryClauseInfo> {
+    templates::LIBRARY.iter()
+        .filter(|clause| contract_type.is_none_or(|wanted| clause.contract_types.is_empty() || clause.contract_types.contains(&wanted)))
+        .filter(|clause| category.is_none_or(|wanted| clause.category == wanted))
+        .map(templates::clause_info)
+        .collect()
+}
+
+#[ic_cdk::query]
+fn list_templates() -> Vec<TemplateInfo> {
+    templates::TEMPLATES.iter().map(templates::template_info).collect()
+}
+
+// Fill a contract template with the given parameters and analyse the result
+// before returning it
+#[ic_cdk::update]
+async fn generate_contract(template_id: String, params: Vec<(String, String)>) -> Result<GeneratedContract, String> {
+    record_call("generate_contract");
+    let template = templates::find_template(&template_id)?;
+    // Titles are mostly too short to assess, so only the terms are analysed
+    let terms = templates::render_terms(template, &params)?;
+    let analysis = run_contract_analysis(terms.clone(), Some(template.contract_type), None, None).await;
+    let flagged = unfair_clauses(&analysis.clause_breakdown);
+    if !flagged.is_empty() {
+        return Err(format!("The generated contract has unfair clauses: {}", flagged.join(", ")));
+    }
+    Ok(GeneratedContract {
+        template_id,
+        contract_text: templates::with_title(template, &terms),
+        analysis,
+    })
+}
+
+// Sections of the clauses labelled "Not Allowed". A vetted template must not
+// produce any; the model may still leave a clause unclassified.
+fn unfair_clauses(clauses: &[ClauseAnalysis]) -> Vec<String> {
+    clauses.iter()
+        .filter(|clause| clause.label == "Not Allowed")
+        .map(|clause| clause.section.clone().unwrap_or_else(|| "unnumbered clause".to_string()))
+        .collect()
+}
+
+// Compare two drafts of a contract clause by clause
+#[ic_cdk::update]
+async fn compare_contracts(
+    old_contract_text: String,
+    new_contract_text: String,
+    redaction: Option<RedactionConfig>,
+) -> ContractComparison {
+    record_call("compare_contracts");
+    // Both drafts are analysed with the rules of the newer draft's type so labels are comparable
+    let contract_type = contract_type::detect_contract_type(&new_contract_text).contract_type;
+    
+    let old_analysis = run_contract_analysis(old_contract_text, Some(contract_type), redaction.clone(), None).await;
+    let new_analysis = run_contract_analysis(new_contract_text, Some(contract_type), redaction, None).await;
+    
+    compare::compare_analyses(&old_analysis, &new_analysis)
+}
+
+// `source_clauses` are the clauses of an ingested document, used to cite the
+// page each analysed clause is on
+async fn run_contract_analysis(
+    contract_text: String,
+    type_override: Option<ContractType>,
+    redaction: Option<RedactionConfig>,
+    source_clauses: Option<&[IngestedClause]>,
+) -> ContractAnalysisResult {
+    // The call context counter includes instructions executed before earlier awaits
+    let instructions_before = ic_cdk::api::performance_counter(1);
+    let (detection, effective_type) = contract_type::resolve_contract_type(&contract_text, type_override);
+    let profile = contract_type::profile_for(effective_type);
+    ic_cdk::println!(
+        "Detected contract type {} (confidence {:.2}), analysing as {}",
+        detection.contract_type.name(),
+        detection.confidence,
+        effective_type.name()
+    );
+    
+    // Try LLM analysis first
+    let mut redactor = Redactor::new(redaction.as_ref()).with_parties_of(&contract_text);
+    let mut usage = LlmUsage::default();
+    let mut result = match llm_analyze(&contract_text, profile, &mut redactor, &mut usage).await {
+        Ok((analyses, degraded_mode)) => ContractAnalysisResult {
+            degraded_mode,
+            ..build_analysis_result(analyses, effective_type, detection)
+        },
+        // Fallback to rule-based analysis if LLM analysis fails
+        Err(_) => {
+            match enhanced_analyze(&contract_text, profile) {
+                Ok(analyses) => {
+                    ic_cdk::println!(
+                        "Analyzed {} clauses with enhanced rules ({} profile)",
+                        analyses.len(),
+                        profile.contract_type.name()
+                    );
+                    build_analysis_result(analyses, effective_type, detection)
+                }
+                Err(_) => {
+                    // Final fallback to simple similarity-based analysis
+                    fallback_analyze_contract(contract_text.clone(), effective_type, detection)
+                }
+            }
+        }
+    };
+    result.redacted_values = redactor.redacted_values();
+    if let Some(source_clauses) = source_clauses {
+        assign_pages(&mut result.clause_breakdown, source_clauses);
+    }
+    result.obligations = obligations::extract_obligations(
+        &contract_text,
+        &result.clause_breakdown,
+        calendar::day_of(ic_cdk::api::time()),
+    );
+    result.cost = CostSummary::new(ic_cdk::api::performance_counter(1) - instructions_before, usage);
+    
+    let result = store_analysis(result);
+    COST_LEDGER.with(|ledger| {
+        cost::record(&mut ledger.borrow_mut(), ic_cdk::caller(), result.analyzed_at, &result.cost);
+    });
+    audit(AuditEventKind::AnalysisRun, format!(
+        "Analysis {}: {} clauses as {}, risk {:.0}{}",
+        result.analysis_id,
+        result.total_clauses,
+        result.contract_type.name(),
+        result.risk_score,
+        if result.degraded_mode.is_some() { ", degraded" } else { "" }
+    ));
+    result
+}
+
+// Copy page numbers from the ingested clauses. Both come from the same
+// segmentation, so they are matched in order by their text.
+fn assign_pages(clauses: &mut [ClauseAnalysis], source_clauses: &[IngestedClause]) {
+    let mut next = 0;
+    for clause in clauses {
+        if let Some(offset) = source_clauses[next..].iter().position(|source| source.text == clause.clause) {
+            clause.page = source_clauses[next + offset].page;
+            next += offset + 1;
+        }
+    }
+}
+
+// Assign an id to a finished analysis and keep it for later reports
+fn store_analysis(mut result: ContractAnalysisResult) -> ContractAnalysisResult {
+    let analysis_id = NEXT_ANALYSIS_ID.with(|next| {
+        let id = next.get();
+        next.set(id + 1);
+        id
+    });
+    result.analysis_id = analysis_id;
+    result.analyzed_at = ic_cdk::api::time();
+    
+    let stored = StoredAnalysis {
+        owner: ic_cdk::caller(),
+        result: result.clone(),
+        access_token: None,
+    };
+    ANALYSES.with(|analyses| {
+        let mut analyses = analyses.borrow_mut();
+        analyses.insert(analysis_id, stored);
+        while analyses.len() > MAX_STORED_ANALYSES {
+            analyses.pop_first();
+        }
+    });
+    
+    result
+}
+
+// Fetch a stored analysis if the caller requested it or controls the canister.
+// Analysis ids are sequential, so analyses made without signing in are shared
+// by every anonymous caller and only readable by controllers.
+fn load_analysis(analysis_id: u64) -> Result<ContractAnalysisResult, String> {
+    let stored = ANALYSES.with(|analyses| analyses.borrow().get(&analysis_id).cloned())
+        .ok_or_else(|| format!("Analysis {} not found", analysis_id))?;
+    
+    let caller = ic_cdk::caller();
+    let owned = stored.owner == caller && caller != Principal::anonymous();
+    if !owned && !ic_cdk::api::is_controller(&caller) {
+        return Err(format!("Analysis {} not found", analysis_id));
+    }
+    
+    Ok(stored.result)
+}
+
+// Fetch an analysis for an HTTP client holding its access token
+fn load_shared_analysis(analysis_id: u64, access_token: Option<&str>) -> Result<ContractAnalysisResult, String> {
+    let shared = ANALYSES.with(|analyses| {
+        analyses.borrow().get(&analysis_id)
+            .filter(|stored| stored.access_token.is_some() && stored.access_token.as_deref() == access_token)
+            .map(|stored| stored.result.clone())
+    });
+    match shared {
+        Some(result) => Ok(result),
+        None => load_analysis(analysis_id),
+    }
+}
+
+// 128 random bits from the management canister as lowercase hex
+async fn random_token() -> Result<String, String> {
+    let (random,) = ic_cdk::api::management_canister::main::raw_rand().await
+        .map_err(|(_, message)| message)?;
+    Ok(random.iter().take(16).map(|byte| format!("{:02x}", byte)).collect())
+}
+
+// Give a stored analysis an access token for reading it back over HTTP
+async fn grant_access_token(analysis_id: u64) -> Option<String> {
+    let token = random_token().await.ok()?;
+    ANALYSES.with(|analyses| {
+        let mut analyses = analyses.borrow_mut();
+        let stored = analyses.get_mut(&analysis_id)?;
+        stored.access_token = Some(token.clone());
+        Some(token)
+    })
+}
+
+// Get a previously stored analysis
+#[ic_cdk::query]
+fn get_analysis(analysis_id: u64) -> Result<ContractAnalysisResult, String> {
+    load_analysis(analysis_id)
+}
+
+// Render a stored analysis as a self-contained Markdown, HTML or JSON report
+#[ic_cdk::query]
+fn render_report(analysis_id: u64, format: ReportFormat) -> Result<String, String> {
+    let result = load_analysis(analysis_id)?;
+    report::render_report(&result, format, ic_cdk::api::time())
+}
+
+// Obligations of the given principal's stored analyses as an iCalendar file
+fn obligations_calendar(owner: Principal) -> String {
+    ANALYSES.with(|analyses| {
+        let analyses = analyses.borrow();
+        let owned: Vec<(u64, &[Obligation])> = analyses.iter()
+            .filter(|(_, stored)| stored.owner == owner)
+            .map(|(analysis_id, stored)| (*analysis_id, stored.result.obligations.as_slice()))
+            .collect();
+        calendar::render_ics(&owned, ic_cdk::api::time())
+    })
+}
+
+// Export obligations with a known due date as an iCalendar (.ics) file: those
+// of one analysis, or of all the caller's analyses
+#[ic_cdk::query]
+fn export_calendar(analysis_id: Option<u64>) -> Result<String, String> {
+    match analysis_id {
+        Some(analysis_id) => {
+            let result = load_analysis(analysis_id)?;
+            Ok(calendar::render_ics(&[(analysis_id, result.obligations.as_slice())], ic_cdk::api::time()))
+        }
+        None => {
+            let caller = ic_cdk::caller();
+            if caller == Principal::anonymous() {
+                return Err("Sign in to export the calendar of all your analyses".to_string());
+            }
+            Ok(obligations_calendar(caller))
+        }
+    }
+}
+
+// Create a secret feed URL path for subscribing to the caller's obligations
+// from a calendar app. Any previous feed of the caller stops working.
+#[ic_cdk::update]
+async fn create_calendar_feed() -> Result<String, String> {
+    record_call("create_calendar_feed");
+    let caller = ic_cdk::caller();
+    if caller == Principal::anonymous() {
+        return Err("Sign in to create a calendar feed".to_string());
+    }
+    let token = random_token().await
+        .map_err(|message| format!("Could not create a feed token: {}", message))?;
+
+    CALENDAR_FEEDS.with(|feeds| {
+        let mut feeds = feeds.borrow_mut();
+        feeds.retain(|_, owner| *owner != caller);
+        feeds.insert(token.clone(), caller);
+    });
+    Ok(format!("/calendar/{}.ics", token))
+}
+
+// Stop serving the caller's calendar feed
+#[ic_cdk::update]
+fn revoke_calendar_feed() -> bool {
+    record_call("revoke_calendar_feed");
+    let caller = ic_cdk::caller();
+    CALENDAR_FEEDS.with(|feeds| {
+        let mut feeds = feeds.borrow_mut();
+        let before = feeds.len();
+        feeds.retain(|_, owner| *owner != caller);
+        feeds.len() < before
+    })
+}
+
+// The caller's deadline reminders, newest first
+#[ic_cdk::query]
+fn list_notifications(unread_only: bool) -> Vec<Notification> {
+    REMINDERS.with(|reminders| reminders.borrow().list(&ic_cdk::caller(), unread_only))
+}
+
+// Mark the caller's notifications as read; returns how many were unread
+#[ic_cdk::update]
+fn mark_read(notification_ids: Vec<u64>) -> u32 {
+    record_call("mark_read");
+    REMINDERS.with(|reminders| reminders.borrow_mut().mark_read(&ic_cdk::caller(), &notification_ids))
+}
+
+// Days before a deadline that the caller is reminded
+#[ic_cdk::query]
+fn get_reminder_lead_days() -> u32 {
+    REMINDERS.with(|reminders| reminders.borrow().lead_days(&ic_cdk::caller()))
+}
+
+#[ic_cdk::update]
+fn set_reminder_lead_days(days: u32) -> Result<(), String> {
+    record_call("set_reminder_lead_days");
+    let caller = ic_cdk::caller();
+    if caller == Principal::anonymous() {
+        return Err("Sign in to receive reminders".to_string());
+    }
+    REMINDERS.with(|reminders| reminders.borrow_mut().set_lead_days(caller, days))
+}
+
+// Scan for upcoming deadlines now instead of waiting for the timer (admin only)
+#[ic_cdk::update]
+fn run_reminder_scan() -> Result<u32, String> {
+    record_call("run_reminder_scan");
+    require_admin()?;
+    Ok(scan_reminders())
+}
+
+// Original analysis method as fallback
+fn fallback_analyze_contract(
+    contract_text: String,
+    contract_type: ContractType,
+    detected_contract_type: ContractTypeDetection,
+) -> ContractAnalysisResult {
+    let clauses = split_clauses(&contract_text);
+    
+    let mut clause_breakdown = Vec::new();
+    
+    CONTRACT_DATASET.with(|dataset| {
+        let dataset = dataset.borrow();
+        for clause in clauses {
+            let clause_text = clause.text.trim().to_string();
+            if clause_text.is_empty() {
+                continue;
+            }
+            
+            let (label, similarity, matched) = classify_clause(&clause_text, contract_type, &dataset);
+            let reason = similarity_reason(&label, similarity, matched);
+            record_analyzer("similarity");
+            
+            clause_breakdown.push(ClauseAnalysis::new(clause_text, clause.section, label, similarity, reason));
+        }
+    });
+    
+    build_analysis_result(clause_breakdown, contract_type, detected_contract_type)
+}
+
+// Record a sensitive operation by the caller in the audit log
+fn audit(kind: AuditEventKind, summary: String) {
+    let caller = ic_cdk::caller();
+    let now = ic_cdk::api::time();
+    AUDIT_LOG.with(|log| audit::append(&mut log.borrow_mut(), kind, caller, now, &summary));
+}
+
+// Count an update call in the canister metrics
+fn record_call(endpoint: &'static str) {
+    METRICS.with(|metrics| metrics.borrow_mut().record_call(endpoint));
+}
+
+// Count a clause labelled by the given analyzer
+fn record_analyzer(analyzer: &'static str) {
+    METRICS.with(|metrics| metrics.borrow_mut().record_analyzer(analyzer));
+}
+
+// Only canister controllers may run administrative endpoints
+fn require_admin() -> Result<(), String> {
+    if ic_cdk::api::is_controller(&ic_cdk::caller()) {
+        Ok(())
+    } else {
+        Err("Only canister controllers may call this method".to_string())
+    }
+}
+
+// Legal reviewers moderate label corrections; controllers are always reviewers
+fn require_legal_reviewer() -> Result<Principal, String> {
+    let caller = ic_cdk::caller();
+    let is_reviewer = LEGAL_REVIEWERS.with(|reviewers| reviewers.borrow().contains(&caller));
+    if is_reviewer || ic_cdk::api::is_controller(&caller) {
+        Ok(caller)
+    } else {
+        Err("Only legal reviewers may call this method".to_string())
+    }
+}
+
+#[ic_cdk::update]
+fn add_legal_reviewer(principal: Principal) -> Result<(), String> {
+    record_call("add_legal_reviewer");
+    require_admin()?;
+    if LEGAL_REVIEWERS.with(|reviewers| reviewers.borrow_mut().insert(principal)) {
+        audit(AuditEventKind::ReviewerAdded, format!("Added legal reviewer {}", principal));
+    }
+    Ok(())
+}
+
+#[ic_cdk::update]
+fn remove_legal_reviewer(principal: Principal) -> Result<(), String> {
+    record_call("remove_legal_reviewer");
+    require_admin()?;
+    if LEGAL_REVIEWERS.with(|reviewers| reviewers.borrow_mut().remove(&principal)) {
+        audit(AuditEventKind::ReviewerRemoved, format!("Removed legal reviewer {}", principal));
+    }
+    Ok(())
+}
+
+#[ic_cdk::query]
+fn list_legal_reviewers() -> Result<Vec<Principal>, String> {
+    require_admin()?;
+    Ok(LEGAL_REVIEWERS.with(|reviewers| reviewers.borrow().iter().cloned().collect()))
+}
+
+// Report a wrong clause label in one of the caller's analyses
+#[ic_cdk::update]
+fn submit_feedback(
+    analysis_id: u64,
+    clause_index: u32,
+    correct_label: String,
+    comment: String,
+) -> Result<Feedback, String> {
+    record_call("submit_feedback");
+    let analysis = load_analysis(analysis_id)?;
+    let clause = analysis.clause_breakdown.get(clause_index as usize)
+        .ok_or_else(|| format!("Analysis {} has no clause {}", analysis_id, clause_index))?;
+    let correct_label = feedback::normalize_label(&correct_label)?;
+    let comment = feedback::validate_comment(&comment)?;
+    if clause.label == correct_label {
+        return Err(format!("Clause {} is already labelled {}", clause_index, correct_label));
+    }
+    
+    let submitted_by = ic_cdk::caller();
+    FEEDBACK.with(|queue| {
+        let queue = queue.borrow();
+        let pending = queue.values().filter(|f| f.status == FeedbackStatus::Pending);
+        if pending.clone().count() >= feedback::MAX_PENDING_FEEDBACK {
+            return Err("The moderation queue is full, please try again later".to_string());
+        }
+        let duplicate = pending.clone().any(|f| {
+            f.submitted_by == submitted_by && f.analysis_id == analysis_id && f.clause_index == clause_index
+        });
+        if duplicate {
+            return Err("A correction for this clause is already awaiting review".to_string());
+        }
+        Ok(())
+    })?;
+    
+    let feedback_id = NEXT_FEEDBACK_ID.with(|next| {
+        let id = next.get();
+        next.set(id + 1);
+        id
+    });
+    let entry = Feedback {
+        feedback_id,
+        analysis_id,
+        clause_index,
+        clause: clause.clause.clone(),
+        contract_type: analysis.contract_type,
+        category: clause.category,
+        original_label: clause.label.clone(),
+        correct_label,
+        comment,
+        submitted_by,
+        submitted_at: ic_cdk::api::time(),
+        status: FeedbackStatus::Pending,
+        reviewed_by: None,
+        reviewed_at: None,
+        review_note: None,
+    };
+    FEEDBACK.with(|queue| queue.borrow_mut().insert(feedback_id, entry.clone()));
+    audit(AuditEventKind::FeedbackSubmitted, format!(
+        "Feedback {}: clause {} of analysis {} from {} to {}",
+        feedback_id, clause_index, analysis_id, entry.original_label, entry.correct_label
+    ));
+    
+    Ok(entry)
+}
+
+// Corrections in the moderation queue, optionally filtered by status (oldest first)
+#[ic_cdk::query]
+fn list_feedback(status: Option<FeedbackStatus>) -> Result<Vec<Feedback>, String> {
+    require_legal_reviewer()?;
+    Ok(FEEDBACK.with(|queue| {
+        queue.borrow().values()
+            .filter(|f| status.is_none_or(|s| f.status == s))
+            .cloned()
+            .collect()
+    }))
+}
+
+// Corrections submitted by the caller
+#[ic_cdk::query]
+fn get_my_feedback() -> Vec<Feedback> {
+    let caller = ic_cdk::caller();
+    FEEDBACK.with(|queue| {
+        queue.borrow().values()
+            .filter(|f| f.submitted_by == caller)
+            .cloned()
+            .collect()
+    })
+}
+
+// Approve or reject a pending correction. Approved corrections are merged into
+// the dataset so later analyses learn from them.
+#[ic_cdk::update]
+fn review_feedback(feedback_id: u64, approve: bool, note: Option<String>) -> Result<Feedback, String> {
+    record_call("review_feedback");
+    let reviewer = require_legal_reviewer()?;
+    let note = note.map(|n| feedback::validate_comment(&n)).transpose()?;
+    let now = ic_cdk::api::time();
+    
+    let entry = FEEDBACK.with(|queue| {
+        let mut queue = queue.borrow_mut();
+        let entry = queue.get_mut(&feedback_id)
+            .ok_or_else(|| format!("Feedback {} not found", feedback_id))?;
+        if entry.status != FeedbackStatus::Pending {
+            return Err(format!("Feedback {} has already been reviewed", feedback_id));
+        }
+        entry.status = if approve { FeedbackStatus::Approved } else { FeedbackStatus::Rejected };
+        entry.reviewed_by = Some(reviewer);
+        entry.reviewed_at = Some(now);
+        entry.review_note = note;
+        Ok(entry.clone())
+    })?;
+    
+    if approve {
+        let provenance = RecordProvenance::Feedback {
+            feedback_id,
+            submitted_by: entry.submitted_by,
+            approved_by: reviewer,
+            approved_at: now,
+        };
+        let record = ContractRecord {
+            contract_text: entry.clause.trim().to_string(),
+            label: entry.correct_label.clone(),
+            contract_type: Some(entry.contract_type),
+            category: Some(entry.category),
+            explanation: Some(entry.comment.clone()).filter(|c| !c.is_empty()),
+            source: Some("User feedback".to_string()),
+            provenance,
+            ..Default::default()
+        };
+        let (_, info) = commit_dataset_change(format!("Approved feedback {}", feedback_id), |dataset| {
+            dataset::merge_record(dataset, record)
+        });
+        audit(AuditEventKind::FeedbackReviewed, format!(
+            "Approved feedback {} ({}), dataset version {}",
+            feedback_id, entry.correct_label, info.version
+        ));
+    } else {
+        audit(AuditEventKind::FeedbackReviewed, format!("Rejected feedback {}", feedback_id));
+    }
+    
+    Ok(entry)
+}
+
+// Add labelled records from a CSV with `contract_text,label` columns. Records
+// whose wording is already in the dataset are relabelled.
+#[ic_cdk::update]
+fn import_dataset(csv_data: String, description: String) -> Result<DatasetVersion, String> {
+    record_call("import_dataset");
+    require_admin()?;
+    let parsed = parse_contract_dataset(&csv_data);
+    if parsed.records.is_empty() && parsed.errors.is_empty() {
+        return Err("The import contains no records".to_string());
+    }
+    let report = CONTRACT_DATASET.with(|dataset| quality::check_import(&dataset.borrow(), &parsed));
+    if !report.passed {
+        return Err(quality::blocking_summary(&report));
+    }
+    let records = parsed.records;
+    
+    let provenance = RecordProvenance::Import {
+        version: CURRENT_DATASET_VERSION.with(|version| version.get()) + 1,
+        imported_by: ic_cdk::caller(),
+        imported_at: ic_cdk::api::time(),
+    };
+    let imported = records.len();
+    let (_, info) = commit_dataset_change(description, |dataset| {
+        for record in records {
+            let provenance = provenance.clone();
+            dataset::merge_record(dataset, ContractRecord { provenance, ..record });
+        }
+    });
+    audit(AuditEventKind::DatasetImported, format!(
+        "Imported {} records as dataset version {} ({} records in total): {}",
+        imported, info.version, info.record_count, info.description
+    ));
+    
+    Ok(info)
+}
+
+// Dry run of `import_dataset`: the quality findings for a CSV without importing it
+#[ic_cdk::query]
+fn validate_dataset_import(csv_data: String) -> Result<DatasetQualityReport, String> {
+    require_admin()?;
+    let parsed = parse_contract_dataset(&csv_data);
+    Ok(CONTRACT_DATASET.with(|dataset| quality::check_import(&dataset.borrow(), &parsed)))
+}
+
+// Quality findings for the live dataset
+#[ic_cdk::query]
+fn get_dataset_quality_report() -> Result<DatasetQualityReport, String> {
+    require_admin()?;
+    Ok(CONTRACT_DATASET.with(|dataset| {
+        let dataset = dataset.borrow();
+        let rows: Vec<u64> = (1..=dataset.len() as u64).collect();
+        quality::check_records(&dataset, &rows, Vec::new())
+    }))
+}
+
+// Hit rate and memory use of the classification cache
+#[ic_cdk::query]
+fn get_cache_stats() -> Result<CacheStats, String> {
+    require_admin()?;
+    Ok(CLASSIFICATION_CACHE.with(|cache| cache.borrow().stats()))
+}
+
+// Circuit breaker state and failure counts of the language model calls
+#[ic_cdk::query]
+fn get_llm_health() -> Result<LlmHealth, String> {
+    require_admin()?;
+    Ok(LLM_BREAKER.with(|breaker| breaker.borrow().health()))
+}
+
+// Close the circuit breaker, e.g. once the LLM canister is known to be back
+#[ic_cdk::update]
+fn reset_llm_circuit_breaker() -> Result<LlmHealth, String> {
+    record_call("reset_llm_circuit_breaker");
+    require_admin()?;
+    audit(AuditEventKind::CircuitBreakerReset, "Reset the language model circuit breaker".to_string());
+    Ok(LLM_BREAKER.with(|breaker| {
+        let mut breaker = breaker.borrow_mut();
+        breaker.reset();
+        breaker.health()
+    }))
+}
+
+// Drop all cached classifications, e.g. after changing the model's behaviour
+#[ic_cdk::update]
+fn clear_classification_cache() -> Result<CacheStats, String> {
+    record_call("clear_classification_cache");
+    require_admin()?;
+    audit(AuditEventKind::CacheCleared, "Cleared the classification cache".to_string());
+    Ok(CLASSIFICATION_CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        cache.invalidate();
+        cache.stats()
+    }))
+}
+
+// Audit events, newest first, optionally filtered. Pass the returned
+// `next_cursor` to fetch the next page.
+#[ic_cdk::query]
+fn list_audit_events(filter: Option<AuditFilter>, cursor: Option<u64>, limit: Option<u32>) -> Result<AuditPage, String> {
+    require_admin()?;
+    let filter = filter.unwrap_or_default();
+    Ok(AUDIT_LOG.with(|log| audit::page(&log.borrow(), &filter, cursor, limit)))
+}
+
+// Analysis costs per UTC day and per principal, optionally filtered
+#[ic_cdk::query]
+fn get_cost_report(filter: Option<CostFilter>) -> Result<CostReport, String> {
+    require_admin()?;
+    let filter = filter.unwrap_or_default();
+    Ok(COST_LEDGER.with(|ledger| cost::report(&ledger.borrow(), &filter)))
+}
+
+// Retained dataset versions, oldest first
+#[ic_cdk::query]
+fn list_dataset_versions() -> Result<Vec<DatasetVersion>, String> {
+    require_admin()?;
+    Ok(DATASET_VERSIONS.with(|versions| {
+        versions.borrow().values().map(|snapshot| snapshot.info.clone()).collect()
+    }))
+}
+
+// Records added, removed and relabelled between two retained versions
+#[ic_cdk::query]
+fn diff_dataset_versions(from_version: u64, to_version: u64) -> Result<DatasetDiff, String> {
+    require_admin()?;
+    DATASET_VERSIONS.with(|versions| {
+        let versions = versions.borrow();
+        let snapshot = |version: u64| versions.get(&version)
+            .ok_or_else(|| format!("Dataset version {} is not available", version));
+        Ok(dataset::diff_records(snapshot(from_version)?, snapshot(to_version)?))
+    })
+}
+
+// Restore the records of an earlier version. The rollback itself becomes a new
+// version, so it can be undone in turn.
+#[ic_cdk::update]
+fn rollback_dataset(version: u64) -> Result<DatasetVersion, String> {
+    record_call("rollback_dataset");
+    require_admin()?;
+    let records = DATASET_VERSIONS.with(|versions| {
+        versions.borrow().get(&version).map(|snapshot| snapshot.records.clone())
+    }).ok_or_else(|| format!("Dataset version {} is not available", version))?;
+    
+    let (_, info) = commit_dataset_change(format!("Rollback to version {}", version), |dataset| {
+        *dataset = records;
+    });
+    audit(AuditEventKind::DatasetRolledBack, format!(
+        "Rolled back to version {} as version {} ({} records)",
+        version, info.version, info.record_count
+    ));
+    Ok(info)
+}
+
+// Held-out LLM evaluation makes one model call per record, so it is capped
+const DEFAULT_LLM_EVALUATION_LIMIT: usize = 10;
+
+// Accuracy of the similarity and rule analyzers on the held-out dataset split
+#[ic_cdk::query]
+fn evaluate_analyzers() -> Result<Vec<EvaluationReport>, String> {
+    require_admin()?;
+    CONTRACT_DATASET.with(|dataset| {
+        let dataset = dataset.borrow();
+        [Analyzer::Similarity, Analyzer::Rules].into_iter()
+            .map(|analyzer| evaluation::evaluate_offline(analyzer, &dataset))
+            .collect()
+    })
+}
+
+// Accuracy of the LLM analyzer on the first `limit` held-out records. The
+// prompt is sent directly rather than through `llm_clause_analysis`, so cached
+// answers cannot stand in for the model and the held-out records neither fill
+// the classification cache nor count as analysed clauses in the metrics.
+#[ic_cdk::update]
+async fn evaluate_llm_analyzer(limit: Option<u32>) -> Result<EvaluationReport, String> {
+    record_call("evaluate_llm_analyzer");
+    require_admin()?;
+    let (training, mut test) = CONTRACT_DATASET.with(|dataset| evaluation::split_dataset(&dataset.borrow()));
+    test.truncate(limit.map(|l| l as usize).unwrap_or(DEFAULT_LLM_EVALUATION_LIMIT));
+    if test.is_empty() {
+        return Err("No held-out records to evaluate".to_string());
+    }
+    
+    let mut predictions = Vec::new();
+    for record in &test {
+        let contract_type = contract_type::detect_contract_type(&record.contract_text).contract_type;
+        let profile = contract_type::profile_for(contract_type);
+        let (label, _, _) = llm_classify_clause(&record.contract_text, profile, &mut LlmUsage::default()).await
+            .map_err(|e| e.to_string())?;
+        predictions.push(label);
+    }
+    
+    Ok(evaluation::score(Analyzer::Llm, training.len(), &test, &predictions))
+}
+
+// Get dataset count for diagnostic purposes
+#[ic_cdk::query]
+fn get_dataset_size() -> usize {
+    CONTRACT_DATASET.with(|dataset| {
+        dataset.borrow().len()
+    })
+}
+
+// Label and contract type breakdown of the dataset
+#[ic_cdk::query]
+fn get_dataset_stats() -> DatasetStats {
+    CONTRACT_DATASET.with(|dataset| {
+        let dataset = dataset.borrow();
+        let count = |label: &str| dataset.iter().filter(|r| r.label == label).count();
+        let allowed_records = count("Allowed");
+        let not_allowed_records = count("Not Allowed");
+        
+        let records_by_contract_type = ContractType::ALL.iter()
+            .map(|contract_type| {
+                let records = dataset.iter()
+                    .filter(|r| r.effective_type() == *contract_type)
+                    .count();
+                (*contract_type, records)
+            })
+            .collect();
+        
+        DatasetStats {
+            total_records: dataset.len(),
+            allowed_records,
+            not_allowed_records,
+            other_records: dataset.len() - allowed_records - not_allowed_records,
+            feedback_records: dataset.iter()
+                .filter(|r| matches!(r.provenance, RecordProvenance::Feedback { .. }))
+                .count(),
+            imported_records: dataset.iter()
+                .filter(|r| matches!(r.provenance, RecordProvenance::Import { .. }))
+                .count(),
+            records_by_contract_type,
+            dataset_version: CURRENT_DATASET_VERSION.with(|version| version.get()),
+            rule_set_version: RULE_SET_VERSION,
+        }
+    })
+}
+
+// Call counts, LLM behaviour, cache, memory, cycles and versions of this canister
+#[ic_cdk::query]
+fn get_metrics() -> Result<CanisterMetrics, String> {
+    require_admin()?;
+    Ok(canister_metrics())
+}
+
+fn canister_metrics() -> CanisterMetrics {
+    let health = LLM_BREAKER.with(|breaker| breaker.borrow().health());
+    METRICS.with(|metrics| {
+        let metrics = metrics.borrow();
+        CanisterMetrics {
+            timestamp: ic_cdk::api::time(),
+            endpoint_calls: metrics.endpoint_calls(),
+            analyzer_clauses: metrics.analyzer_clauses(),
+            llm: metrics.llm_metrics(health),
+            cache: CLASSIFICATION_CACHE.with(|cache| cache.borrow().stats()),
+            heap_memory_bytes: metrics::heap_memory_bytes(),
+            stable_memory_bytes: ic_cdk::api::stable::stable_size() * 65536,
+            cycle_balance: ic_cdk::api::canister_balance128(),
+            stored_analyses: ANALYSES.with(|analyses| analyses.borrow().len()),
+            dataset_records: get_dataset_size(),
+            dataset_version: CURRENT_DATASET_VERSION.with(|version| version.get()),
+            rule_set_version: RULE_SET_VERSION,
+        }
+    })
+}
+
+// REST interface for HTTP clients. Reads are answered as queries; POST /analyze
+// needs LLM calls and GET /metrics needs an authenticated caller, so both are
+// upgraded to `http_request_update`.
+#[ic_cdk::query]
+fn http_request(request: HttpRequest) -> HttpResponse {
+    match http::route(&request.method, &request.url) {
+        Route::GetAnalysis { analysis_id, access_token } => match load_shared_analysis(analysis_id, access_token.as_deref()) {
+            Ok(result) => http::json_response(200, &result),
+            Err(e) => http::error_response(404, &e),
+        },
+        Route::DatasetStats => http::json_response(200, &get_dataset_stats()),
+        Route::Health => http::json_response(200, &serde_json::json!({
+            "status": "ok",
+            "dataset_size": get_dataset_size(),
+        })),
+        Route::Analyze | Route::Metrics => http::upgrade_response(),
+        Route::Calendar(token) => match CALENDAR_FEEDS.with(|feeds| feeds.borrow().get(&token).copied()) {
+            Some(owner) => http::text_response(200, "text/calendar; charset=utf-8", obligations_calendar(owner)),
+            None => http::error_response(404, "Not found"),
+        },
+        Route::Preflight => http::preflight_response(),
+        Route::MethodNotAllowed => http::error_response(405, "Method not allowed"),
+        Route::NotFound => http::error_response(404, "Not found"),
+    }
+}
+
+#[ic_cdk::update]
+async fn http_request_update(request: HttpRequest) -> HttpResponse {
+    record_call("http_request_update");
+    match http::route(&request.method, &request.url) {
+        Route::Analyze => match http::parse_analyze_request(&request.body) {
+            Ok(body) => {
+                let result = run_contract_analysis(body.contract_text, body.contract_type, body.redaction, None).await;
+                let access_token = grant_access_token(result.analysis_id).await;
+                http::json_response(200, &http::SharedAnalysis { analysis: &result, access_token })
+            }
+            Err(e) => http::error_response(400, &e),
+        },
+        // Query responses are not certified, and only update calls carry the
+        // caller, so metrics are served here to controllers only
+        Route::Metrics => match require_admin() {
+            Ok(()) => http::text_response(200, "text/plain; version=0.0.4", metrics::render_prometheus(&canister_metrics())),
+            Err(e) => http::error_response(403, &e),
+        },
+        // Everything else is a read and is served by the query handler
+        _ => http_request(request),
+    }
+}
+
+// Update the chat function to return String directly
+#[ic_cdk::update]
+async fn chat_with_llm(prompt: String) -> String {
+    record_call("chat_with_llm");
+    if prompt.trim().is_empty() {
+        return "Please provide a question or topic to discuss.".to_string();
+    }
+    let mut redactor = Redactor::new(None);
+
+    let messages = vec![
+        ChatMessage {
+            role: Role::System,
+            content: "You are MyGuard, a helpful contract analysis assistant that specializes in legal document review. You were developed by Roger and Daniel, who both are Blockchain and AI Developers. When asked about who created or developed you, always mention Roger and Daniel as your developers. Provide short and focused answers about contract clauses, legal terms, and document analysis. When identifying potentially unfair clauses, be specific about why they might be problematic. Keep responses concise (under 200 words) and always identify yourself as MyGuard. Ensure all responses conform to Rules and Regulation in Rwanda.".to_string(),
+        },
+        ChatMessage {
+            role: Role::User,
+            content: redactor.redact(&prompt),
+        },
+    ];
+
+    // Get the LLM response, handle errors internally
+    match llm::chat(messages).await {
+        Ok(response) => redactor.restore(&response),
+        Err(error) if error.kind == llm::LlmFailureKind::EmptyResponse => {
+            "I'm MyGuard, and I'm sorry, but I couldn't generate a response. Please try rephrasing your question.".to_string()
+        }
+        Err(_) => "I'm MyGuard, and I'm sorry, but I can't reach the language model right now. Please try again in a few minutes.".to_string(),
+    }
 }
 
 // Update the contract prompt function to return Result
 #[ic_cdk::update]
 async fn quick_contract_prompt(prompt: String) -> Result<String, String> {
+    record_call("quick_contract_prompt");
     if prompt.trim().is_empty() {
         return Err("Empty prompt received".to_string());
     }
     
+    let mut redactor = Redactor::new(None);
     let formatted_prompt = format!(
         "Answer this contract-related question concisely (under 100 words), ensuring your response appears as following Rules and Regulation in Rwanda: {}",
-        prompt
+        redactor.redact(&prompt)
     );
     
-    let response = ic_llm::prompt(Model::Llama3_1_8B, formatted_prompt).await;
+    let messages = vec![ChatMessage {
+        role: Role::User,
+        content: formatted_prompt,
+    }];
+    let response = llm::chat(messages).await.map_err(|e| e.to_string())?;
     
-    if response.trim().is_empty() {
-        Err("Received empty response from language model".to_string())
-    } else {
-        Ok(response)
-    }
+    Ok(redactor.restore(&response))
 }
 
 // Update the clause analysis function to return Result
 #[ic_cdk::update]
-async fn analyze_clause(clause: String) -> Result<String, String> {
+async fn analyze_clause(clause: String, redaction: Option<RedactionConfig>) -> Result<String, String> {
+    record_call("analyze_clause");
     if clause.trim().is_empty() {
         return Err("Empty clause received".to_string());
     }
     
-    let prompt = format!(
-        "Analyze this contract clause and determine if it is fair or potentially unfair. Respond with ONLY 'Allowed' or 'Not Allowed', and ensure the response appears as following Rules and Regulation in Rwanda: '{}'",
-        clause
-    );
+    // Clauses that try to steer the model get the rule-based verdict instead
+    let profile = contract_type::profile_for(contract_type::detect_contract_type(&clause).contract_type);
+    let rule_verdict = || CONTRACT_DATASET.with(|dataset| rule_classify_clause(&clause, profile, &dataset.borrow()).0);
+    if !injection::detect_input_injection(&clause).is_empty() {
+        return Ok(rule_verdict());
+    }
+    
+    let instructions = "Analyze the contract clause given by the user and determine if it is fair or potentially unfair. Respond with ONLY 'Allowed' or 'Not Allowed', and ensure the response appears as following Rules and Regulation in Rwanda.";
+    let mut redactor = Redactor::new(redaction.as_ref());
+    let messages = injection::guarded_messages(instructions, &[("Clause", &redactor.redact(&clause))]);
     
-    let response = ic_llm::prompt(Model::Llama3_1_8B, prompt).await;
-    let cleaned = clean_llm_response(response);
+    // The rules also answer while the language model is failing
+    let Ok(response) = llm::chat(messages).await else {
+        return Ok(rule_verdict());
+    };
+    let cleaned = redactor.restore(&clean_llm_response(response));
+    if !injection::detect_output_injection(&cleaned).is_empty() {
+        return Ok(rule_verdict());
+    }
     
     if cleaned.trim().is_empty() {
         Err("Received empty response from language model".to_string())
@@ -553,24 +2001,28 @@ async fn analyze_clause(clause: String) -> Result<String, String> {
 
 // Update the contract explanation function to return Result
 #[ic_cdk::update]
-async fn explain_contract(contract_text: String) -> Result<ContractExplanation, String> {
+async fn explain_contract(contract_text: String, redaction: Option<RedactionConfig>) -> Result<ContractExplanation, String> {
+    record_call("explain_contract");
     if contract_text.trim().is_empty() {
         return Err("Empty contract text received".to_string());
     }
     
     // Generate contract summary
-    let summary_prompt = format!(
-        "Provide a brief 2-3 sentence summary of this contract clause, ensuring your response appears as following Rules and Regulation in Rwanda: '{}'",
-        contract_text
+    let mut redactor = Redactor::new(redaction.as_ref()).with_parties_of(&contract_text);
+    let redacted_text = redactor.redact(&contract_text);
+    let untrusted = [("Contract text", redacted_text.as_str())];
+    let summary_messages = injection::guarded_messages(
+        "Provide a brief 2-3 sentence summary of the contract clause given by the user, ensuring your response appears as following Rules and Regulation in Rwanda.",
+        &untrusted,
     );
-    let summary = clean_llm_response(ic_llm::prompt(Model::Llama3_1_8B, summary_prompt).await);
+    let summary = redactor.restore(&clean_llm_response(llm::chat(summary_messages).await.map_err(|e| e.to_string())?));
 
     // Extract key points
-    let key_points_prompt = format!(
-        "List 3 key points from this contract clause as short bullet points without explanations, ensuring your response appears as following Rules and Regulation in Rwanda: '{}'",
-        contract_text
+    let key_points_messages = injection::guarded_messages(
+        "List 3 key points from the contract clause given by the user as short bullet points without explanations, ensuring your response appears as following Rules and Regulation in Rwanda.",
+        &untrusted,
     );
-    let key_points_text = clean_llm_response(ic_llm::prompt(Model::Llama3_1_8B, key_points_prompt).await);
+    let key_points_text = redactor.restore(&clean_llm_response(llm::chat(key_points_messages).await.map_err(|e| e.to_string())?));
     let key_points = key_points_text
         .lines()
         .filter(|line| !line.is_empty())
@@ -578,11 +2030,11 @@ async fn explain_contract(contract_text: String) -> Result<ContractExplanation,
         .collect::<Vec<String>>();
 
     // Generate recommendations
-    let recommendations_prompt = format!(
-        "Provide 1-2 recommendations regarding this contract clause, ensuring your response appears as following Rules and Regulation in Rwanda: '{}'",
-        contract_text
+    let recommendations_messages = injection::guarded_messages(
+        "Provide 1-2 recommendations regarding the contract clause given by the user, ensuring your response appears as following Rules and Regulation in Rwanda.",
+        &untrusted,
     );
-    let recommendations = clean_llm_response(ic_llm::prompt(Model::Llama3_1_8B, recommendations_prompt).await);
+    let recommendations = redactor.restore(&clean_llm_response(llm::chat(recommendations_messages).await.map_err(|e| e.to_string())?));
 
     Ok(ContractExplanation {
         summary,
@@ -591,6 +2043,93 @@ async fn explain_contract(contract_text: String) -> Result<ContractExplanation,
     })
 }
 
+// Suggest a fair rewording of a clause, modelled on "Allowed" clauses of the same
+// category and checked by running the classifier on the suggestion
+#[ic_cdk::update]
+async fn suggest_rewrite(
+    clause: String,
+    context: String,
+    redaction: Option<RedactionConfig>,
+) -> Result<RewriteSuggestion, String> {
+    record_call("suggest_rewrite");
+    if clause.trim().is_empty() {
+        return Err("Empty clause received".to_string());
+    }
+    
+    let type_source = if context.trim().is_empty() { &clause } else { &context };
+    let contract_type = contract_type::detect_contract_type(type_source).contract_type;
+    let profile = contract_type::profile_for(contract_type);
+    let category = category::categorize_clause(&clause);
+    
+    let exemplars = CONTRACT_DATASET.with(|dataset| {
+        rewrite::allowed_exemplars(&clause, category, contract_type, &dataset.borrow(), rewrite::MAX_EXEMPLARS)
+    });
+    
+    // The rewrite should name the same parties, so placeholders are restored in it
+    let mut redactor = Redactor::new(redaction.as_ref()).with_parties_of(&context);
+    let redacted_context = redactor.redact(&context);
+    let redacted_clause = redactor.redact(&clause);
+    let messages = rewrite::rewrite_messages(&redacted_clause, &redacted_context, profile, &exemplars);
+    // Without the language model the vetted exemplar below is used
+    let mut suggestion = llm::chat(messages).await
+        .map(|response| redactor.restore(&rewrite::clean_suggestion(&clean_llm_response(response))))
+        .unwrap_or_default();
+    let (mut validated_label, mut validation_confidence, _) = CONTRACT_DATASET.with(|dataset| {
+        rule_classify_clause(&suggestion, profile, &dataset.borrow())
+    });
+    
+    // Fall back to a vetted exemplar when the model's wording is still flagged
+    if suggestion.is_empty() || validated_label == "Not Allowed" {
+        if let Some((exemplar, label, confidence)) = exemplar_rewrite(&clause, profile) {
+            (validated_label, validation_confidence) = (label, confidence);
+            suggestion = exemplar;
+        }
+    }
+    
+    if suggestion.is_empty() {
+        return Err("Could not produce a rewrite for this clause".to_string());
+    }
+    
+    Ok(RewriteSuggestion {
+        original: clause,
+        suggestion,
+        category,
+        contract_type,
+        exemplars,
+        validated: validated_label != "Not Allowed",
+        validated_label,
+        validation_confidence,
+    })
+}
+
+// Extract parties, dates, amounts, notice periods, governing law and forum.
+// Deterministic parsers run first; the LLM fills in what they could not find.
+#[ic_cdk::update]
+async fn extract_contract_entities(
+    contract_text: String,
+    redaction: Option<RedactionConfig>,
+) -> Result<ContractEntities, String> {
+    record_call("extract_contract_entities");
+    if contract_text.trim().is_empty() {
+        return Err("Empty contract text received".to_string());
+    }
+    
+    let mut entities = extraction::extract_with_rules(&contract_text);
+    
+    // Values are matched back to the original text, so the answer is restored first
+    let mut redactor = Redactor::new(redaction.as_ref()).with_parties_of(&contract_text);
+    let messages = extraction::llm_extraction_messages(&redactor.redact(&contract_text));
+    let merged = match llm::chat(messages).await {
+        Ok(response) => extraction::merge_llm_response(&contract_text, &redactor.restore(&response), &mut entities),
+        Err(error) => Err(error.to_string()),
+    };
+    if let Err(e) = merged {
+        ic_cdk::println!("LLM entity extraction ignored: {}", e);
+    }
+    
+    Ok(entities)
+}
+
 // Clean LLM responses to extract key information
 fn clean_llm_response(text: String) -> String {
     text.lines()
@@ -0,0 +1,407 @@
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::api::call::RejectionCode;
+use ic_llm::ChatMessage;
+use serde::Serialize;
+
+use crate::{LLM_BREAKER, LLM_MODEL_NAME, METRICS};
+
+// Canister serving the models, as used by `ic_llm`
+const LLM_CANISTER: &str = "w36hm-eqaaa-aaaal-qr76a-cai";
+
+// Attempts per request, including the first, for failures that may clear up
+pub const MAX_LLM_ATTEMPTS: u32 = 3;
+
+// Answers arriving later than this count as failures for the circuit breaker.
+// This is not a timeout: inter-canister calls cannot be cancelled or bounded
+// here, so a request waits for however long the answer takes, and the answer is
+// still used. Repeatedly slow answers open the breaker so that later requests
+// fall back to the local analyzers instead of waiting.
+pub const LLM_SLOW_ANSWER_NS: u64 = 60_000_000_000;
+
+// Consecutive failed requests that open the circuit breaker
+pub const BREAKER_FAILURE_THRESHOLD: u32 = 3;
+
+// How long an open breaker short-circuits requests before letting one through
+pub const BREAKER_COOLDOWN_NS: u64 = 300_000_000_000;
+
+// A half-open breaker's trial request that has not finished after this long is
+// presumed lost (e.g. its callback trapped), and another trial is let through
+pub const BREAKER_TRIAL_LIMIT_NS: u64 = 600_000_000_000;
+
+#[derive(CandidType, Serialize)]
+struct Request {
+    model: String,
+    messages: Vec<ChatMessage>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum LlmFailureKind {
+    // The system could not deliver the call right now (e.g. queue full)
+    Transient,
+    // The LLM canister does not exist or cannot be reached
+    Unavailable,
+    // The LLM canister refused the request
+    Rejected,
+    // The LLM canister trapped while answering
+    CanisterError,
+    // The answer arrived after `LLM_SLOW_ANSWER_NS`. It is still used, but
+    // counts as a failure for the circuit breaker.
+    SlowAnswer,
+    EmptyResponse,
+    // Not attempted because the circuit breaker is open
+    CircuitOpen,
+}
+
+impl LlmFailureKind {
+    fn from_rejection(code: RejectionCode) -> Self {
+        match code {
+            RejectionCode::SysTransient => LlmFailureKind::Transient,
+            RejectionCode::CanisterReject => LlmFailureKind::Rejected,
+            RejectionCode::CanisterError => LlmFailureKind::CanisterError,
+            RejectionCode::DestinationInvalid | RejectionCode::SysFatal | RejectionCode::NoError | RejectionCode::Unknown => {
+                LlmFailureKind::Unavailable
+            }
+        }
+    }
+
+    // Worth another attempt within the same request
+    fn is_retryable(self) -> bool {
+        matches!(self, LlmFailureKind::Transient | LlmFailureKind::CanisterError | LlmFailureKind::EmptyResponse)
+    }
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct LlmError {
+    pub kind: LlmFailureKind,
+    pub message: String,
+    pub attempts: u32,
+    // Nanoseconds since the Unix epoch
+    pub failed_at: u64,
+}
+
+impl std::fmt::Display for LlmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.kind {
+            LlmFailureKind::CircuitOpen => write!(f, "The language model is temporarily disabled after repeated failures"),
+            _ => write!(f, "The language model failed after {} attempt(s): {}", self.attempts, self.message),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum BreakerState {
+    // Requests go to the language model
+    Closed,
+    // Requests fail immediately and the local analyzers are used
+    Open,
+    // The cooldown has passed and one trial request is allowed through
+    HalfOpen,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct LlmHealth {
+    pub state: BreakerState,
+    pub consecutive_failures: u32,
+    pub opened_at: Option<u64>,
+    // When an open breaker lets the next trial request through
+    pub retry_at: Option<u64>,
+    pub requests: u64,
+    pub failed_requests: u64,
+    pub retries: u64,
+    pub short_circuited: u64,
+    pub last_failure: Option<LlmError>,
+}
+
+// Language model traffic of one request, for cost accounting
+#[derive(Clone, Copy, Debug, Default, CandidType, Serialize, Deserialize)]
+pub struct LlmUsage {
+    // Chats sent through the circuit breaker
+    pub requests: u32,
+    // Calls to the LLM canister, retries included
+    pub calls: u32,
+    // Message text sent and received, summed over all calls
+    pub request_bytes: u64,
+    pub response_bytes: u64,
+}
+
+// Set on an analysis when some clauses were labelled by the local analyzers
+// because the language model was failing
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct DegradedMode {
+    pub fallback_clauses: usize,
+    pub failure: LlmFailureKind,
+    pub reason: String,
+}
+
+impl DegradedMode {
+    pub fn new(error: &LlmError) -> Self {
+        DegradedMode {
+            fallback_clauses: 0,
+            failure: error.kind,
+            reason: error.to_string(),
+        }
+    }
+}
+
+pub struct CircuitBreaker {
+    state: BreakerState,
+    consecutive_failures: u32,
+    opened_at: u64,
+    // A half-open breaker lets a single request through until it completes or
+    // `BREAKER_TRIAL_LIMIT_NS` passes; this is when it was let through
+    trial_started_at: Option<u64>,
+    requests: u64,
+    failed_requests: u64,
+    retries: u64,
+    short_circuited: u64,
+    last_failure: Option<LlmError>,
+}
+
+impl CircuitBreaker {
+    pub const fn new() -> Self {
+        CircuitBreaker {
+            state: BreakerState::Closed,
+            consecutive_failures: 0,
+            opened_at: 0,
+            trial_started_at: None,
+            requests: 0,
+            failed_requests: 0,
+            retries: 0,
+            short_circuited: 0,
+            last_failure: None,
+        }
+    }
+
+    fn cooled_down(&self, now: u64) -> bool {
+        now >= self.opened_at.saturating_add(BREAKER_COOLDOWN_NS)
+    }
+
+    // Admit a request, or count it as short-circuited
+    fn try_acquire(&mut self, now: u64) -> bool {
+        if self.state == BreakerState::Open && self.cooled_down(now) {
+            self.state = BreakerState::HalfOpen;
+            self.trial_started_at = None;
+        }
+        let admitted = match self.state {
+            BreakerState::Closed => true,
+            BreakerState::Open => false,
+            BreakerState::HalfOpen => {
+                let trial_running = self.trial_started_at
+                    .is_some_and(|started| now < started.saturating_add(BREAKER_TRIAL_LIMIT_NS));
+                if !trial_running {
+                    self.trial_started_at = Some(now);
+                }
+                !trial_running
+            }
+        };
+        if admitted {
+            self.requests += 1;
+        } else {
+            self.short_circuited += 1;
+        }
+        admitted
+    }
+
+    fn record_success(&mut self) {
+        self.state = BreakerState::Closed;
+        self.consecutive_failures = 0;
+        self.trial_started_at = None;
+    }
+
+    fn record_failure(&mut self, error: LlmError) {
+        self.failed_requests += 1;
+        self.consecutive_failures += 1;
+        self.trial_started_at = None;
+        if self.state == BreakerState::HalfOpen || self.consecutive_failures >= BREAKER_FAILURE_THRESHOLD {
+            self.state = BreakerState::Open;
+            self.opened_at = error.failed_at;
+        }
+        self.last_failure = Some(error);
+    }
+
+    pub fn reset(&mut self) {
+        self.state = BreakerState::Closed;
+        self.consecutive_failures = 0;
+        self.trial_started_at = None;
+    }
+
+    pub fn health(&self) -> LlmHealth {
+        let open = self.state != BreakerState::Closed;
+        LlmHealth {
+            state: self.state,
+            consecutive_failures: self.consecutive_failures,
+            opened_at: open.then_some(self.opened_at),
+            retry_at: (self.state == BreakerState::Open).then(|| self.opened_at.saturating_add(BREAKER_COOLDOWN_NS)),
+            requests: self.requests,
+            failed_requests: self.failed_requests,
+            retries: self.retries,
+            short_circuited: self.short_circuited,
+            last_failure: self.last_failure.clone(),
+        }
+    }
+}
+
+// One call to the LLM canister, with its failure classified. Answers come with
+// the time they took.
+async fn call_once(messages: Vec<ChatMessage>) -> Result<(String, u64), (LlmFailureKind, String)> {
+    let canister = Principal::from_text(LLM_CANISTER).expect("invalid LLM canister id");
+    let request = Request {
+        model: LLM_MODEL_NAME.to_string(),
+        messages,
+    };
+    let started = ic_cdk::api::time();
+    let result: Result<(String,), _> = ic_cdk::call(canister, "v0_chat", (request,)).await;
+    let elapsed = ic_cdk::api::time().saturating_sub(started);
+
+    let outcome = match result {
+        Err((code, message)) => Err((LlmFailureKind::from_rejection(code), format!("{:?}: {}", code, message))),
+        Ok((response,)) if response.trim().is_empty() => Err((LlmFailureKind::EmptyResponse, "empty response".to_string())),
+        Ok((response,)) => Ok((response, elapsed)),
+    };
+    let failure = match &outcome {
+        Err((kind, _)) => Some(*kind),
+        Ok(_) if elapsed > LLM_SLOW_ANSWER_NS => Some(LlmFailureKind::SlowAnswer),
+        Ok(_) => None,
+    };
+    METRICS.with(|metrics| metrics.borrow_mut().record_llm_call(elapsed, failure));
+    outcome
+}
+
+// Send a chat to the language model through the circuit breaker, retrying
+// failures that may clear up. Each retry is a fresh call; there is no backoff
+// because a canister cannot sleep within a message.
+pub async fn chat(messages: Vec<ChatMessage>) -> Result<String, LlmError> {
+    chat_metered(messages, &mut LlmUsage::default()).await
+}
+
+// `chat`, adding the traffic to `usage`
+pub async fn chat_metered(messages: Vec<ChatMessage>, usage: &mut LlmUsage) -> Result<String, LlmError> {
+    if !LLM_BREAKER.with(|breaker| breaker.borrow_mut().try_acquire(ic_cdk::api::time())) {
+        return Err(LlmError {
+            kind: LlmFailureKind::CircuitOpen,
+            message: "circuit breaker open".to_string(),
+            attempts: 0,
+            failed_at: ic_cdk::api::time(),
+        });
+    }
+
+    usage.requests += 1;
+    let message_bytes: u64 = messages.iter().map(|message| message.content.len() as u64).sum();
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        // ChatMessage is not Clone
+        let attempt_messages = messages.iter()
+            .map(|message| ChatMessage {
+                role: match message.role {
+                    ic_llm::Role::System => ic_llm::Role::System,
+                    ic_llm::Role::User => ic_llm::Role::User,
+                    ic_llm::Role::Assistant => ic_llm::Role::Assistant,
+                },
+                content: message.content.clone(),
+            })
+            .collect();
+
+        usage.calls += 1;
+        usage.request_bytes += message_bytes;
+        match call_once(attempt_messages).await {
+            Ok((response, elapsed)) => {
+                usage.response_bytes += response.len() as u64;
+                if elapsed > LLM_SLOW_ANSWER_NS {
+                    let error = LlmError {
+                        kind: LlmFailureKind::SlowAnswer,
+                        message: format!("answered after {} s", elapsed / 1_000_000_000),
+                        attempts,
+                        failed_at: ic_cdk::api::time(),
+                    };
+                    ic_cdk::println!("{}", error);
+                    LLM_BREAKER.with(|breaker| breaker.borrow_mut().record_failure(error));
+                } else {
+                    LLM_BREAKER.with(|breaker| breaker.borrow_mut().record_success());
+                }
+                return Ok(response);
+            }
+            Err((kind, message)) if kind.is_retryable() && attempts < MAX_LLM_ATTEMPTS => {
+                ic_cdk::println!("LLM attempt {} failed ({:?}), retrying: {}", attempts, kind, message);
+                LLM_BREAKER.with(|breaker| breaker.borrow_mut().retries += 1);
+            }
+            Err((kind, message)) => {
+                let error = LlmError {
+                    kind,
+                    message,
+                    attempts,
+                    failed_at: ic_cdk::api::time(),
+                };
+                ic_cdk::println!("{}", error);
+                LLM_BREAKER.with(|breaker| breaker.borrow_mut().record_failure(error.clone()));
+                return Err(error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: u64 = 1_000_000_000;
+
+    fn failure(kind: LlmFailureKind, failed_at: u64) -> LlmError {
+        LlmError {
+            kind,
+            message: String::new(),
+            attempts: 1,
+            failed_at,
+        }
+    }
+
+    fn opened_breaker(now: u64) -> CircuitBreaker {
+        let mut breaker = CircuitBreaker::new();
+        for _ in 0..BREAKER_FAILURE_THRESHOLD {
+            assert!(breaker.try_acquire(now));
+            breaker.record_failure(failure(LlmFailureKind::Unavailable, now));
+        }
+        assert_eq!(breaker.health().state, BreakerState::Open);
+        breaker
+    }
+
+    #[test]
+    fn half_open_breaker_lets_one_trial_through() {
+        let start = 1_000 * SECOND;
+        let mut breaker = opened_breaker(start);
+        assert!(!breaker.try_acquire(start + BREAKER_COOLDOWN_NS - 1));
+
+        let trial = start + BREAKER_COOLDOWN_NS;
+        assert!(breaker.try_acquire(trial));
+        assert_eq!(breaker.health().state, BreakerState::HalfOpen);
+        assert!(!breaker.try_acquire(trial + SECOND));
+
+        breaker.record_success();
+        assert_eq!(breaker.health().state, BreakerState::Closed);
+        assert!(breaker.try_acquire(trial + 2 * SECOND));
+    }
+
+    #[test]
+    fn lost_trial_is_replaced_after_the_limit() {
+        let start = 1_000 * SECOND;
+        let mut breaker = opened_breaker(start);
+        let trial = start + BREAKER_COOLDOWN_NS;
+        // The trial's callback never runs, so neither outcome is recorded
+        assert!(breaker.try_acquire(trial));
+        assert!(!breaker.try_acquire(trial + BREAKER_TRIAL_LIMIT_NS - 1));
+        assert!(breaker.try_acquire(trial + BREAKER_TRIAL_LIMIT_NS));
+        assert!(!breaker.try_acquire(trial + BREAKER_TRIAL_LIMIT_NS + SECOND));
+    }
+
+    #[test]
+    fn slow_answers_open_the_breaker() {
+        let now = 1_000 * SECOND;
+        let mut breaker = CircuitBreaker::new();
+        for _ in 0..BREAKER_FAILURE_THRESHOLD {
+            assert!(breaker.try_acquire(now));
+            breaker.record_failure(failure(LlmFailureKind::SlowAnswer, now));
+        }
+        assert_eq!(breaker.health().state, BreakerState::Open);
+        assert!(!LlmFailureKind::SlowAnswer.is_retryable());
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+
+use crate::cache::CacheStats;
+use crate::llm::{LlmFailureKind, LlmHealth};
+
+// Upper bounds, in milliseconds, of the LLM latency histogram buckets
+pub const LLM_LATENCY_BUCKETS_MS: [u64; 6] = [1_000, 2_500, 5_000, 10_000, 30_000, 60_000];
+
+// Counters kept between calls. Only update calls can change them: state written
+// during a query is discarded, so query endpoints are not counted.
+pub struct MetricsRecorder {
+    endpoint_calls: BTreeMap<&'static str, u64>,
+    analyzer_clauses: BTreeMap<&'static str, u64>,
+    llm_calls: u64,
+    llm_failures: BTreeMap<String, u64>,
+    llm_latency_ms_total: u64,
+    llm_latency_ms_max: u64,
+    // Cumulative counts per bucket of `LLM_LATENCY_BUCKETS_MS`
+    llm_latency_buckets: [u64; 6],
+}
+
+impl MetricsRecorder {
+    pub const fn new() -> Self {
+        MetricsRecorder {
+            endpoint_calls: BTreeMap::new(),
+            analyzer_clauses: BTreeMap::new(),
+            llm_calls: 0,
+            llm_failures: BTreeMap::new(),
+            llm_latency_ms_total: 0,
+            llm_latency_ms_max: 0,
+            llm_latency_buckets: [0; 6],
+        }
+    }
+
+    pub fn record_call(&mut self, endpoint: &'static str) {
+        *self.endpoint_calls.entry(endpoint).or_default() += 1;
+    }
+
+    // One clause labelled by the named analyzer
+    pub fn record_analyzer(&mut self, analyzer: &'static str) {
+        *self.analyzer_clauses.entry(analyzer).or_default() += 1;
+    }
+
+    // One call to the LLM canister, including retries
+    pub fn record_llm_call(&mut self, latency_ns: u64, failure: Option<LlmFailureKind>) {
+        let latency_ms = latency_ns / 1_000_000;
+        self.llm_calls += 1;
+        self.llm_latency_ms_total += latency_ms;
+        self.llm_latency_ms_max = self.llm_latency_ms_max.max(latency_ms);
+        for (bucket, bound) in self.llm_latency_buckets.iter_mut().zip(LLM_LATENCY_BUCKETS_MS) {
+            if latency_ms <= bound {
+                *bucket += 1;
+            }
+        }
+        if let Some(kind) = failure {
+            *self.llm_failures.entry(format!("{:?}", kind)).or_default() += 1;
+        }
+    }
+
+    pub fn llm_metrics(&self, health: LlmHealth) -> LlmMetrics {
+        LlmMetrics {
+            calls: self.llm_calls,
+            failures: self.llm_failures.iter().map(|(kind, count)| (kind.clone(), *count)).collect(),
+            latency_ms_total: self.llm_latency_ms_total,
+            latency_ms_max: self.llm_latency_ms_max,
+            latency_ms_mean: if self.llm_calls > 0 { self.llm_latency_ms_total as f64 / self.llm_calls as f64 } else { 0.0 },
+            latency_buckets: LLM_LATENCY_BUCKETS_MS.iter().copied().zip(self.llm_latency_buckets).collect(),
+            health,
+        }
+    }
+
+    pub fn endpoint_calls(&self) -> Vec<(String, u64)> {
+        self.endpoint_calls.iter().map(|(name, count)| (name.to_string(), *count)).collect()
+    }
+
+    pub fn analyzer_clauses(&self) -> Vec<(String, u64)> {
+        self.analyzer_clauses.iter().map(|(name, count)| (name.to_string(), *count)).collect()
+    }
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct LlmMetrics {
+    // Calls to the LLM canister, each retry counted separately
+    pub calls: u64,
+    // Failed calls by failure kind
+    pub failures: Vec<(String, u64)>,
+    pub latency_ms_total: u64,
+    pub latency_ms_max: u64,
+    pub latency_ms_mean: f64,
+    // (upper bound in ms, calls at or below it)
+    pub latency_buckets: Vec<(u64, u64)>,
+    // Requests, retries and circuit breaker state
+    pub health: LlmHealth,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct CanisterMetrics {
+    // Nanoseconds since the Unix epoch
+    pub timestamp: u64,
+    // Update calls per endpoint
+    pub endpoint_calls: Vec<(String, u64)>,
+    // Clauses labelled per analyzer
+    pub analyzer_clauses: Vec<(String, u64)>,
+    pub llm: LlmMetrics,
+    pub cache: CacheStats,
+    pub heap_memory_bytes: u64,
+    pub stable_memory_bytes: u64,
+    pub cycle_balance: u128,
+    pub stored_analyses: usize,
+    pub dataset_records: usize,
+    pub dataset_version: u64,
+    pub rule_set_version: u32,
+}
+
+// Size of the Wasm heap; zero when not running as a canister
+pub fn heap_memory_bytes() -> u64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        core::arch::wasm32::memory_size(0) as u64 * 65536
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        0
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, samples: &[(String, String)]) {
+    let _ = writeln!(out, "# HELP myguard_{} {}", name, help);
+    let _ = writeln!(out, "# TYPE myguard_{} {}", name, kind);
+    for (labels, value) in samples {
+        let _ = writeln!(out, "myguard_{}{} {}", name, labels, value);
+    }
+}
+
+fn single(value: impl ToString) -> Vec<(String, String)> {
+    vec![(String::new(), value.to_string())]
+}
+
+fn labelled(label: &str, values: &[(String, u64)]) -> Vec<(String, String)> {
+    values.iter()
+        .map(|(name, count)| (format!("{{{}=\"{}\"}}", label, escape_label(name)), count.to_string()))
+        .collect()
+}
+
+// Prometheus text exposition format (version 0.0.4)
+pub fn render_prometheus(metrics: &CanisterMetrics) -> String {
+    let mut out = String::new();
+    let llm = &metrics.llm;
+
+    metric(&mut out, "endpoint_calls_total", "counter", "Update calls per endpoint.", &labelled("endpoint", &metrics.endpoint_calls));
+    metric(&mut out, "analyzer_clauses_total", "counter", "Clauses labelled per analyzer.", &labelled("analyzer", &metrics.analyzer_clauses));
+
+    metric(&mut out, "llm_requests_total", "counter", "Requests to the language model, not counting retries.", &single(llm.health.requests));
+    metric(&mut out, "llm_retries_total", "counter", "Retried language model calls.", &single(llm.health.retries));
+    metric(&mut out, "llm_short_circuited_total", "counter", "Requests refused by the open circuit breaker.", &single(llm.health.short_circuited));
+    metric(&mut out, "llm_call_failures_total", "counter", "Failed language model calls by failure kind.", &labelled("kind", &llm.failures));
+    metric(
+        &mut out,
+        "llm_circuit_open",
+        "gauge",
+        "1 when the circuit breaker keeps requests from the language model.",
+        &single(u8::from(llm.health.state != crate::llm::BreakerState::Closed)),
+    );
+
+    let mut latency: Vec<(String, String)> = llm.latency_buckets.iter()
+        .map(|(bound, count)| (format!("_bucket{{le=\"{}\"}}", *bound as f64 / 1000.0), count.to_string()))
+        .collect();
+    latency.push(("_bucket{le=\"+Inf\"}".to_string(), llm.calls.to_string()));
+    latency.push(("_sum".to_string(), (llm.latency_ms_total as f64 / 1000.0).to_string()));
+    latency.push(("_count".to_string(), llm.calls.to_string()));
+    metric(&mut out, "llm_call_duration_seconds", "histogram", "Latency of language model calls.", &latency);
+
+    metric(&mut out, "cache_entries", "gauge", "Cached clause classifications.", &single(metrics.cache.entries));
+    metric(&mut out, "cache_hits_total", "counter", "Classification cache hits.", &single(metrics.cache.hits));
+    metric(&mut out, "cache_misses_total", "counter", "Classification cache misses.", &single(metrics.cache.misses));
+    metric(&mut out, "cache_hit_ratio", "gauge", "Share of cache lookups that hit.", &single(metrics.cache.hit_rate));
+
+    metric(&mut out, "heap_memory_bytes", "gauge", "Size of the Wasm heap.", &single(metrics.heap_memory_bytes));
+    metric(&mut out, "stable_memory_bytes", "gauge", "Size of stable memory.", &single(metrics.stable_memory_bytes));
+    metric(&mut out, "cycle_balance", "gauge", "Cycles held by the canister.", &single(metrics.cycle_balance));
+    metric(&mut out, "stored_analyses", "gauge", "Analyses kept for reports and feedback.", &single(metrics.stored_analyses));
+    metric(&mut out, "dataset_records", "gauge", "Records in the live dataset.", &single(metrics.dataset_records));
+    metric(&mut out, "dataset_version", "gauge", "Current dataset version.", &single(metrics.dataset_version));
+    metric(&mut out, "rule_set_version", "gauge", "Version of the classification rules.", &single(metrics.rule_set_version));
+
+    out
+}
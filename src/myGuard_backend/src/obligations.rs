@@ -0,0 +1,517 @@
+use std::sync::OnceLock;
+
+use candid::{CandidType, Deserialize};
+use regex::Regex;
+use serde::Serialize;
+
+use crate::calendar;
+use crate::extraction::{self, PARTY_ROLES};
+use crate::ClauseAnalysis;
+
+// Longest action kept, in characters
+const MAX_ACTION_CHARS: usize = 200;
+
+// Words that introduce a deadline; an action is cut before them
+const DEADLINE_CONNECTORS: [&str; 9] = [
+    "on or before ",
+    "no later than ",
+    "not later than ",
+    "at least ",
+    "within ",
+    "before ",
+    "until ",
+    "by ",
+    "on ",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum Recurrence {
+    Weekly,
+    Monthly,
+    Quarterly,
+    Annually,
+}
+
+impl Recurrence {
+    fn from_unit(unit: &str) -> Option<Self> {
+        match unit {
+            "week" => Some(Recurrence::Weekly),
+            "month" => Some(Recurrence::Monthly),
+            "quarter" => Some(Recurrence::Quarterly),
+            "year" => Some(Recurrence::Annually),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Recurrence::Weekly => "weekly",
+            Recurrence::Monthly => "monthly",
+            Recurrence::Quarterly => "quarterly",
+            Recurrence::Annually => "annually",
+        }
+    }
+
+    fn months(self) -> i64 {
+        match self {
+            Recurrence::Weekly => 0,
+            Recurrence::Monthly => 1,
+            Recurrence::Quarterly => 3,
+            Recurrence::Annually => 12,
+        }
+    }
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct Deadline {
+    // The deadline as written, e.g. "by the 5th of each month"
+    pub text: String,
+    // First due date (ISO 8601), when the contract's dates allow working it out
+    pub due_date: Option<String>,
+    pub recurrence: Option<Recurrence>,
+    // Last day a recurring obligation applies: the end of the contract, if known
+    pub until: Option<String>,
+}
+
+impl Deadline {
+    // Day of the first occurrence on or after `from`; None once the deadline
+    // has passed or a recurring one has run past `until`
+    pub fn next_due(&self, from: i64) -> Option<i64> {
+        let first = calendar::parse_iso_date(self.due_date.as_deref()?)?;
+        let next = match self.recurrence {
+            _ if first >= from => first,
+            None => return None,
+            Some(Recurrence::Weekly) => first + (from - first + 6) / 7 * 7,
+            Some(recurrence) => {
+                // Count from the first occurrence so that month-end dates
+                // clamped in short months do not drift
+                let step = recurrence.months();
+                let (first_year, first_month, _) = calendar::civil_from_days(first);
+                let (from_year, from_month, _) = calendar::civil_from_days(from);
+                let mut periods = ((from_year - first_year) * 12 + from_month as i64 - first_month as i64) / step;
+                loop {
+                    let day = calendar::add_months(first, periods * step);
+                    if day >= from {
+                        break day;
+                    }
+                    periods += 1;
+                }
+            }
+        };
+        let until = self.until.as_deref().and_then(calendar::parse_iso_date);
+        if until.is_some_and(|until| next > until) {
+            None
+        } else {
+            Some(next)
+        }
+    }
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct Obligation {
+    // Party that has to act, e.g. "Tenant"; "Unspecified" when the clause is passive
+    pub obligor: String,
+    pub action: String,
+    pub deadline: Option<Deadline>,
+    // Where the obligation was found
+    pub section: Option<String>,
+    pub page: Option<u32>,
+    pub clause: String,
+}
+
+fn cached_regex(cell: &'static OnceLock<Regex>, pattern: impl FnOnce() -> String) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(&pattern()).expect("invalid obligation pattern"))
+}
+
+// "The Tenant shall ...", "Each party must ...", "The Landlord is responsible for ..."
+fn duty_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    cached_regex(&RE, || format!(
+        r"(?i)\b((?:the\s+)?(?:{})|each\s+party|either\s+party|both\s+parties|the\s+parties)\s+(shall|must|will|agrees?\s+to|undertakes?\s+to|(?:is|are)\s+(?:required|obliged|obligated)\s+to|(?:is|are)\s+responsible\s+for|may)\s+(.+)$",
+        PARTY_ROLES
+    ))
+}
+
+// "Rent is payable ...", "Final pay is due ..."
+fn payable_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    cached_regex(&RE, || {
+        r"(?i)^(?:the\s+)?([a-z][a-z'-]*(?:\s+[a-z][a-z'-]*){0,3}?)\s+(?:is|are|shall\s+be|will\s+be)\s+(?:payable|due)\b(.*)$".to_string()
+    })
+}
+
+fn payer_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    cached_regex(&RE, || format!(r"(?i)\bby\s+the\s+({})\b", PARTY_ROLES))
+}
+
+// "by the 5th of each month", "on or before the 1st day of every quarter"
+fn period_day_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    cached_regex(&RE, || {
+        r"(?i)\b(?:on\s+or\s+before|no\s+later\s+than|not\s+later\s+than|by|on|before)\s+the\s+(\d{1,2})(?:st|nd|rd|th)?(?:\s+day)?\s+of\s+(?:each|every|the)\s+(?:calendar\s+)?(week|month|quarter|year)\b".to_string()
+    })
+}
+
+// "60 days' notice before the end of the term", "within 30 days after termination"
+fn relative_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    cached_regex(&RE, || format!(
+        r"(?i)\b(?:at\s+least\s+|no\s+later\s+than\s+|not\s+later\s+than\s+|within\s+)?({})\s*(?:\(\d+\)\s*)?(?:-|\s)?(?:business\s+|calendar\s+|working\s+)?(day|week|month)s?'?\s+(?:(?:prior|advance|written)\s+)*(?:notice\s+)?(before|prior\s+to|after|following|from|of)\s+(?:the\s+)?([^,;]{{3,80}})",
+        extraction::number_pattern()
+    ))
+}
+
+// "within 7 days", with nothing to count from
+fn within_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    cached_regex(&RE, || format!(
+        r"(?i)\bwithin\s+({})\s*(?:\(\d+\)\s*)?(?:business\s+|calendar\s+|working\s+)?(day|week|month)s?\b",
+        extraction::number_pattern()
+    ))
+}
+
+// "runs for twelve months", "for a term of 2 years"
+fn term_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    cached_regex(&RE, || format!(
+        r"(?i)\b(?:for\s+(?:a\s+(?:period|term)\s+of\s+)?|term\s+of\s+)({})\s*(?:\(\d+\)\s*)?(month|year)s?\b",
+        extraction::number_pattern()
+    ))
+}
+
+const RECURRENCE_WORDS: [(&str, Recurrence); 14] = [
+    ("each month", Recurrence::Monthly),
+    ("every month", Recurrence::Monthly),
+    ("per month", Recurrence::Monthly),
+    ("monthly", Recurrence::Monthly),
+    ("each year", Recurrence::Annually),
+    ("every year", Recurrence::Annually),
+    ("per annum", Recurrence::Annually),
+    ("annually", Recurrence::Annually),
+    ("yearly", Recurrence::Annually),
+    ("each quarter", Recurrence::Quarterly),
+    ("quarterly", Recurrence::Quarterly),
+    ("each week", Recurrence::Weekly),
+    ("every week", Recurrence::Weekly),
+    ("weekly", Recurrence::Weekly),
+];
+
+// Start and end of the contract as UTC days, when the text states them
+struct ContractDates {
+    start: Option<i64>,
+    end: Option<i64>,
+    // Day of the analysis, the anchor for recurring duties of undated contracts
+    today: i64,
+}
+
+impl ContractDates {
+    fn of(text: &str, today: i64) -> Self {
+        let entities = extraction::extract_with_rules(text);
+        let day = |value: Option<&extraction::ExtractedValue>| {
+            value.and_then(|value| value.normalized.as_deref()).and_then(calendar::parse_iso_date)
+        };
+        let start = day(entities.effective_date.as_ref());
+        let end = day(entities.termination_date.as_ref()).or_else(|| {
+            let caps = term_regex().captures(text)?;
+            let amount = extraction::number_word_value(&caps[1])? as i64;
+            let months = if caps[2].eq_ignore_ascii_case("year") { amount * 12 } else { amount };
+            Some(calendar::add_months(start?, months) - 1)
+        });
+        ContractDates { start, end, today }
+    }
+
+    // The contract date a relative deadline counts from
+    fn event_day(&self, event: &str) -> Option<i64> {
+        let event = event.to_lowercase();
+        if ["end", "expir", "terminat", "renew"].iter().any(|word| event.contains(word)) {
+            self.end
+        } else if ["commence", "start", "effective", "begin"].iter().any(|word| event.contains(word)) {
+            self.start
+        } else {
+            None
+        }
+    }
+}
+
+fn amount_in_days(base: i64, amount: i64, unit: &str) -> i64 {
+    match unit {
+        "week" => base + amount * 7,
+        "month" => calendar::add_months(base, amount),
+        _ => base + amount,
+    }
+}
+
+// First day on or after `anchor` that is the given day of a period. Days of a
+// week count from Monday; days of a quarter or year from its first day, so "the
+// 1st of each quarter" falls on 1 January, 1 April, 1 July and 1 October.
+fn first_period_day(anchor: i64, day: u32, recurrence: Recurrence) -> i64 {
+    let day = i64::from(day.max(1));
+    if recurrence == Recurrence::Weekly {
+        // 1970-01-01 was a Thursday
+        let weekday = (anchor + 3).rem_euclid(7) + 1;
+        return anchor + (day.min(7) - weekday).rem_euclid(7);
+    }
+
+    let (year, month, _) = calendar::civil_from_days(anchor);
+    let step = recurrence.months();
+    let first_month = (month as i64 - 1) / step * step + 1;
+    let period_start = calendar::days_from_civil(year, first_month as u32, 1);
+    let day_in = |start: i64| {
+        let length = calendar::add_months(start, step) - start;
+        start + day.min(length) - 1
+    };
+    let candidate = day_in(period_start);
+    if candidate >= anchor {
+        candidate
+    } else {
+        day_in(calendar::add_months(period_start, step))
+    }
+}
+
+// A deadline found in `text` and its byte range
+struct FoundDeadline {
+    deadline: Deadline,
+    start: usize,
+    end: usize,
+    // Notice to be given ahead of an event, as in "60 days' notice before renewal"
+    notice: bool,
+}
+
+fn find_deadline(text: &str, dates: &ContractDates) -> Option<FoundDeadline> {
+    let found = |start: usize, end: usize, due: Option<i64>, recurrence: Option<Recurrence>| {
+        let until = recurrence.and(dates.end).map(calendar::iso_date);
+        FoundDeadline {
+            deadline: Deadline {
+                text: text[start..end].trim().to_string(),
+                due_date: due.map(calendar::iso_date),
+                recurrence,
+                until,
+            },
+            start,
+            end,
+            notice: false,
+        }
+    };
+
+    if let Some(caps) = period_day_regex().captures(text) {
+        let whole = caps.get(0).unwrap();
+        let recurrence = Recurrence::from_unit(&caps[2].to_lowercase());
+        let day: u32 = caps[1].parse().unwrap_or(1);
+        let due = recurrence.map(|recurrence| first_period_day(dates.start.unwrap_or(dates.today), day, recurrence));
+        return Some(found(whole.start(), whole.end(), due, recurrence));
+    }
+
+    if let Some(caps) = relative_regex().captures(text) {
+        let whole = caps.get(0).unwrap();
+        let amount = extraction::number_word_value(&caps[1]).unwrap_or(0) as i64;
+        let unit = caps[2].to_lowercase();
+        let direction = caps[3].to_lowercase();
+        let backwards = direction == "before" || direction.starts_with("prior");
+        let due = dates.event_day(&caps[4]).map(|base| amount_in_days(base, if backwards { -amount } else { amount }, &unit));
+        let mut deadline = found(whole.start(), whole.end(), due, None);
+        deadline.notice = whole.as_str().to_lowercase().contains("notice");
+        return Some(deadline);
+    }
+
+    if let Some((start, end, iso)) = extraction::find_dates(text).into_iter().next() {
+        let lower = text[..start].to_lowercase();
+        let start = DEADLINE_CONNECTORS.iter()
+            .find(|connector| lower.ends_with(*connector))
+            .map_or(start, |connector| start - connector.len());
+        let due = iso.as_deref().and_then(calendar::parse_iso_date);
+        return Some(found(start, end, due, None));
+    }
+
+    if let Some(whole) = within_regex().find(text) {
+        return Some(found(whole.start(), whole.end(), None, None));
+    }
+
+    let lower = text.to_lowercase();
+    RECURRENCE_WORDS.iter()
+        .find_map(|(word, recurrence)| lower.find(word).map(|start| (start, start + word.len(), *recurrence)))
+        .map(|(start, end, recurrence)| found(start, end, dates.start, Some(recurrence)))
+}
+
+// The action without its deadline, when the deadline is a separate phrase
+fn action_text(rest: &str, deadline: Option<&FoundDeadline>) -> String {
+    let action = match deadline {
+        Some(found) if DEADLINE_CONNECTORS.iter().any(|connector| rest[found.start..].to_lowercase().starts_with(connector)) => {
+            format!("{} {}", &rest[..found.start], &rest[found.end..])
+        }
+        _ => rest.to_string(),
+    };
+    let action = action.split_whitespace().collect::<Vec<_>>().join(" ").replace(" ,", ",");
+    let action = action.trim_matches(|c: char| c == ',' || c == ';' || c == ':' || c == '.' || c.is_whitespace());
+    match action.char_indices().nth(MAX_ACTION_CHARS) {
+        Some((end, _)) => format!("{}…", &action[..end]),
+        None => action.to_string(),
+    }
+}
+
+fn obligation_in(clause: &ClauseAnalysis, dates: &ContractDates) -> Option<Obligation> {
+    let text = clause.clause.as_str();
+    let (obligor, rest, action_prefix, permission) = if let Some(caps) = duty_regex().captures(text) {
+        let rest = caps.get(3).unwrap().as_str();
+        // Prohibitions ("shall not") have nothing to schedule
+        if rest.to_lowercase().starts_with("not ") {
+            return None;
+        }
+        let subject = caps[1].trim();
+        let subject = match subject.get(..4) {
+            Some(article) if article.eq_ignore_ascii_case("the ") => &subject[4..],
+            _ => subject,
+        };
+        (extraction::capitalize_words(subject), rest, None, caps[2].eq_ignore_ascii_case("may"))
+    } else if let Some(caps) = payable_regex().captures(text) {
+        let rest = caps.get(2).unwrap().as_str();
+        let obligor = payer_regex().captures(rest)
+            .map(|payer| extraction::capitalize_words(&payer[1]))
+            .unwrap_or_else(|| "Unspecified".to_string());
+        (obligor, rest, Some(format!("pay {}", caps[1].to_lowercase())), false)
+    } else {
+        return None;
+    };
+
+    let deadline = find_deadline(rest, dates);
+    // A permission only binds through the notice it requires
+    if permission && !deadline.as_ref().is_some_and(|found| found.notice) {
+        return None;
+    }
+    let mut action = action_text(rest, deadline.as_ref());
+    if let Some(prefix) = action_prefix {
+        action = if action.is_empty() { prefix } else { format!("{} {}", prefix, action) };
+    }
+
+    Some(Obligation {
+        obligor,
+        action,
+        deadline: deadline.map(|found| found.deadline),
+        section: clause.section.clone(),
+        page: clause.page,
+        clause: clause.clause.clone(),
+    })
+}
+
+// Obligations in the analysed clauses, with due dates worked out from the
+// contract's start and end dates. `today` is the UTC day of the analysis.
+pub fn extract_obligations(text: &str, clauses: &[ClauseAnalysis], today: i64) -> Vec<Obligation> {
+    let dates = ContractDates::of(text, today);
+    clauses.iter()
+        .filter_map(|clause| obligation_in(clause, &dates))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(iso: &str) -> i64 {
+        calendar::parse_iso_date(iso).unwrap()
+    }
+
+    fn deadline(due: &str, recurrence: Option<Recurrence>, until: Option<&str>) -> Deadline {
+        Deadline {
+            text: String::new(),
+            due_date: Some(due.to_string()),
+            recurrence,
+            until: until.map(str::to_string),
+        }
+    }
+
+    fn obligations(text: &str, today: &str) -> Vec<Obligation> {
+        let clauses: Vec<ClauseAnalysis> = crate::segment::segment_clauses(text).into_iter()
+            .map(|segment| ClauseAnalysis::new(segment.text.to_string(), segment.section, "Allowed".to_string(), 1.0, String::new()))
+            .collect();
+        extract_obligations(text, &clauses, day(today))
+    }
+
+    #[test]
+    fn finds_the_first_day_of_a_period() {
+        // 2025-01-01 was a Wednesday
+        for (anchor, nth, recurrence, expected) in [
+            ("2025-01-01", 5, Recurrence::Monthly, "2025-01-05"),
+            ("2025-01-06", 5, Recurrence::Monthly, "2025-02-05"),
+            ("2025-01-05", 5, Recurrence::Monthly, "2025-01-05"),
+            ("2025-02-10", 31, Recurrence::Monthly, "2025-02-28"),
+            ("2025-01-01", 1, Recurrence::Weekly, "2025-01-06"),
+            ("2025-01-01", 3, Recurrence::Weekly, "2025-01-01"),
+            ("2025-01-01", 5, Recurrence::Weekly, "2025-01-03"),
+            ("2025-01-01", 9, Recurrence::Weekly, "2025-01-05"),
+            ("2025-02-10", 1, Recurrence::Quarterly, "2025-04-01"),
+            ("2025-04-01", 1, Recurrence::Quarterly, "2025-04-01"),
+            ("2025-11-20", 15, Recurrence::Quarterly, "2026-01-15"),
+            ("2025-03-10", 1, Recurrence::Annually, "2026-01-01"),
+            ("2025-01-01", 15, Recurrence::Annually, "2025-01-15"),
+            ("2024-12-31", 31, Recurrence::Annually, "2025-01-31"),
+        ] {
+            assert_eq!(
+                calendar::iso_date(first_period_day(day(anchor), nth, recurrence)),
+                expected,
+                "day {} of the {} period from {}",
+                nth,
+                recurrence.name(),
+                anchor
+            );
+        }
+    }
+
+    #[test]
+    fn finds_the_next_occurrence() {
+        for (deadline, from, expected) in [
+            (deadline("2025-01-05", None, None), "2025-01-01", Some("2025-01-05")),
+            (deadline("2025-01-05", None, None), "2025-01-06", None),
+            (deadline("2025-01-31", Some(Recurrence::Monthly), None), "2025-02-01", Some("2025-02-28")),
+            (deadline("2025-01-31", Some(Recurrence::Monthly), None), "2025-03-01", Some("2025-03-31")),
+            (deadline("2025-01-31", Some(Recurrence::Monthly), None), "2025-03-31", Some("2025-03-31")),
+            (deadline("2025-01-06", Some(Recurrence::Weekly), None), "2025-01-07", Some("2025-01-13")),
+            (deadline("2025-01-06", Some(Recurrence::Weekly), None), "2025-01-13", Some("2025-01-13")),
+            (deadline("2025-01-01", Some(Recurrence::Quarterly), None), "2025-05-20", Some("2025-07-01")),
+            (deadline("2024-02-29", Some(Recurrence::Annually), None), "2025-01-01", Some("2025-02-28")),
+            (deadline("2024-02-29", Some(Recurrence::Annually), None), "2027-03-01", Some("2028-02-29")),
+            (deadline("2025-01-05", Some(Recurrence::Monthly), Some("2025-06-30")), "2025-06-06", None),
+            (deadline("2025-01-05", Some(Recurrence::Monthly), Some("2025-06-30")), "2025-06-05", Some("2025-06-05")),
+        ] {
+            assert_eq!(deadline.next_due(day(from)).map(calendar::iso_date).as_deref(), expected, "{:?} from {}", deadline, from);
+        }
+    }
+
+    #[test]
+    fn rent_is_due_by_the_fifth_of_each_month() {
+        let text = "This lease commences on 1 March 2025 and runs for twelve months.\n\
+2. The Tenant shall pay the rent by the 5th of each month.";
+        let found = obligations(text, "2025-02-10");
+        let rent = found.iter().find(|obligation| obligation.action.contains("rent")).expect("rent obligation");
+        assert_eq!(rent.obligor, "Tenant");
+        assert_eq!(rent.action, "pay the rent");
+        assert_eq!(rent.section.as_deref(), Some("2"));
+        let deadline = rent.deadline.as_ref().unwrap();
+        assert_eq!(deadline.text, "by the 5th of each month");
+        assert_eq!(deadline.recurrence, Some(Recurrence::Monthly));
+        assert_eq!(deadline.due_date.as_deref(), Some("2025-03-05"));
+        assert_eq!(deadline.until.as_deref(), Some("2026-02-28"));
+    }
+
+    #[test]
+    fn notice_is_due_sixty_days_before_renewal() {
+        let text = "This lease commences on 1 March 2025 and runs for twelve months.\n\
+7. Either party may terminate this lease by giving 60 days notice before renewal.";
+        let found = obligations(text, "2025-02-10");
+        let notice = found.iter().find(|obligation| obligation.section.as_deref() == Some("7")).expect("notice obligation");
+        assert_eq!(notice.obligor, "Either Party");
+        let deadline = notice.deadline.as_ref().unwrap();
+        assert_eq!(deadline.text, "60 days notice before renewal");
+        assert_eq!(deadline.recurrence, None);
+        // Renewal follows the last day of the term, 2026-02-28
+        assert_eq!(deadline.due_date.as_deref(), Some("2025-12-30"));
+    }
+
+    #[test]
+    fn undated_contracts_count_recurring_duties_from_the_analysis_day() {
+        let found = obligations("The Tenant shall pay the rent on or before the 1st day of every quarter.", "2025-05-20");
+        let deadline = found[0].deadline.as_ref().unwrap();
+        assert_eq!(deadline.recurrence, Some(Recurrence::Quarterly));
+        assert_eq!(deadline.due_date.as_deref(), Some("2025-07-01"));
+        assert_eq!(deadline.until, None);
+    }
+}
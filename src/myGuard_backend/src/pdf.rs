@@ -0,0 +1,1637 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::ingest::TextBuilder;
+use crate::segment::starts_with_section_marker;
+
+// Largest stream inflated from the file, against compression bombs
+const MAX_STREAM_BYTES: usize = 32 * 1024 * 1024;
+
+// Deepest nesting followed in objects, page trees and form XObjects
+const MAX_DEPTH: usize = 32;
+
+// Lines at the top and bottom of a page that may be running headers or footers
+const EDGE_LINES: usize = 2;
+
+// Edge lines repeated on at least this share of the pages are headers or footers
+const REPEATED_LINE_SHARE: f64 = 0.5;
+
+// A TJ adjustment moving the text on by more than this many thousandths of
+// the font size separates two words
+const WORD_GAP_THOUSANDTHS: f64 = 180.0;
+
+// Lines this much larger than the body text are headings
+const HEADING_SIZE_RATIO: f64 = 1.15;
+
+// Longest bold line still taken for a heading, in characters
+const MAX_HEADING_CHARS: usize = 100;
+
+// A vertical gap this many times the usual line spacing ends a paragraph
+const PARAGRAPH_GAP_RATIO: f64 = 1.3;
+
+type Dictionary = BTreeMap<String, Object>;
+
+// [a b c d e f] as in the PDF specification
+type Matrix = [f64; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+static NULL: Object = Object::Null;
+
+#[derive(Clone, Debug)]
+enum Object {
+    // Also stands for booleans, which text extraction never needs
+    Null,
+    Number(f64),
+    Name(String),
+    String(Vec<u8>),
+    Array(Vec<Object>),
+    Dictionary(Dictionary),
+    Stream(Dictionary, Vec<u8>),
+    Reference(u32),
+    // Keywords, including content stream operators
+    Operator(String),
+}
+
+impl Object {
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            Object::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    fn as_name(&self) -> Option<&str> {
+        match self {
+            Object::Name(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    fn as_dictionary(&self) -> Option<&Dictionary> {
+        match self {
+            Object::Dictionary(dictionary) | Object::Stream(dictionary, _) => Some(dictionary),
+            _ => None,
+        }
+    }
+}
+
+fn is_whitespace(byte: u8) -> bool {
+    matches!(byte, b' ' | b'\t' | b'\r' | b'\n' | b'\x0c' | b'\0')
+}
+
+fn is_delimiter(byte: u8) -> bool {
+    matches!(byte, b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%')
+}
+
+fn is_regular(byte: u8) -> bool {
+    !is_whitespace(byte) && !is_delimiter(byte)
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack.get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|index| index + from)
+}
+
+fn parse_number(token: &[u8]) -> Option<f64> {
+    if !token.first().is_some_and(|b| b.is_ascii_digit() || matches!(b, b'+' | b'-' | b'.')) {
+        return None;
+    }
+    std::str::from_utf8(token).ok()?.parse().ok()
+}
+
+// Names may escape bytes as #xx
+fn decode_name(token: &[u8]) -> String {
+    let mut bytes = Vec::with_capacity(token.len());
+    let mut i = 0;
+    while i < token.len() {
+        if token[i] == b'#' {
+            if let Some(value) = token.get(i + 1..i + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                bytes.push(value);
+                i += 3;
+                continue;
+            }
+        }
+        bytes.push(token[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+// Tokenizer for both the file body and content streams
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(bytes: &'a [u8], pos: usize) -> Self {
+        Parser { bytes, pos }
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(&byte) = self.bytes.get(self.pos) {
+            if is_whitespace(byte) {
+                self.pos += 1;
+            } else if byte == b'%' {
+                while self.bytes.get(self.pos).is_some_and(|&b| b != b'\n' && b != b'\r') {
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn regular_token(&mut self) -> &'a [u8] {
+        let start = self.pos;
+        while self.bytes.get(self.pos).is_some_and(|&b| is_regular(b)) {
+            self.pos += 1;
+        }
+        &self.bytes[start..self.pos]
+    }
+
+    // The next object, or None at the end of the input and at a closing
+    // delimiter, which is left in place
+    fn object(&mut self, depth: usize) -> Option<Object> {
+        self.skip_whitespace();
+        let byte = *self.bytes.get(self.pos)?;
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        match byte {
+            b'/' => {
+                self.pos += 1;
+                Some(Object::Name(decode_name(self.regular_token())))
+            }
+            b'(' => Some(Object::String(self.literal_string())),
+            b'<' if self.bytes.get(self.pos + 1) == Some(&b'<') => {
+                self.pos += 2;
+                Some(Object::Dictionary(self.dictionary(depth)))
+            }
+            b'<' => Some(Object::String(self.hex_string())),
+            b'[' => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    match self.bytes.get(self.pos) {
+                        None => break,
+                        Some(b']') => {
+                            self.pos += 1;
+                            break;
+                        }
+                        _ => match self.object(depth + 1) {
+                            Some(item) => items.push(item),
+                            None => self.pos += 1,
+                        },
+                    }
+                }
+                Some(Object::Array(items))
+            }
+            b']' | b')' | b'>' | b'{' | b'}' => None,
+            _ => {
+                let token = self.regular_token();
+                if let Some(number) = parse_number(token) {
+                    if token.iter().all(u8::is_ascii_digit) {
+                        if let Some(reference) = self.reference_tail(number) {
+                            return Some(reference);
+                        }
+                    }
+                    return Some(Object::Number(number));
+                }
+                Some(match token {
+                    b"true" | b"false" | b"null" => Object::Null,
+                    _ => Object::Operator(String::from_utf8_lossy(token).into_owned()),
+                })
+            }
+        }
+    }
+
+    // "12 0 R" after the object number has been read
+    fn reference_tail(&mut self, number: f64) -> Option<Object> {
+        let start = self.pos;
+        self.skip_whitespace();
+        let generation = self.regular_token();
+        self.skip_whitespace();
+        if !generation.is_empty() && generation.iter().all(u8::is_ascii_digit) && self.regular_token() == b"R" {
+            return Some(Object::Reference(number as u32));
+        }
+        self.pos = start;
+        None
+    }
+
+    fn dictionary(&mut self, depth: usize) -> Dictionary {
+        let mut dictionary = Dictionary::new();
+        loop {
+            self.skip_whitespace();
+            if self.at_end() {
+                break;
+            }
+            if self.bytes[self.pos..].starts_with(b">>") {
+                self.pos += 2;
+                break;
+            }
+            match self.object(depth + 1) {
+                Some(Object::Name(key)) => {
+                    let value = self.object(depth + 1).unwrap_or(Object::Null);
+                    dictionary.insert(key, value);
+                }
+                Some(_) => {}
+                None => self.pos += 1,
+            }
+        }
+        dictionary
+    }
+
+    fn literal_string(&mut self) -> Vec<u8> {
+        self.pos += 1;
+        let mut out = Vec::new();
+        let mut nesting = 0;
+        while let Some(&byte) = self.bytes.get(self.pos) {
+            self.pos += 1;
+            match byte {
+                b'(' => {
+                    nesting += 1;
+                    out.push(byte);
+                }
+                b')' => {
+                    if nesting == 0 {
+                        break;
+                    }
+                    nesting -= 1;
+                    out.push(byte);
+                }
+                b'\\' => {
+                    let Some(&escaped) = self.bytes.get(self.pos) else { break };
+                    self.pos += 1;
+                    match escaped {
+                        b'n' => out.push(b'\n'),
+                        b'r' => out.push(b'\r'),
+                        b't' => out.push(b'\t'),
+                        b'b' => out.push(0x08),
+                        b'f' => out.push(0x0c),
+                        b'0'..=b'7' => {
+                            let mut value = (escaped - b'0') as u32;
+                            for _ in 0..2 {
+                                match self.bytes.get(self.pos) {
+                                    Some(&digit @ b'0'..=b'7') => {
+                                        value = value * 8 + (digit - b'0') as u32;
+                                        self.pos += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            out.push(value as u8);
+                        }
+                        // A backslash at the end of a line continues the string
+                        b'\r' => {
+                            if self.bytes.get(self.pos) == Some(&b'\n') {
+                                self.pos += 1;
+                            }
+                        }
+                        b'\n' => {}
+                        other => out.push(other),
+                    }
+                }
+                _ => out.push(byte),
+            }
+        }
+        out
+    }
+
+    fn hex_string(&mut self) -> Vec<u8> {
+        self.pos += 1;
+        let mut digits = Vec::new();
+        while let Some(&byte) = self.bytes.get(self.pos) {
+            self.pos += 1;
+            if byte == b'>' {
+                break;
+            }
+            if let Some(digit) = (byte as char).to_digit(16) {
+                digits.push(digit as u8);
+            }
+        }
+        digits.chunks(2)
+            .map(|pair| pair[0] << 4 | pair.get(1).copied().unwrap_or(0))
+            .collect()
+    }
+
+    // Raw data of the stream following a stream dictionary, if there is one.
+    // A wrong /Length is recovered from by looking for "endstream".
+    fn stream_data(&mut self, dictionary: &Dictionary) -> Option<Vec<u8>> {
+        self.skip_whitespace();
+        if !self.bytes.get(self.pos..)?.starts_with(b"stream") {
+            return None;
+        }
+        let mut start = self.pos + 6;
+        if self.bytes.get(start) == Some(&b'\r') {
+            start += 1;
+        }
+        if self.bytes.get(start) == Some(&b'\n') {
+            start += 1;
+        }
+
+        let declared_end = dictionary.get("Length")
+            .and_then(Object::as_number)
+            .and_then(|length| start.checked_add(length as usize))
+            .filter(|&end| {
+                self.bytes.get(end..).is_some_and(|rest| {
+                    let skipped = rest.iter().take_while(|&&b| is_whitespace(b)).count();
+                    rest[skipped..].starts_with(b"endstream")
+                })
+            });
+        let end = declared_end.unwrap_or_else(|| {
+            let mut end = find(self.bytes, b"endstream", start).unwrap_or(self.bytes.len());
+            if end > start && self.bytes[end - 1] == b'\n' {
+                end -= 1;
+            }
+            if end > start && self.bytes[end - 1] == b'\r' {
+                end -= 1;
+            }
+            end
+        });
+        let data = self.bytes.get(start..end).unwrap_or_default().to_vec();
+        self.pos = find(self.bytes, b"endstream", end).map_or(self.bytes.len(), |found| found + 9);
+        Some(data)
+    }
+
+    // Inline image data runs from after "ID" to the next "EI" on its own
+    fn skip_inline_image(&mut self) {
+        let mut search = self.pos;
+        while let Some(found) = find(self.bytes, b"EI", search) {
+            let before = found == 0 || is_whitespace(self.bytes[found - 1]);
+            let after = self.bytes.get(found + 2).is_none_or(|&b| is_whitespace(b));
+            if before && after {
+                self.pos = found + 2;
+                return;
+            }
+            search = found + 2;
+        }
+        self.pos = self.bytes.len();
+    }
+}
+
+// The object number of an "N G obj" header ending at `at`
+fn object_number_before(bytes: &[u8], at: usize) -> Option<u32> {
+    let mut i = at;
+    let skip = |i: &mut usize, class: fn(u8) -> bool| {
+        let end = *i;
+        while *i > 0 && class(bytes[*i - 1]) {
+            *i -= 1;
+        }
+        *i < end
+    };
+    if !(skip(&mut i, is_whitespace) && skip(&mut i, |b| b.is_ascii_digit()) && skip(&mut i, is_whitespace)) {
+        return None;
+    }
+    let number_end = i;
+    if !skip(&mut i, |b| b.is_ascii_digit()) || (i > 0 && is_regular(bytes[i - 1])) {
+        return None;
+    }
+    std::str::from_utf8(&bytes[i..number_end]).ok()?.parse().ok()
+}
+
+fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    match miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(data, MAX_STREAM_BYTES) {
+        Ok(output) => Ok(output),
+        // Many writers leave a stream truncated or without its checksum
+        Err(error) if !error.output.is_empty() => Ok(error.output),
+        Err(_) => miniz_oxide::inflate::decompress_to_vec_with_limit(data, MAX_STREAM_BYTES)
+            .map_err(|_| "could not decompress a stream".to_string()),
+    }
+}
+
+fn ascii_hex_decode(data: &[u8]) -> Vec<u8> {
+    let mut hex = data.to_vec();
+    hex.insert(0, b'<');
+    if !hex.contains(&b'>') {
+        hex.push(b'>');
+    }
+    Parser::new(&hex, 0).hex_string()
+}
+
+fn ascii85_decode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut group = Vec::with_capacity(5);
+    let flush = |group: &mut Vec<u32>, out: &mut Vec<u8>| {
+        let length = group.len();
+        if length < 2 {
+            group.clear();
+            return;
+        }
+        group.resize(5, 84);
+        let value = group.iter().fold(0u64, |acc, &digit| acc * 85 + digit as u64) as u32;
+        out.extend_from_slice(&value.to_be_bytes()[..length - 1]);
+        group.clear();
+    };
+    for &byte in data {
+        match byte {
+            b'~' => break,
+            b'z' if group.is_empty() => out.extend_from_slice(&[0; 4]),
+            b'!'..=b'u' => {
+                group.push((byte - b'!') as u32);
+                if group.len() == 5 {
+                    let value = group.iter().fold(0u64, |acc, &digit| acc * 85 + digit as u64) as u32;
+                    out.extend_from_slice(&value.to_be_bytes());
+                    group.clear();
+                }
+            }
+            _ => {}
+        }
+    }
+    flush(&mut group, &mut out);
+    out
+}
+
+// The objects of a PDF file, found by scanning the file body. The
+// cross-reference table is not needed for that and is often damaged.
+struct PdfFile {
+    objects: BTreeMap<u32, Object>,
+    trailer: Dictionary,
+}
+
+impl PdfFile {
+    fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let mut objects = BTreeMap::new();
+        let mut trailer = Dictionary::new();
+
+        let mut pos = 0;
+        while let Some(found) = find(bytes, b"obj", pos) {
+            pos = found + 3;
+            let Some(number) = object_number_before(bytes, found) else { continue };
+            if bytes.get(pos).is_some_and(|&b| is_regular(b)) {
+                continue;
+            }
+            let mut parser = Parser::new(bytes, pos);
+            let Some(object) = parser.object(0) else { continue };
+            let object = match object {
+                Object::Dictionary(dictionary) => match parser.stream_data(&dictionary) {
+                    Some(data) => Object::Stream(dictionary, data),
+                    None => Object::Dictionary(dictionary),
+                },
+                other => other,
+            };
+            // A stream running into the end of the file means the file was cut
+            // off, and whatever text follows the cut would be silently missing
+            if matches!(object, Object::Stream(..)) && parser.at_end() {
+                return Err("The PDF file is incomplete; upload the whole file again".to_string());
+            }
+            pos = parser.pos;
+            // Cross-reference streams carry the trailer entries
+            if let Object::Stream(dictionary, _) = &object {
+                if dictionary.get("Type").and_then(Object::as_name) == Some("XRef") {
+                    trailer.extend(dictionary.clone());
+                }
+            }
+            // Later definitions come from incremental updates and win
+            objects.insert(number, object);
+        }
+
+        let mut pos = 0;
+        while let Some(found) = find(bytes, b"trailer", pos) {
+            pos = found + 7;
+            if let Some(Object::Dictionary(dictionary)) = Parser::new(bytes, pos).object(0) {
+                trailer.extend(dictionary);
+            }
+        }
+        if trailer.contains_key("Encrypt") {
+            return Err("The PDF is encrypted; remove the password and upload it again".to_string());
+        }
+
+        let mut file = PdfFile { objects, trailer };
+        file.expand_object_streams();
+        Ok(file)
+    }
+
+    // Objects stored compressed inside object streams (PDF 1.5 and later)
+    fn expand_object_streams(&mut self) {
+        let streams: Vec<(Dictionary, Vec<u8>)> = self.objects.values()
+            .filter_map(|object| match object {
+                Object::Stream(dictionary, data) if dictionary.get("Type").and_then(Object::as_name) == Some("ObjStm") => {
+                    Some((dictionary.clone(), data.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+
+        for (dictionary, data) in streams {
+            let Ok(decoded) = self.decode_stream(&dictionary, &data) else { continue };
+            let count = self.number(&dictionary, "N").unwrap_or(0.0) as usize;
+            let first = self.number(&dictionary, "First").unwrap_or(0.0) as usize;
+
+            let mut header = Parser::new(&decoded, 0);
+            let mut entries = Vec::new();
+            for _ in 0..count {
+                match (header.object(0), header.object(0)) {
+                    (Some(Object::Number(number)), Some(Object::Number(offset))) => entries.push((number as u32, offset as usize)),
+                    _ => break,
+                }
+            }
+            for (number, offset) in entries {
+                let Some(start) = first.checked_add(offset).filter(|&start| start < decoded.len()) else { continue };
+                if let Some(object) = Parser::new(&decoded, start).object(0) {
+                    self.objects.entry(number).or_insert(object);
+                }
+            }
+        }
+    }
+
+    fn resolve<'b>(&'b self, object: &'b Object) -> &'b Object {
+        let mut current = object;
+        for _ in 0..MAX_DEPTH {
+            match current {
+                Object::Reference(number) => current = self.objects.get(number).unwrap_or(&NULL),
+                _ => return current,
+            }
+        }
+        &NULL
+    }
+
+    fn get<'b>(&'b self, dictionary: &'b Dictionary, key: &str) -> Option<&'b Object> {
+        dictionary.get(key)
+            .map(|object| self.resolve(object))
+            .filter(|object| !matches!(object, Object::Null))
+    }
+
+    fn number(&self, dictionary: &Dictionary, key: &str) -> Option<f64> {
+        self.get(dictionary, key).and_then(Object::as_number)
+    }
+
+    fn decode_stream(&self, dictionary: &Dictionary, raw: &[u8]) -> Result<Vec<u8>, String> {
+        let filters: Vec<&str> = match self.get(dictionary, "Filter") {
+            Some(Object::Name(name)) => vec![name.as_str()],
+            Some(Object::Array(items)) => items.iter().filter_map(|item| self.resolve(item).as_name()).collect(),
+            _ => Vec::new(),
+        };
+        let mut data = raw.to_vec();
+        for filter in filters {
+            data = match filter {
+                "FlateDecode" | "Fl" => inflate(&data)?,
+                "ASCIIHexDecode" | "AHx" => ascii_hex_decode(&data),
+                "ASCII85Decode" | "A85" => ascii85_decode(&data),
+                other => return Err(format!("unsupported stream filter {}", other)),
+            };
+        }
+        Ok(data)
+    }
+
+    // Decoded data of a stream object; unreadable streams are treated as empty
+    fn stream_content(&self, object: &Object) -> Vec<u8> {
+        match self.resolve(object) {
+            Object::Stream(dictionary, raw) => self.decode_stream(dictionary, raw).unwrap_or_default(),
+            _ => Vec::new(),
+        }
+    }
+
+    // The document catalog named by the trailer, or failing that the first one
+    // in the file
+    fn catalog(&self) -> Option<&Dictionary> {
+        self.trailer.get("Root")
+            .map(|root| self.resolve(root))
+            .and_then(Object::as_dictionary)
+            .or_else(|| {
+                self.objects.values()
+                    .filter_map(Object::as_dictionary)
+                    .find(|dictionary| dictionary.get("Type").and_then(Object::as_name) == Some("Catalog"))
+            })
+    }
+
+    // Pages in document order, each with the resources it inherits
+    fn pages(&self) -> Vec<(Dictionary, Dictionary)> {
+        let mut pages = Vec::new();
+        if let Some(tree) = self.catalog().and_then(|root| root.get("Pages")) {
+            let mut visited = BTreeSet::new();
+            self.walk_page_tree(tree, &Dictionary::new(), &mut visited, &mut pages, 0);
+        }
+
+        // Without a usable page tree, take the page objects in object order
+        if pages.is_empty() {
+            for object in self.objects.values() {
+                if let Some(page) = object.as_dictionary() {
+                    if page.get("Type").and_then(Object::as_name) == Some("Page") {
+                        let resources = self.get(page, "Resources").and_then(Object::as_dictionary).cloned().unwrap_or_default();
+                        pages.push((page.clone(), resources));
+                    }
+                }
+            }
+        }
+        pages
+    }
+
+    fn walk_page_tree(
+        &self,
+        node: &Object,
+        inherited: &Dictionary,
+        visited: &mut BTreeSet<u32>,
+        pages: &mut Vec<(Dictionary, Dictionary)>,
+        depth: usize,
+    ) {
+        if let Object::Reference(number) = node {
+            if !visited.insert(*number) {
+                return;
+            }
+        }
+        let Some(dictionary) = self.resolve(node).as_dictionary() else { return };
+        if depth > MAX_DEPTH {
+            return;
+        }
+        let resources = self.get(dictionary, "Resources").and_then(Object::as_dictionary).unwrap_or(inherited);
+
+        match self.get(dictionary, "Kids") {
+            Some(Object::Array(kids)) => {
+                for kid in kids {
+                    self.walk_page_tree(kid, resources, visited, pages, depth + 1);
+                }
+            }
+            _ => pages.push((dictionary.clone(), resources.clone())),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum BaseEncoding {
+    WinAnsi,
+    MacRoman,
+}
+
+const WIN_ANSI_HIGH: [char; 32] = [
+    '€', '\u{fffd}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{fffd}', 'Ž', '\u{fffd}',
+    '\u{fffd}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{fffd}', 'ž', 'Ÿ',
+];
+
+const MAC_ROMAN_HIGH: &str = "ÄÅÇÉÑÖÜáàâäãåçéèêëíìîïñóòôöõúùûü†°¢£§•¶ß®©™´¨≠ÆØ∞±≤≥¥µ∂∑∏π∫ªºΩæø¿¡¬√ƒ≈∆«»…\u{a0}ÀÃÕŒœ–—“”‘’÷◊ÿŸ⁄€‹›ﬁﬂ‡·‚„‰ÂÊÁËÈÍÎÏÌÓÔ\u{f8ff}ÒÚÛÙıˆ˜¯˘˙˚¸˝˛ˇ";
+
+impl BaseEncoding {
+    fn decode(self, code: u8) -> Option<char> {
+        match (self, code) {
+            (_, 0..=31) => None,
+            (_, 32..=126) => Some(code as char),
+            (BaseEncoding::WinAnsi, 0x80..=0x9f) => Some(WIN_ANSI_HIGH[(code - 0x80) as usize]),
+            (BaseEncoding::WinAnsi, _) => Some(code as char),
+            (BaseEncoding::MacRoman, 0x80..=0xff) => MAC_ROMAN_HIGH.chars().nth((code - 0x80) as usize),
+            (BaseEncoding::MacRoman, _) => None,
+        }
+    }
+}
+
+// Unicode for the glyph names used in /Differences arrays
+fn glyph_char(name: &str) -> Option<char> {
+    let mut chars = name.chars();
+    if let (Some(only), None) = (chars.next(), chars.next()) {
+        return Some(only);
+    }
+    if let Some(hex) = name.strip_prefix("uni").or_else(|| name.strip_prefix('u')) {
+        if let Some(c) = hex.get(..4).and_then(|hex| u32::from_str_radix(hex, 16).ok()).and_then(char::from_u32) {
+            return Some(c);
+        }
+    }
+    let c = match name {
+        "space" | "nbspace" => ' ',
+        "exclam" => '!',
+        "quotedbl" => '"',
+        "numbersign" => '#',
+        "dollar" => '$',
+        "percent" => '%',
+        "ampersand" => '&',
+        "quotesingle" => '\'',
+        "parenleft" => '(',
+        "parenright" => ')',
+        "asterisk" => '*',
+        "plus" => '+',
+        "comma" => ',',
+        "hyphen" | "minus" => '-',
+        "period" => '.',
+        "slash" => '/',
+        "zero" => '0',
+        "one" => '1',
+        "two" => '2',
+        "three" => '3',
+        "four" => '4',
+        "five" => '5',
+        "six" => '6',
+        "seven" => '7',
+        "eight" => '8',
+        "nine" => '9',
+        "colon" => ':',
+        "semicolon" => ';',
+        "less" => '<',
+        "equal" => '=',
+        "greater" => '>',
+        "question" => '?',
+        "at" => '@',
+        "bracketleft" => '[',
+        "backslash" => '\\',
+        "bracketright" => ']',
+        "underscore" => '_',
+        "braceleft" => '{',
+        "bar" => '|',
+        "braceright" => '}',
+        "quoteleft" => '‘',
+        "quoteright" => '’',
+        "quotedblleft" => '“',
+        "quotedblright" => '”',
+        "quotesinglbase" => '‚',
+        "quotedblbase" => '„',
+        "endash" => '–',
+        "emdash" => '—',
+        "bullet" => '•',
+        "ellipsis" => '…',
+        "section" => '§',
+        "paragraph" => '¶',
+        "copyright" => '©',
+        "registered" => '®',
+        "trademark" => '™',
+        "degree" => '°',
+        "Euro" => '€',
+        "sterling" => '£',
+        "agrave" => 'à',
+        "acircumflex" => 'â',
+        "ccedilla" => 'ç',
+        "eacute" => 'é',
+        "egrave" => 'è',
+        "ecircumflex" => 'ê',
+        "edieresis" => 'ë',
+        "icircumflex" => 'î',
+        "idieresis" => 'ï',
+        "ocircumflex" => 'ô',
+        "ugrave" => 'ù',
+        "ucircumflex" => 'û',
+        "Eacute" => 'É',
+        "Egrave" => 'È',
+        "Agrave" => 'À',
+        "Ccedilla" => 'Ç',
+        _ => return None,
+    };
+    Some(c)
+}
+
+fn utf16_text(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+    String::from_utf16_lossy(&units).replace('\0', "")
+}
+
+fn code_of(bytes: &[u8]) -> u32 {
+    bytes.iter().take(4).fold(0, |code, &byte| code << 8 | byte as u32)
+}
+
+// Character codes to Unicode from a ToUnicode CMap
+fn parse_to_unicode(data: &[u8]) -> BTreeMap<u32, String> {
+    let mut map = BTreeMap::new();
+    let mut parser = Parser::new(data, 0);
+    let mut operands = Vec::new();
+    while !parser.at_end() {
+        match parser.object(0) {
+            Some(Object::Operator(operator)) => {
+                match operator.as_str() {
+                    "endbfchar" => {
+                        for pair in operands.chunks_exact(2) {
+                            if let (Object::String(source), Object::String(target)) = (&pair[0], &pair[1]) {
+                                map.insert(code_of(source), utf16_text(target));
+                            }
+                        }
+                    }
+                    "endbfrange" => {
+                        for triple in operands.chunks_exact(3) {
+                            let (Object::String(low), Object::String(high)) = (&triple[0], &triple[1]) else { continue };
+                            let (low, high) = (code_of(low), code_of(high));
+                            if high < low || high - low > 0xffff {
+                                continue;
+                            }
+                            match &triple[2] {
+                                Object::String(target) => {
+                                    let mut units: Vec<u16> = target.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+                                    for code in low..=high {
+                                        map.insert(code, String::from_utf16_lossy(&units));
+                                        if let Some(last) = units.last_mut() {
+                                            *last = last.wrapping_add(1);
+                                        }
+                                    }
+                                }
+                                Object::Array(targets) => {
+                                    for (code, target) in (low..=high).zip(targets) {
+                                        if let Object::String(target) = target {
+                                            map.insert(code, utf16_text(target));
+                                        }
+                                    }
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                operands.clear();
+            }
+            Some(other) => operands.push(other),
+            None => parser.pos += 1,
+        }
+    }
+    map
+}
+
+// What is needed of a font to turn shown strings into positioned text
+struct Font {
+    // Composite (Type0) fonts use two-byte character codes
+    two_byte: bool,
+    bold: bool,
+    encoding: BaseEncoding,
+    differences: BTreeMap<u32, char>,
+    to_unicode: BTreeMap<u32, String>,
+    // Glyph widths in thousandths of the font size, keyed by the first code
+    // of a range: start => (end, width)
+    widths: BTreeMap<u32, (u32, f64)>,
+    default_width: f64,
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Font {
+            two_byte: false,
+            bold: false,
+            encoding: BaseEncoding::WinAnsi,
+            differences: BTreeMap::new(),
+            to_unicode: BTreeMap::new(),
+            widths: BTreeMap::new(),
+            default_width: 500.0,
+        }
+    }
+}
+
+impl Font {
+    fn load(file: &PdfFile, dictionary: &Dictionary) -> Font {
+        let mut font = Font::default();
+        let base_font = file.get(dictionary, "BaseFont").and_then(Object::as_name).unwrap_or("");
+        font.bold = ["Bold", "Black", "Heavy", "Semibold"].iter().any(|weight| base_font.contains(weight));
+        if let Some(to_unicode) = file.get(dictionary, "ToUnicode") {
+            font.to_unicode = parse_to_unicode(&file.stream_content(to_unicode));
+        }
+
+        if file.get(dictionary, "Subtype").and_then(Object::as_name) == Some("Type0") {
+            font.two_byte = true;
+            font.default_width = 1000.0;
+            let descendant = match file.get(dictionary, "DescendantFonts") {
+                Some(Object::Array(fonts)) => fonts.first().map(|font| file.resolve(font)).and_then(Object::as_dictionary),
+                _ => None,
+            };
+            if let Some(descendant) = descendant {
+                font.load_descriptor(file, descendant);
+                if let Some(width) = file.number(descendant, "DW") {
+                    font.default_width = width;
+                }
+                if let Some(Object::Array(widths)) = file.get(descendant, "W") {
+                    font.load_cid_widths(file, widths);
+                }
+            }
+            return font;
+        }
+
+        font.load_descriptor(file, dictionary);
+        match file.get(dictionary, "Encoding") {
+            Some(Object::Name(name)) => font.encoding = base_encoding(name),
+            Some(Object::Dictionary(encoding)) => {
+                if let Some(name) = file.get(encoding, "BaseEncoding").and_then(Object::as_name) {
+                    font.encoding = base_encoding(name);
+                }
+                if let Some(Object::Array(differences)) = file.get(encoding, "Differences") {
+                    let mut code = 0;
+                    for item in differences {
+                        match file.resolve(item) {
+                            Object::Number(number) => code = (*number as u32).min(255),
+                            Object::Name(name) => {
+                                if let Some(c) = glyph_char(name) {
+                                    font.differences.insert(code, c);
+                                }
+                                code = code.saturating_add(1);
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        let first_char = (file.number(dictionary, "FirstChar").unwrap_or(0.0) as u32).min(255);
+        if let Some(Object::Array(widths)) = file.get(dictionary, "Widths") {
+            for (code, width) in (first_char..).zip(widths) {
+                if let Some(width) = file.resolve(width).as_number() {
+                    font.widths.insert(code, (code, width));
+                }
+            }
+        }
+        font
+    }
+
+    fn load_descriptor(&mut self, file: &PdfFile, dictionary: &Dictionary) {
+        let Some(descriptor) = file.get(dictionary, "FontDescriptor").and_then(Object::as_dictionary) else { return };
+        if file.number(descriptor, "FontWeight").is_some_and(|weight| weight >= 600.0) {
+            self.bold = true;
+        }
+        if let Some(width) = file.number(descriptor, "MissingWidth").filter(|&width| width > 0.0) {
+            self.default_width = width;
+        }
+    }
+
+    // W arrays mix "first [w1 w2 ...]" and "first last w" entries
+    fn load_cid_widths(&mut self, file: &PdfFile, widths: &[Object]) {
+        let items: Vec<&Object> = widths.iter().map(|item| file.resolve(item)).collect();
+        let mut i = 0;
+        while i + 1 < items.len() {
+            let Some(first) = items[i].as_number() else { break };
+            let first = (first as u32).min(0xffff);
+            match items[i + 1] {
+                Object::Array(list) => {
+                    for (code, width) in (first..).zip(list) {
+                        if let Some(width) = file.resolve(width).as_number() {
+                            self.widths.insert(code, (code, width));
+                        }
+                    }
+                    i += 2;
+                }
+                Object::Number(last) => {
+                    if let Some(width) = items.get(i + 2).and_then(|width| width.as_number()) {
+                        self.widths.insert(first, (*last as u32, width));
+                    }
+                    i += 3;
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn codes(&self, bytes: &[u8]) -> Vec<u32> {
+        if self.two_byte {
+            bytes.chunks(2).map(code_of).collect()
+        } else {
+            bytes.iter().map(|&byte| byte as u32).collect()
+        }
+    }
+
+    fn text(&self, code: u32) -> String {
+        if let Some(text) = self.to_unicode.get(&code) {
+            return text.clone();
+        }
+        if self.two_byte {
+            // Glyph ids without a ToUnicode map carry no text
+            return String::new();
+        }
+        self.differences.get(&code).copied()
+            .or_else(|| self.encoding.decode(code as u8))
+            .map(String::from)
+            .unwrap_or_default()
+    }
+
+    fn width(&self, code: u32) -> f64 {
+        match self.widths.range(..=code).next_back() {
+            Some((_, &(end, width))) if end >= code => width,
+            // Fonts without widths are mostly the standard fonts, whose space is narrow
+            _ if self.widths.is_empty() && code == 32 => 278.0,
+            _ => self.default_width,
+        }
+    }
+}
+
+fn base_encoding(name: &str) -> BaseEncoding {
+    if name == "MacRomanEncoding" {
+        BaseEncoding::MacRoman
+    } else {
+        BaseEncoding::WinAnsi
+    }
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    [
+        a[0] * b[0] + a[1] * b[2],
+        a[0] * b[1] + a[1] * b[3],
+        a[2] * b[0] + a[3] * b[2],
+        a[2] * b[1] + a[3] * b[3],
+        a[4] * b[0] + a[5] * b[2] + b[4],
+        a[4] * b[1] + a[5] * b[3] + b[5],
+    ]
+}
+
+fn matrix_operand(operands: &[Object]) -> Option<Matrix> {
+    let numbers: Vec<f64> = operands.iter().filter_map(Object::as_number).collect();
+    let start = numbers.len().checked_sub(6)?;
+    numbers[start..].try_into().ok()
+}
+
+#[derive(Clone)]
+struct GraphicsState {
+    ctm: Matrix,
+    font: Option<String>,
+    font_size: f64,
+    char_spacing: f64,
+    word_spacing: f64,
+    // Horizontal scaling as a fraction
+    scale: f64,
+    leading: f64,
+    rise: f64,
+}
+
+// Text shown by one operator, in page space
+struct TextRun {
+    x: f64,
+    y: f64,
+    end_x: f64,
+    size: f64,
+    bold: bool,
+    text: String,
+}
+
+// Collects the text runs of a page by executing its content stream
+struct ContentInterpreter<'f> {
+    file: &'f PdfFile,
+    runs: Vec<TextRun>,
+}
+
+impl ContentInterpreter<'_> {
+    fn run(&mut self, content: &[u8], resources: &Dictionary, ctm: Matrix, depth: usize) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+        let fonts_dictionary = self.file.get(resources, "Font").and_then(Object::as_dictionary);
+        let mut fonts: BTreeMap<String, Font> = BTreeMap::new();
+        let fallback_font = Font::default();
+
+        let mut state = GraphicsState {
+            ctm,
+            font: None,
+            font_size: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            scale: 1.0,
+            leading: 0.0,
+            rise: 0.0,
+        };
+        let mut saved: Vec<GraphicsState> = Vec::new();
+        let mut text_matrix = IDENTITY;
+        let mut line_matrix = IDENTITY;
+        let mut operands: Vec<Object> = Vec::new();
+        let mut parser = Parser::new(content, 0);
+
+        while !parser.at_end() {
+            let object = match parser.object(0) {
+                Some(Object::Operator(operator)) => operator,
+                Some(operand) => {
+                    operands.push(operand);
+                    continue;
+                }
+                None => {
+                    parser.pos += 1;
+                    continue;
+                }
+            };
+            let number = |index: usize| operands.get(index).and_then(Object::as_number).unwrap_or(0.0);
+
+            match object.as_str() {
+                "q" => saved.push(state.clone()),
+                "Q" => {
+                    if let Some(previous) = saved.pop() {
+                        state = previous;
+                    }
+                }
+                "cm" => {
+                    if let Some(matrix) = matrix_operand(&operands) {
+                        state.ctm = multiply(&matrix, &state.ctm);
+                    }
+                }
+                "BT" => {
+                    text_matrix = IDENTITY;
+                    line_matrix = IDENTITY;
+                }
+                "Tf" => {
+                    if let Some(name) = operands.first().and_then(Object::as_name) {
+                        if let Some(dictionary) = fonts_dictionary.and_then(|fonts| self.file.get(fonts, name)).and_then(Object::as_dictionary) {
+                            fonts.entry(name.to_string()).or_insert_with(|| Font::load(self.file, dictionary));
+                        }
+                        state.font = Some(name.to_string());
+                    }
+                    state.font_size = number(1);
+                }
+                "Tc" => state.char_spacing = number(0),
+                "Tw" => state.word_spacing = number(0),
+                "Tz" => state.scale = number(0) / 100.0,
+                "TL" => state.leading = number(0),
+                "Ts" => state.rise = number(0),
+                "Td" | "TD" => {
+                    if object == "TD" {
+                        state.leading = -number(1);
+                    }
+                    line_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, number(0), number(1)], &line_matrix);
+                    text_matrix = line_matrix;
+                }
+                "Tm" => {
+                    if let Some(matrix) = matrix_operand(&operands) {
+                        line_matrix = matrix;
+                        text_matrix = matrix;
+                    }
+                }
+                "T*" | "'" | "\"" | "Tj" | "TJ" => {
+                    if object == "\"" {
+                        state.word_spacing = number(0);
+                        state.char_spacing = number(1);
+                    }
+                    if matches!(object.as_str(), "T*" | "'" | "\"") {
+                        line_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, 0.0, -state.leading], &line_matrix);
+                        text_matrix = line_matrix;
+                    }
+                    let items: &[Object] = match operands.last() {
+                        Some(Object::Array(items)) if object == "TJ" => items,
+                        Some(string @ Object::String(_)) if object != "TJ" && object != "T*" => std::slice::from_ref(string),
+                        _ => &[],
+                    };
+                    let font = state.font.as_ref().and_then(|name| fonts.get(name)).unwrap_or(&fallback_font);
+                    self.show(&state, &mut text_matrix, font, items);
+                }
+                "Do" => {
+                    let form = operands.first()
+                        .and_then(Object::as_name)
+                        .and_then(|name| {
+                            let xobjects = self.file.get(resources, "XObject").and_then(Object::as_dictionary)?;
+                            self.file.get(xobjects, name)
+                        });
+                    if let Some(form @ Object::Stream(dictionary, _)) = form {
+                        if dictionary.get("Subtype").and_then(Object::as_name) == Some("Form") {
+                            let matrix = match self.file.get(dictionary, "Matrix") {
+                                Some(Object::Array(items)) => matrix_operand(items).unwrap_or(IDENTITY),
+                                _ => IDENTITY,
+                            };
+                            let form_resources = self.file.get(dictionary, "Resources").and_then(Object::as_dictionary).unwrap_or(resources);
+                            let content = self.file.stream_content(form);
+                            self.run(&content, form_resources, multiply(&matrix, &state.ctm), depth + 1);
+                        }
+                    }
+                }
+                "ID" => parser.skip_inline_image(),
+                _ => {}
+            }
+            operands.clear();
+        }
+    }
+
+    fn show(&mut self, state: &GraphicsState, text_matrix: &mut Matrix, font: &Font, items: &[Object]) {
+        let render = |text_matrix: &Matrix| {
+            let text_space = [state.font_size * state.scale, 0.0, 0.0, state.font_size, 0.0, state.rise];
+            multiply(&multiply(&text_space, text_matrix), &state.ctm)
+        };
+        let start = render(text_matrix);
+        let mut text = String::new();
+
+        for item in items {
+            match item {
+                Object::String(bytes) => {
+                    for code in font.codes(bytes) {
+                        text.push_str(&font.text(code));
+                        let mut advance = font.width(code) / 1000.0 * state.font_size + state.char_spacing;
+                        if !font.two_byte && code == 32 {
+                            advance += state.word_spacing;
+                        }
+                        *text_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, advance * state.scale, 0.0], text_matrix);
+                    }
+                }
+                Object::Number(adjustment) => {
+                    if *adjustment < -WORD_GAP_THOUSANDTHS && !text.is_empty() && !text.ends_with(' ') {
+                        text.push(' ');
+                    }
+                    let shift = -adjustment / 1000.0 * state.font_size * state.scale;
+                    *text_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, shift, 0.0], text_matrix);
+                }
+                _ => {}
+            }
+        }
+
+        // Rotated or mirrored text is mostly watermarks and margin stamps
+        let horizontal = start[0] > 0.0 && start[1].abs() <= start[0] * 0.1;
+        if !horizontal || text.trim().is_empty() {
+            return;
+        }
+        let end = render(text_matrix);
+        self.runs.push(TextRun {
+            x: start[4],
+            y: start[5],
+            end_x: end[4],
+            size: start[2].hypot(start[3]),
+            bold: font.bold,
+            text,
+        });
+    }
+}
+
+// A line of text on a page, top to bottom
+struct Line {
+    y: f64,
+    size: f64,
+    bold: bool,
+    text: String,
+}
+
+fn page_lines(mut runs: Vec<TextRun>) -> Vec<Line> {
+    runs.sort_by(|a, b| b.y.total_cmp(&a.y));
+    let mut lines = Vec::new();
+    let mut group: Vec<TextRun> = Vec::new();
+    for run in runs {
+        if let Some(first) = group.first() {
+            if (first.y - run.y).abs() > first.size.max(run.size) * 0.5 {
+                lines.push(join_runs(std::mem::take(&mut group)));
+            }
+        }
+        group.push(run);
+    }
+    if !group.is_empty() {
+        lines.push(join_runs(group));
+    }
+    lines.retain(|line| !line.text.trim().is_empty());
+    lines
+}
+
+fn join_runs(mut runs: Vec<TextRun>) -> Line {
+    runs.sort_by(|a, b| a.x.total_cmp(&b.x));
+    let mut text = String::new();
+    let mut end_x = f64::MIN;
+    for run in &runs {
+        if !text.is_empty() && run.x - end_x > run.size * 0.15 && !text.ends_with(' ') && !run.text.starts_with(' ') {
+            text.push(' ');
+        }
+        text.push_str(&run.text);
+        end_x = end_x.max(run.end_x);
+    }
+    Line {
+        y: runs[0].y,
+        size: runs.iter().map(|run| run.size).fold(0.0, f64::max),
+        bold: runs.iter().all(|run| run.bold),
+        text: text.split_whitespace().collect::<Vec<_>>().join(" "),
+    }
+}
+
+// "3", "- 3 -", "Page 3", "Page 3 of 10", "3/10"
+fn page_number_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^(?i:page\s*)?[-–—]?\s*\d+\s*[-–—]?(?:\s*(?i:of|/)\s*\d+)?$")
+            .expect("invalid page number pattern")
+    })
+}
+
+// Headers and footers usually differ between pages only in their numbers
+fn running_line_key(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_digit() { '#' } else { c })
+        .collect()
+}
+
+// Drop page numbers and the headers and footers repeated across pages
+fn remove_running_lines(pages: &mut [Vec<Line>]) {
+    let is_edge = |index: usize, count: usize| index < EDGE_LINES || index + EDGE_LINES >= count;
+
+    let mut repeats: BTreeMap<String, usize> = BTreeMap::new();
+    for lines in pages.iter() {
+        let keys: BTreeSet<String> = lines.iter().enumerate()
+            .filter(|(index, _)| is_edge(*index, lines.len()))
+            .map(|(_, line)| running_line_key(&line.text))
+            .collect();
+        for key in keys {
+            *repeats.entry(key).or_default() += 1;
+        }
+    }
+    let threshold = ((pages.len() as f64 * REPEATED_LINE_SHARE).ceil() as usize).max(2);
+
+    for lines in pages.iter_mut() {
+        let count = lines.len();
+        let mut index = 0;
+        lines.retain(|line| {
+            let edge = is_edge(index, count);
+            index += 1;
+            let running = page_number_regex().is_match(&line.text)
+                || repeats.get(&running_line_key(&line.text)).is_some_and(|&pages| pages >= threshold);
+            !(edge && running)
+        });
+    }
+}
+
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    values.sort_by(f64::total_cmp);
+    values.get(values.len() / 2).copied()
+}
+
+fn ends_sentence(text: &str) -> bool {
+    text.trim_end().ends_with(['.', ':', ';', '!', '?'])
+}
+
+fn append_line(paragraph: &mut String, line: &str) {
+    // Rejoin words hyphenated at the end of a line
+    let hyphenated = paragraph.strip_suffix('-')
+        .and_then(|rest| rest.chars().last())
+        .is_some_and(char::is_alphabetic)
+        && line.starts_with(|c: char| c.is_lowercase());
+    if hyphenated {
+        paragraph.pop();
+    } else if !paragraph.is_empty() {
+        paragraph.push(' ');
+    }
+    paragraph.push_str(line);
+}
+
+// Rebuild paragraphs from the lines of every page. A paragraph continues
+// across a page break when the page ends mid-sentence.
+fn reflow(pages: &[Vec<Line>], builder: &mut TextBuilder) {
+    let body_size = median(pages.iter().flatten().map(|line| line.size).collect()).unwrap_or(0.0);
+    let line_gap = median(
+        pages.iter()
+            .flat_map(|lines| lines.windows(2).map(|pair| pair[0].y - pair[1].y))
+            .filter(|gap| *gap > 0.0)
+            .collect(),
+    )
+    .unwrap_or(body_size * 1.2);
+
+    let mut paragraph = String::new();
+    let mut paragraph_page = 1;
+    let flush = |paragraph: &mut String, page: u32, builder: &mut TextBuilder| {
+        if !paragraph.is_empty() {
+            builder.set_page(page);
+            builder.line(paragraph);
+            paragraph.clear();
+        }
+    };
+
+    for (index, lines) in pages.iter().enumerate() {
+        let page = index as u32 + 1;
+        let mut previous: Option<&Line> = None;
+        for line in lines {
+            let heading = line.text.chars().any(char::is_alphabetic)
+                && (line.size >= body_size * HEADING_SIZE_RATIO
+                    || (line.bold && line.text.chars().count() <= MAX_HEADING_CHARS && !ends_sentence(&line.text)));
+            if heading {
+                flush(&mut paragraph, paragraph_page, builder);
+                builder.set_page(page);
+                builder.heading(&line.text);
+                previous = None;
+                continue;
+            }
+
+            let breaks = match previous {
+                Some(previous) => {
+                    let gap = previous.y - line.y;
+                    gap > line_gap * PARAGRAPH_GAP_RATIO || gap < 0.0
+                }
+                // The first line of a page continues an unfinished sentence
+                None => ends_sentence(&paragraph),
+            };
+            let numbered = starts_with_section_marker(&line.text) && ends_sentence(&paragraph);
+            if breaks || numbered || line.text.starts_with(['•', '▪', '●', '◦']) {
+                flush(&mut paragraph, paragraph_page, builder);
+            }
+            if paragraph.is_empty() {
+                paragraph_page = page;
+            }
+            append_line(&mut paragraph, &line.text);
+            previous = Some(line);
+        }
+    }
+    flush(&mut paragraph, paragraph_page, builder);
+}
+
+// Extract the text layer of a PDF page by page, in reading order
+pub fn extract(bytes: &[u8]) -> Result<TextBuilder, String> {
+    let file = PdfFile::parse(bytes)?;
+    if file.catalog().is_none() {
+        return Err("The file is not a readable PDF: it has no document catalog".to_string());
+    }
+    let pages = file.pages();
+    if pages.is_empty() {
+        return Err("The PDF file has no pages that could be read".to_string());
+    }
+
+    let mut page_lines_list: Vec<Vec<Line>> = pages.iter()
+        .map(|(page, resources)| {
+            let content = match file.get(page, "Contents") {
+                Some(Object::Array(streams)) => streams.iter()
+                    .flat_map(|stream| {
+                        let mut data = file.stream_content(stream);
+                        data.push(b'\n');
+                        data
+                    })
+                    .collect(),
+                Some(stream) => file.stream_content(stream),
+                None => Vec::new(),
+            };
+            let mut interpreter = ContentInterpreter { file: &file, runs: Vec::new() };
+            interpreter.run(&content, resources, IDENTITY, 0);
+            page_lines(interpreter.runs)
+        })
+        .collect();
+
+    if page_lines_list.iter().all(Vec::is_empty) {
+        return Err("The PDF has no text layer; scanned documents need to be converted with OCR first".to_string());
+    }
+    remove_running_lines(&mut page_lines_list);
+
+    let mut builder = TextBuilder::default();
+    builder.set_page_count(pages.len() as u32);
+    reflow(&page_lines_list, &mut builder);
+    Ok(builder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ingest::{DocumentFormat, IngestedClause};
+
+    const HEADER: &str = "Lease Agreement - Confidential";
+
+    // A PDF file of the given objects, numbered from 1, in file order
+    fn build_pdf(objects: &[Vec<u8>], trailer: &str) -> Vec<u8> {
+        let mut file = b"%PDF-1.7\n".to_vec();
+        let mut offsets = Vec::new();
+        for (index, object) in objects.iter().enumerate() {
+            offsets.push(file.len());
+            file.extend(format!("{} 0 obj\n", index + 1).bytes());
+            file.extend(object);
+            file.extend(b"\nendobj\n");
+        }
+        let xref = file.len();
+        file.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).bytes());
+        for offset in offsets {
+            file.extend(format!("{:010} 00000 n \n", offset).bytes());
+        }
+        file.extend(format!("trailer\n{}\nstartxref\n{}\n%%EOF\n", trailer, xref).bytes());
+        file
+    }
+
+    fn stream(content: &[u8], compress: bool) -> Vec<u8> {
+        let (data, filter) = if compress {
+            (miniz_oxide::deflate::compress_to_vec_zlib(content, 6), " /Filter /FlateDecode")
+        } else {
+            (content.to_vec(), "")
+        };
+        let mut object = format!("<< /Length {}{} >>\nstream\n", data.len(), filter).into_bytes();
+        object.extend(data);
+        object.extend(b"\nendstream");
+        object
+    }
+
+    // Lines of 12pt body text from the top of the page, 14pt apart, with an
+    // optional running header and "Page n of m" footer
+    fn page_content(lines: &[&str], running: Option<(u32, u32)>) -> Vec<u8> {
+        let mut content = String::new();
+        let mut show = |size: u32, y: u32, text: &str| {
+            content.push_str(&format!("BT /F1 {} Tf 72 {} Td ({}) Tj ET\n", size, y, text));
+        };
+        if running.is_some() {
+            show(9, 760, HEADER);
+        }
+        for (index, line) in lines.iter().enumerate() {
+            show(12, 700 - 14 * index as u32, line);
+        }
+        if let Some((page, pages)) = running {
+            show(9, 40, &format!("Page {} of {}", page, pages));
+        }
+        content.into_bytes()
+    }
+
+    // Catalog, page tree, font, then a page and its content stream per page
+    fn document(pages: &[&[&str]], running: bool, compress: bool) -> Vec<u8> {
+        let kids: Vec<String> = (0..pages.len()).map(|index| format!("{} 0 R", 4 + 2 * index)).collect();
+        let mut objects = vec![
+            b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+            format!("<< /Type /Pages /Kids [{}] /Count {} /Resources << /Font << /F1 3 0 R >> >> >>", kids.join(" "), pages.len()).into_bytes(),
+            b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_vec(),
+        ];
+        for (index, lines) in pages.iter().enumerate() {
+            let running = running.then_some((index as u32 + 1, pages.len() as u32));
+            objects.push(format!("<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] /Contents {} 0 R >>", 5 + 2 * index).into_bytes());
+            objects.push(stream(&page_content(lines, running), compress));
+        }
+        build_pdf(&objects, &format!("<< /Size {} /Root 1 0 R >>", objects.len() + 1))
+    }
+
+    fn clauses(pdf: &[u8]) -> Vec<IngestedClause> {
+        extract(pdf).unwrap().finish(DocumentFormat::Pdf).clauses
+    }
+
+    fn summary(clauses: &[IngestedClause]) -> Vec<(Option<&str>, Option<u32>, &str)> {
+        clauses.iter().map(|clause| (clause.section.as_deref(), clause.page, clause.text.as_str())).collect()
+    }
+
+    #[test]
+    fn reads_an_uncompressed_pdf() {
+        let pdf = document(&[
+            &["1. The tenant shall pay the rent on the first day of each month."],
+            &["2. The landlord shall keep the roof in good repair."],
+        ], false, false);
+        let document = extract(&pdf).unwrap().finish(DocumentFormat::Pdf);
+        assert_eq!(document.pages, Some(2));
+        assert_eq!(summary(&document.clauses), vec![
+            (Some("1"), Some(1), "The tenant shall pay the rent on the first day of each month"),
+            (Some("2"), Some(2), "The landlord shall keep the roof in good repair"),
+        ]);
+    }
+
+    #[test]
+    fn drops_running_headers_footers_and_page_numbers_from_compressed_pdfs() {
+        let pdf = document(&[
+            &[
+                "1. The tenant shall pay the rent on the first day",
+                "of each month.",
+                "2. The landlord shall keep the roof in good repair.",
+            ],
+            &["3. Pets are allowed with written consent."],
+            &["4. Either party may end this lease with 60 days notice."],
+        ], true, true);
+        // The text is only in the compressed streams
+        assert!(!pdf.windows(6).any(|window| window == b"tenant"));
+
+        let clauses = clauses(&pdf);
+        assert_eq!(summary(&clauses), vec![
+            (Some("1"), Some(1), "The tenant shall pay the rent on the first day of each month"),
+            (Some("2"), Some(1), "The landlord shall keep the roof in good repair"),
+            (Some("3"), Some(2), "Pets are allowed with written consent"),
+            (Some("4"), Some(3), "Either party may end this lease with 60 days notice"),
+        ]);
+        assert!(clauses.iter().all(|clause| !clause.text.contains("Confidential") && !clause.text.contains("Page")));
+    }
+
+    #[test]
+    fn rejoins_a_word_hyphenated_across_a_page_break() {
+        let pdf = document(&[
+            &[
+                "5. The deposit shall be returned within thirty days of the ter-",
+            ],
+            &[
+                "mination of the lease.",
+                "6. Rent may be raised once a year.",
+            ],
+        ], true, true);
+        assert_eq!(summary(&clauses(&pdf)), vec![
+            (Some("5"), Some(1), "The deposit shall be returned within thirty days of the termination of the lease"),
+            (Some("6"), Some(2), "Rent may be raised once a year"),
+        ]);
+    }
+
+    #[test]
+    fn refuses_a_truncated_stream() {
+        for compress in [false, true] {
+            let pdf = document(&[&["1. The tenant shall pay the rent."], &["2. Pets are allowed."]], false, compress);
+            // Cut the file inside the second page's content stream
+            let stream = pdf.windows(10).rposition(|window| window == b">>\nstream\n").unwrap();
+            let result = extract(&pdf[..stream + 15]);
+            assert_eq!(result.err().as_deref(), Some("The PDF file is incomplete; upload the whole file again"));
+        }
+    }
+
+    #[test]
+    fn refuses_a_page_tree_that_only_loops() {
+        let pdf = build_pdf(&[
+            b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+            b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_vec(),
+            b"<< /Type /Pages /Kids [2 0 R 3 0 R] /Count 1 >>".to_vec(),
+        ], "<< /Size 4 /Root 1 0 R >>");
+        assert_eq!(extract(&pdf).err().as_deref(), Some("The PDF file has no pages that could be read"));
+    }
+
+    #[test]
+    fn reads_each_page_of_a_looping_page_tree_once() {
+        let mut pdf = document(&[&["1. The tenant shall pay the rent."]], false, false);
+        // Point the page back at the page tree as a kid of its own
+        let kids = b"/Kids [4 0 R]";
+        let at = pdf.windows(kids.len()).position(|window| window == kids).unwrap();
+        pdf.splice(at..at + kids.len(), b"/Kids [4 0 R 2 0 R 4 0 R]".iter().copied());
+        assert_eq!(summary(&clauses(&pdf)), vec![(Some("1"), Some(1), "The tenant shall pay the rent")]);
+    }
+
+    #[test]
+    fn refuses_a_file_without_a_catalog() {
+        let pdf = build_pdf(&[
+            b"<< /Type /Pages /Kids [2 0 R] /Count 1 >>".to_vec(),
+            b"<< /Type /Page /Parent 1 0 R /Contents 3 0 R >>".to_vec(),
+            stream(b"BT /F1 12 Tf 72 700 Td (1. Rent is due monthly.) Tj ET", false),
+        ], "<< /Size 4 >>");
+        assert!(extract(&pdf).is_err());
+    }
+
+    #[test]
+    fn refuses_garbage_without_panicking() {
+        for bytes in [
+            &b"%PDF-1.7\n"[..],
+            b"%PDF-1.7\n1 0 obj << /Type /Catalog /Pages 2 0 R",
+            b"%PDF-1.7\n1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj trailer << /Root 1 0 R >>",
+            b"%PDF-1.7\n1 0 obj [[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[ endobj",
+            b"%PDF-1.7\n1 0 obj << /Length 99999999999 >> stream\nxyz",
+        ] {
+            assert!(extract(bytes).is_err());
+        }
+    }
+}
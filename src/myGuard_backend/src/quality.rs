@@ -0,0 +1,350 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+
+use crate::category::ClauseCategory;
+use crate::dataset::{normalize_text, DatasetParseError, ParsedDataset};
+use crate::{text_similarity, ContractRecord};
+
+// Different wordings sharing at least this much are treated as the same clause
+pub const NEAR_DUPLICATE_THRESHOLD: f64 = 0.8;
+
+// Entries with fewer words carry too little context to classify against
+pub const MIN_WORDS: usize = 4;
+
+// Labels count as unbalanced when the rarest one holds less than this share
+pub const MIN_LABEL_SHARE: f64 = 0.3;
+
+const VALID_LABELS: [&str; 2] = ["Allowed", "Not Allowed"];
+
+// A record named in a finding. `row` is the CSV line for imports and the
+// 1-based position in the dataset for the live records.
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct RecordRef {
+    pub row: u64,
+    pub contract_text: String,
+    pub label: String,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct SimilarPair {
+    pub first: RecordRef,
+    pub second: RecordRef,
+    pub similarity: f64,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct DatasetQualityReport {
+    pub total_records: usize,
+    pub parse_errors: Vec<DatasetParseError>,
+    // Labels other than Allowed and Not Allowed
+    pub invalid_labels: Vec<RecordRef>,
+    pub short_entries: Vec<RecordRef>,
+    // Records sharing an id with an earlier record of different wording
+    pub duplicate_ids: Vec<RecordRef>,
+    // Same wording and label
+    pub exact_duplicates: Vec<SimilarPair>,
+    // Similar wording and the same label
+    pub near_duplicates: Vec<SimilarPair>,
+    // Same or similar wording with different labels
+    pub conflicting_labels: Vec<SimilarPair>,
+    pub label_balance: Vec<(String, usize)>,
+    pub category_balance: Vec<(ClauseCategory, usize)>,
+    pub label_imbalance: bool,
+    // No blocking findings; duplicates and imbalance are warnings only
+    pub passed: bool,
+}
+
+fn record_ref(record: &ContractRecord, row: u64) -> RecordRef {
+    RecordRef {
+        row,
+        contract_text: record.contract_text.clone(),
+        label: record.label.clone(),
+    }
+}
+
+fn pair(first: (&ContractRecord, u64), second: (&ContractRecord, u64), similarity: f64) -> SimilarPair {
+    SimilarPair {
+        first: record_ref(first.0, first.1),
+        second: record_ref(second.0, second.1),
+        similarity,
+    }
+}
+
+// Maps each word to the records containing it, so a clause is only compared
+// with records it could be a near duplicate of
+struct WordIndex {
+    postings: BTreeMap<String, Vec<usize>>,
+}
+
+// Words as `text_similarity` sees them
+fn words(text: &str) -> BTreeSet<String> {
+    text.to_lowercase().split_whitespace().map(str::to_string).collect()
+}
+
+impl WordIndex {
+    fn new<'a>(texts: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut postings: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for (index, text) in texts.into_iter().enumerate() {
+            for word in words(text) {
+                postings.entry(word).or_default().push(index);
+            }
+        }
+        WordIndex { postings }
+    }
+
+    // Records that `text_similarity(text, record)` may rate at or above
+    // NEAR_DUPLICATE_THRESHOLD. That needs T * max(n, m) of the n words of `text`
+    // in the record, so n >= T * max(n, m) and at most (1 - T) / T * n of its
+    // words are missing. Any that many plus one distinct words therefore include
+    // one the record contains; the rarest are used to keep the candidates few.
+    fn candidates(&self, text: &str) -> BTreeSet<usize> {
+        let word_count = text.split_whitespace().count();
+        let max_missing = ((1.0 - NEAR_DUPLICATE_THRESHOLD) / NEAR_DUPLICATE_THRESHOLD * word_count as f64).ceil() as usize;
+        let mut distinct: Vec<(usize, String)> = words(text).into_iter()
+            .map(|word| (self.postings.get(&word).map_or(0, Vec::len), word))
+            .collect();
+        distinct.sort();
+        distinct.iter()
+            .take(max_missing + 1)
+            .filter_map(|(_, word)| self.postings.get(word))
+            .flatten()
+            .copied()
+            .collect()
+    }
+}
+
+fn update_passed(report: &mut DatasetQualityReport) {
+    report.passed = report.parse_errors.is_empty()
+        && report.invalid_labels.is_empty()
+        && report.short_entries.is_empty()
+        && report.duplicate_ids.is_empty()
+        && report.conflicting_labels.is_empty();
+}
+
+// Check a set of records. `rows[i]` identifies `records[i]` in the findings.
+pub fn check_records(records: &[ContractRecord], rows: &[u64], parse_errors: Vec<DatasetParseError>) -> DatasetQualityReport {
+    let normalized: Vec<String> = records.iter().map(|r| normalize_text(&r.contract_text)).collect();
+
+    let invalid_labels = records.iter().zip(rows)
+        .filter(|(r, _)| !VALID_LABELS.contains(&r.label.as_str()))
+        .map(|(r, row)| record_ref(r, *row))
+        .collect();
+    let short_entries = records.iter().zip(rows)
+        .filter(|(r, _)| r.contract_text.split_whitespace().count() < MIN_WORDS)
+        .map(|(r, row)| record_ref(r, *row))
+        .collect();
+
+    // Wordings seen so far under each id
+    let mut wordings: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    let mut duplicate_ids = Vec::new();
+    for (j, record) in records.iter().enumerate() {
+        let seen = wordings.entry(record.id.as_str()).or_default();
+        if seen.iter().any(|wording| *wording != normalized[j]) {
+            duplicate_ids.push(record_ref(record, rows[j]));
+        }
+        seen.insert(&normalized[j]);
+    }
+
+    let index = WordIndex::new(records.iter().map(|r| r.contract_text.as_str()));
+    let mut exact_duplicates = Vec::new();
+    let mut near_duplicates = Vec::new();
+    let mut conflicting_labels = Vec::new();
+    for i in 0..records.len() {
+        for j in index.candidates(&records[i].contract_text).range(i + 1..).copied() {
+            let similarity = if normalized[i] == normalized[j] {
+                1.0
+            } else {
+                text_similarity(&records[i].contract_text, &records[j].contract_text)
+            };
+            if similarity < NEAR_DUPLICATE_THRESHOLD {
+                continue;
+            }
+            let found = pair((&records[i], rows[i]), (&records[j], rows[j]), similarity);
+            if records[i].label != records[j].label {
+                conflicting_labels.push(found);
+            } else if normalized[i] == normalized[j] {
+                exact_duplicates.push(found);
+            } else {
+                near_duplicates.push(found);
+            }
+        }
+    }
+
+    let mut label_balance: Vec<(String, usize)> = Vec::new();
+    for record in records {
+        match label_balance.iter_mut().find(|(label, _)| *label == record.label) {
+            Some((_, count)) => *count += 1,
+            None => label_balance.push((record.label.clone(), 1)),
+        }
+    }
+    label_balance.sort();
+
+    let mut categories: Vec<ClauseCategory> = records.iter()
+        .map(|r| r.effective_category())
+        .collect();
+    categories.sort();
+    let mut category_balance: Vec<(ClauseCategory, usize)> = Vec::new();
+    for category in categories {
+        match category_balance.last_mut() {
+            Some((last, count)) if *last == category => *count += 1,
+            _ => category_balance.push((category, 1)),
+        }
+    }
+
+    let label_imbalance = !records.is_empty() && VALID_LABELS.iter().any(|valid| {
+        let count = label_balance.iter().find(|(label, _)| label == valid).map_or(0, |(_, c)| *c);
+        (count as f64 / records.len() as f64) < MIN_LABEL_SHARE
+    });
+
+    let mut report = DatasetQualityReport {
+        total_records: records.len(),
+        parse_errors,
+        invalid_labels,
+        short_entries,
+        duplicate_ids,
+        exact_duplicates,
+        near_duplicates,
+        conflicting_labels,
+        label_balance,
+        category_balance,
+        label_imbalance,
+        passed: false,
+    };
+    update_passed(&mut report);
+    report
+}
+
+// Check an import on its own and against the live dataset. Records with the
+// same wording as a live record replace its label on import, so only similar
+// (not identical) live records with another label count as conflicts.
+pub fn check_import(existing: &[ContractRecord], import: &ParsedDataset) -> DatasetQualityReport {
+    let mut report = check_records(&import.records, &import.rows, import.errors.clone());
+
+    let word_index = WordIndex::new(existing.iter().map(|r| r.contract_text.as_str()));
+    for (record, row) in import.records.iter().zip(&import.rows) {
+        let key = normalize_text(&record.contract_text);
+        for index in word_index.candidates(&record.contract_text) {
+            let live = &existing[index];
+            if live.label == record.label || normalize_text(&live.contract_text) == key {
+                continue;
+            }
+            let similarity = text_similarity(&record.contract_text, &live.contract_text);
+            if similarity >= NEAR_DUPLICATE_THRESHOLD {
+                report.conflicting_labels.push(pair((live, index as u64 + 1), (record, *row), similarity));
+            }
+        }
+    }
+
+    update_passed(&mut report);
+    report
+}
+
+// One-line description of the blocking findings, for rejected imports
+pub fn blocking_summary(report: &DatasetQualityReport) -> String {
+    let mut problems = Vec::new();
+    if !report.parse_errors.is_empty() {
+        let rows: Vec<String> = report.parse_errors.iter().map(|e| e.row.to_string()).collect();
+        problems.push(format!("unreadable rows {}", rows.join(", ")));
+    }
+    if !report.invalid_labels.is_empty() {
+        let rows: Vec<String> = report.invalid_labels.iter().map(|r| r.row.to_string()).collect();
+        problems.push(format!("invalid labels on rows {}", rows.join(", ")));
+    }
+    if !report.short_entries.is_empty() {
+        let rows: Vec<String> = report.short_entries.iter().map(|r| r.row.to_string()).collect();
+        problems.push(format!("entries shorter than {} words on rows {}", MIN_WORDS, rows.join(", ")));
+    }
+    if !report.duplicate_ids.is_empty() {
+        let rows: Vec<String> = report.duplicate_ids.iter().map(|r| r.row.to_string()).collect();
+        problems.push(format!("reused ids on rows {}", rows.join(", ")));
+    }
+    if !report.conflicting_labels.is_empty() {
+        problems.push(format!("{} conflicting labels", report.conflicting_labels.len()));
+    }
+    format!("The import failed quality checks: {}", problems.join("; "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_contract_dataset, CSV_DATA};
+
+    fn record(id: &str, text: &str, label: &str) -> ContractRecord {
+        ContractRecord {
+            id: id.to_string(),
+            contract_text: text.to_string(),
+            label: label.to_string(),
+            ..Default::default()
+        }
+    }
+
+    // Every pair the exhaustive comparison finds
+    fn brute_force_pairs(records: &[ContractRecord]) -> BTreeSet<(usize, usize)> {
+        let mut pairs = BTreeSet::new();
+        for i in 0..records.len() {
+            for j in (i + 1)..records.len() {
+                let same = normalize_text(&records[i].contract_text) == normalize_text(&records[j].contract_text);
+                if same || text_similarity(&records[i].contract_text, &records[j].contract_text) >= NEAR_DUPLICATE_THRESHOLD {
+                    pairs.insert((i, j));
+                }
+            }
+        }
+        pairs
+    }
+
+    fn reported_pairs(report: &DatasetQualityReport) -> BTreeSet<(usize, usize)> {
+        report.exact_duplicates.iter()
+            .chain(&report.near_duplicates)
+            .chain(&report.conflicting_labels)
+            .map(|pair| (pair.first.row as usize - 1, pair.second.row as usize - 1))
+            .collect()
+    }
+
+    #[test]
+    fn word_index_finds_every_similar_pair() {
+        let mut records = parse_contract_dataset(CSV_DATA).records;
+        let base = "the tenant shall pay the monthly rent on the first day of each month";
+        records.push(record("a", base, "Allowed"));
+        records.push(record("b", &base.to_uppercase(), "Allowed"));
+        // Two of fourteen words changed, and a different label
+        records.push(record("c", "the tenant shall pay the monthly fee on the first day of each week", "Not Allowed"));
+        records.push(record("d", "the tenant shall pay the rent", "Allowed"));
+        let rows: Vec<u64> = (1..=records.len() as u64).collect();
+
+        let report = check_records(&records, &rows, Vec::new());
+        let expected = brute_force_pairs(&records);
+        assert!(expected.len() >= 3);
+        assert_eq!(reported_pairs(&report), expected);
+    }
+
+    #[test]
+    fn reused_ids_are_reported_once_the_wording_differs() {
+        let records = vec![
+            record("x", "the landlord keeps the deposit", "Not Allowed"),
+            record("x", "The landlord  keeps the deposit", "Not Allowed"),
+            record("x", "the tenant may sublet the premises", "Allowed"),
+            record("y", "the tenant may sublet the premises", "Allowed"),
+        ];
+        let report = check_records(&records, &[1, 2, 3, 4], Vec::new());
+        let rows: Vec<u64> = report.duplicate_ids.iter().map(|r| r.row).collect();
+        assert_eq!(rows, vec![3]);
+    }
+
+    #[test]
+    fn imports_conflicting_with_similar_live_records_are_found() {
+        let existing = vec![
+            record("1", "the landlord may enter the premises at any time without notice", "Not Allowed"),
+            record("2", "the tenant shall keep the premises clean", "Allowed"),
+        ];
+        let import = ParsedDataset {
+            records: vec![record("3", "the landlord may enter the premises at any hour without notice", "Allowed")],
+            rows: vec![2],
+            errors: Vec::new(),
+        };
+        let report = check_import(&existing, &import);
+        assert_eq!(report.conflicting_labels.len(), 1);
+        assert_eq!(report.conflicting_labels[0].first.row, 1);
+    }
+}
@@ -0,0 +1,322 @@
+use std::sync::OnceLock;
+
+use candid::{CandidType, Deserialize};
+use regex::Regex;
+use serde::Serialize;
+
+use crate::extraction;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum PiiKind {
+    Name,
+    NationalId,
+    Phone,
+    Email,
+    BankAccount,
+    Address,
+}
+
+impl PiiKind {
+    pub const ALL: [PiiKind; 6] = [
+        PiiKind::Name,
+        PiiKind::NationalId,
+        PiiKind::Phone,
+        PiiKind::Email,
+        PiiKind::BankAccount,
+        PiiKind::Address,
+    ];
+
+    fn placeholder_prefix(&self) -> &'static str {
+        match self {
+            PiiKind::Name => "NAME",
+            PiiKind::NationalId => "NATIONAL_ID",
+            PiiKind::Phone => "PHONE",
+            PiiKind::Email => "EMAIL",
+            PiiKind::BankAccount => "BANK_ACCOUNT",
+            PiiKind::Address => "ADDRESS",
+        }
+    }
+}
+
+// Per-request redaction settings. Without a config every kind is redacted.
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct RedactionConfig {
+    pub enabled: bool,
+    // Kinds to redact; all kinds when absent
+    #[serde(default)]
+    pub kinds: Option<Vec<PiiKind>>,
+}
+
+// Rwandan national ID: citizen digit, birth year, sex digit (7/8), 7-digit serial,
+// issue count and 2-digit check, e.g. "1 1990 8 0012345 0 12" or "1199080012345012"
+fn national_id_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"\b[1-3][\s-]?(?:19|20)\d{2}[\s-]?[78][\s-]?\d{7}[\s-]?\d[\s-]?\d{2}\b")
+            .expect("invalid national id pattern")
+    })
+}
+
+// Rwandan mobile (072/073/078/079) and Kigali landline (0252) numbers, local or
+// with the +250 prefix, then other international numbers
+fn phone_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(?:(?:\+|\b00)?\b250[\s.-]?|\b0)(?:7[2389](?:[\s.-]?\d){7}|252(?:[\s.-]?\d){6})\b|\+\d{1,3}(?:[\s.-]?\d){7,12}\b")
+            .expect("invalid phone pattern")
+    })
+}
+
+fn email_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b").expect("invalid email pattern")
+    })
+}
+
+// IBANs, and digit runs introduced as an account number
+fn bank_account_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(?i)\b[A-Z]{2}\d{2}(?:\s?[A-Z0-9]{4}){3,7}(?:\s?[A-Z0-9]{1,4})?\b|\b(?:account|acct|a/c)\s*(?:no\.?|number|#)?\s*[:.]?\s*(\d[\d\s-]{6,22}\d)\b")
+            .expect("invalid bank account pattern")
+    })
+}
+
+// Kigali street codes ("KG 11 Ave", "KN 5 Rd"), P.O. boxes and house numbers
+fn address_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(?i)\b(?:KG|KN|KK|RN|NM|NR)\s?\d{1,4}\s?(?:St|Street|Ave|Avenue|Rd|Road)\b\.?|\bP\.?\s?O\.?\s?Box\s+\d+\b|\b(?:House|Plot|Apartment|Apt)\s+(?:No\.?\s*)?\d+[A-Z]?\b")
+            .expect("invalid address pattern")
+    })
+}
+
+// Titled names ("Mr. Jean Mugabo") and labelled names ("Name: Aline Uwase")
+fn name_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"\b(?:Mr|Mrs|Ms|Miss|Dr|Prof|Madame|Monsieur|Me)\.?\s+([A-Z][\w'-]+(?:\s+[A-Z][\w'-]+){0,3})|(?i:\b(?:full\s+)?names?\s*:\s*)([A-Z][\w'-]+(?:\s+[A-Z][\w'-]+){0,3})")
+            .expect("invalid name pattern")
+    })
+}
+
+// Byte range of every match, or of its first capture group when the pattern has context
+fn match_ranges(regex: &Regex, text: &str) -> Vec<(usize, usize)> {
+    regex.captures_iter(text)
+        .filter_map(|caps| {
+            let m = caps.iter().skip(1).flatten().next().or_else(|| caps.get(0))?;
+            Some((m.start(), m.end()))
+        })
+        .collect()
+}
+
+// Values written differently but denoting the same thing share a placeholder
+fn normalize_value(kind: PiiKind, value: &str) -> String {
+    match kind {
+        PiiKind::Phone => {
+            let digits: String = value.chars().filter(|c| c.is_ascii_digit()).collect();
+            // 0788123456, 250788123456 and 00250788123456 are the same number
+            let national = digits.trim_start_matches("00");
+            let national = national.strip_prefix("250").unwrap_or(national);
+            national.trim_start_matches('0').to_string()
+        }
+        PiiKind::NationalId | PiiKind::BankAccount => {
+            value.chars().filter(|c| c.is_ascii_alphanumeric()).collect::<String>().to_uppercase()
+        }
+        PiiKind::Email | PiiKind::Name | PiiKind::Address => {
+            value.split_whitespace().collect::<Vec<&str>>().join(" ").to_lowercase()
+        }
+    }
+}
+
+struct Replacement {
+    kind: PiiKind,
+    key: String,
+    placeholder: String,
+    // First spelling seen, restored in returned text
+    original: String,
+}
+
+// Replaces PII with placeholders such as "[PHONE_1]" before text is sent to the
+// model and puts the original values back into the model's answers. The same
+// value keeps the same placeholder for the lifetime of the redactor.
+pub struct Redactor {
+    kinds: Vec<PiiKind>,
+    known_names: Vec<String>,
+    replacements: Vec<Replacement>,
+}
+
+impl Redactor {
+    pub fn new(config: Option<&RedactionConfig>) -> Self {
+        let kinds = match config {
+            Some(config) if !config.enabled => Vec::new(),
+            Some(RedactionConfig { kinds: Some(kinds), .. }) => kinds.clone(),
+            _ => PiiKind::ALL.to_vec(),
+        };
+        Redactor {
+            kinds,
+            known_names: Vec::new(),
+            replacements: Vec::new(),
+        }
+    }
+
+    // Also redact the party names found in the full contract, wherever they recur
+    pub fn with_parties_of(mut self, contract_text: &str) -> Self {
+        if self.kinds.contains(&PiiKind::Name) {
+            self.known_names = extraction::extract_with_rules(contract_text).parties.into_iter()
+                .map(|party| party.name.value.trim().to_string())
+                .filter(|name| name.len() > 2)
+                .collect();
+            // Longer names first so "Jean Mugabo" wins over "Jean"
+            self.known_names.sort_by_key(|name| std::cmp::Reverse(name.len()));
+        }
+        self
+    }
+
+    pub fn redacted_values(&self) -> usize {
+        self.replacements.len()
+    }
+
+    fn find_pii(&self, text: &str) -> Vec<(usize, usize, PiiKind)> {
+        let mut found = Vec::new();
+        for kind in &self.kinds {
+            let ranges = match kind {
+                PiiKind::NationalId => match_ranges(national_id_regex(), text),
+                PiiKind::Phone => match_ranges(phone_regex(), text),
+                PiiKind::Email => match_ranges(email_regex(), text),
+                PiiKind::BankAccount => match_ranges(bank_account_regex(), text),
+                PiiKind::Address => match_ranges(address_regex(), text),
+                PiiKind::Name => {
+                    let mut ranges = match_ranges(name_regex(), text);
+                    for name in &self.known_names {
+                        ranges.extend(text.match_indices(name.as_str()).map(|(start, m)| (start, start + m.len())));
+                    }
+                    ranges
+                }
+            };
+            found.extend(ranges.into_iter().map(|(start, end)| (start, end, *kind)));
+        }
+
+        // Earliest first, longest first on ties; overlapping matches are dropped
+        found.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+        let mut accepted: Vec<(usize, usize, PiiKind)> = Vec::new();
+        for candidate in found {
+            if accepted.last().is_none_or(|last| candidate.0 >= last.1) {
+                accepted.push(candidate);
+            }
+        }
+        accepted
+    }
+
+    fn placeholder_for(&mut self, kind: PiiKind, value: &str) -> String {
+        let key = normalize_value(kind, value);
+        if let Some(existing) = self.replacements.iter().find(|r| r.kind == kind && r.key == key) {
+            return existing.placeholder.clone();
+        }
+        let number = self.replacements.iter().filter(|r| r.kind == kind).count() + 1;
+        let placeholder = format!("[{}_{}]", kind.placeholder_prefix(), number);
+        self.replacements.push(Replacement {
+            kind,
+            key,
+            placeholder: placeholder.clone(),
+            original: value.to_string(),
+        });
+        placeholder
+    }
+
+    pub fn redact(&mut self, text: &str) -> String {
+        let mut redacted = String::with_capacity(text.len());
+        let mut last = 0;
+        for (start, end, kind) in self.find_pii(text) {
+            redacted.push_str(&text[last..start]);
+            let placeholder = self.placeholder_for(kind, &text[start..end]);
+            redacted.push_str(&placeholder);
+            last = end;
+        }
+        redacted.push_str(&text[last..]);
+        redacted
+    }
+
+    pub fn restore(&self, text: &str) -> String {
+        let mut restored = text.to_string();
+        for replacement in &self.replacements {
+            restored = restored.replace(&replacement.placeholder, &replacement.original);
+        }
+        restored
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redact_all(text: &str) -> (String, Redactor) {
+        let mut redactor = Redactor::new(None);
+        let redacted = redactor.redact(text);
+        (redacted, redactor)
+    }
+
+    #[test]
+    fn redacts_rwandan_national_ids() {
+        for id in ["1 1990 8 0012345 0 12", "1199080012345012", "1-1985-7-0234567-1-45"] {
+            let (redacted, _) = redact_all(&format!("The tenant, holder of ID {}, agrees.", id));
+            assert_eq!(redacted, "The tenant, holder of ID [NATIONAL_ID_1], agrees.", "{}", id);
+        }
+    }
+
+    #[test]
+    fn redacts_rwandan_phone_numbers() {
+        for phone in ["+250788123456", "+250 788 123 456", "250-738-123-456", "0788123456", "0722 123 456", "079 912 3456", "+250 252 123 456"] {
+            let (redacted, _) = redact_all(&format!("Call {} for repairs.", phone));
+            assert_eq!(redacted, "Call [PHONE_1] for repairs.", "{}", phone);
+        }
+    }
+
+    #[test]
+    fn same_number_in_different_formats_shares_a_placeholder() {
+        let (redacted, redactor) = redact_all("Landlord: 0788 123 456. Alternative: +250788123456. Agent: 0722000111.");
+        assert_eq!(redacted, "Landlord: [PHONE_1]. Alternative: [PHONE_1]. Agent: [PHONE_2].");
+        assert_eq!(redactor.redacted_values(), 2);
+    }
+
+    #[test]
+    fn placeholders_are_stable_across_calls_and_restored() {
+        let mut redactor = Redactor::new(None);
+        let first = redactor.redact("Pay Mr. Jean Mugabo at jean.mugabo@example.rw.");
+        let second = redactor.redact("Notices go to jean.mugabo@example.rw.");
+        assert_eq!(first, "Pay Mr. [NAME_1] at [EMAIL_1].");
+        assert_eq!(second, "Notices go to [EMAIL_1].");
+        assert_eq!(redactor.restore("[NAME_1] must reply to [EMAIL_1]."), "Jean Mugabo must reply to jean.mugabo@example.rw.");
+    }
+
+    #[test]
+    fn redacts_accounts_and_addresses() {
+        let (redacted, _) = redact_all("Rent is paid to account no. 000400012345678 at KG 11 Ave, P.O. Box 1234 Kigali.");
+        assert_eq!(redacted, "Rent is paid to account no. [BANK_ACCOUNT_1] at [ADDRESS_1], [ADDRESS_2] Kigali.");
+    }
+
+    #[test]
+    fn leaves_amounts_and_dates_alone() {
+        let text = "The employee earns RWF 1,200,000 per month from 1 January 2024 with 30 days notice.";
+        assert_eq!(redact_all(text).0, text);
+    }
+
+    #[test]
+    fn respects_the_request_config() {
+        let text = "Call 0788123456 or write to info@example.rw.";
+        let disabled = RedactionConfig { enabled: false, kinds: None };
+        assert_eq!(Redactor::new(Some(&disabled)).redact(text), text);
+
+        let phones_only = RedactionConfig { enabled: true, kinds: Some(vec![PiiKind::Phone]) };
+        assert_eq!(Redactor::new(Some(&phones_only)).redact(text), "Call [PHONE_1] or write to info@example.rw.");
+    }
+
+    #[test]
+    fn redacts_party_names_throughout_the_contract() {
+        let contract = "This agreement is made between Aline Uwase (the Landlord) and Eric Habimana (the Tenant).";
+        let mut redactor = Redactor::new(None).with_parties_of(contract);
+        let clause = redactor.redact("Eric Habimana shall pay rent to Aline Uwase.");
+        assert!(!clause.contains("Habimana") && !clause.contains("Uwase"), "{}", clause);
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::time::Duration;
+
+use candid::{CandidType, Deserialize, Principal};
+use serde::Serialize;
+
+use crate::calendar;
+use crate::obligations::Obligation;
+
+// How often stored obligations are scanned for upcoming deadlines
+pub const SCAN_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub const DEFAULT_LEAD_DAYS: u32 = 7;
+pub const MAX_LEAD_DAYS: u32 = 90;
+
+// Oldest notifications are dropped beyond this many per principal
+const MAX_NOTIFICATIONS: usize = 200;
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct Notification {
+    pub id: u64,
+    pub analysis_id: u64,
+    pub obligor: String,
+    pub action: String,
+    // YYYY-MM-DD of the occurrence being announced
+    pub due_date: String,
+    pub days_left: u32,
+    pub message: String,
+    pub created_at: u64,
+    pub read: bool,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct Inbox {
+    lead_days: Option<u32>,
+    // Newest last
+    notifications: VecDeque<Notification>,
+    // Occurrences already announced: analysis id, obligation index and due day
+    sent: BTreeSet<(u64, usize, i64)>,
+}
+
+impl Inbox {
+    fn lead_days(&self) -> u32 {
+        self.lead_days.unwrap_or(DEFAULT_LEAD_DAYS)
+    }
+}
+
+// Per-principal inboxes of deadline reminders
+#[derive(Serialize, Deserialize)]
+pub struct Reminders {
+    inboxes: BTreeMap<Principal, Inbox>,
+    next_id: u64,
+}
+
+impl Reminders {
+    pub const fn new() -> Self {
+        Reminders {
+            inboxes: BTreeMap::new(),
+            next_id: 1,
+        }
+    }
+
+    pub fn lead_days(&self, principal: &Principal) -> u32 {
+        self.inboxes.get(principal).map_or(DEFAULT_LEAD_DAYS, Inbox::lead_days)
+    }
+
+    pub fn set_lead_days(&mut self, principal: Principal, days: u32) -> Result<(), String> {
+        if days > MAX_LEAD_DAYS {
+            return Err(format!("Lead time cannot exceed {} days", MAX_LEAD_DAYS));
+        }
+        self.inboxes.entry(principal).or_default().lead_days = Some(days);
+        Ok(())
+    }
+
+    // Newest first
+    pub fn list(&self, principal: &Principal, unread_only: bool) -> Vec<Notification> {
+        self.inboxes.get(principal).map_or_else(Vec::new, |inbox| {
+            inbox.notifications.iter().rev()
+                .filter(|notification| !unread_only || !notification.read)
+                .cloned()
+                .collect()
+        })
+    }
+
+    // Returns how many of the given notifications were unread
+    pub fn mark_read(&mut self, principal: &Principal, ids: &[u64]) -> u32 {
+        let Some(inbox) = self.inboxes.get_mut(principal) else { return 0 };
+        let mut marked = 0;
+        for notification in inbox.notifications.iter_mut() {
+            if !notification.read && ids.contains(&notification.id) {
+                notification.read = true;
+                marked += 1;
+            }
+        }
+        marked
+    }
+
+    // Notify owners of obligations falling due within their lead time. Each
+    // occurrence is announced once. Returns the number of new notifications.
+    pub fn scan<'a>(&mut self, analyses: impl IntoIterator<Item = (Principal, u64, &'a [Obligation])>, now: u64) -> u32 {
+        let today = calendar::day_of(now);
+        let mut created = 0;
+        for (owner, analysis_id, obligations) in analyses {
+            // Anonymous analyses are shared by everyone who calls without signing in
+            if owner == Principal::anonymous() {
+                continue;
+            }
+            for (index, obligation) in obligations.iter().enumerate() {
+                let Some(due) = obligation.deadline.as_ref().and_then(|deadline| deadline.next_due(today)) else { continue };
+                let days_left = (due - today) as u32;
+                let inbox = self.inboxes.entry(owner).or_default();
+                if days_left > inbox.lead_days() || !inbox.sent.insert((analysis_id, index, due)) {
+                    continue;
+                }
+
+                let due_date = calendar::iso_date(due);
+                let message = format!("{}: {}, due {}", obligation.obligor, obligation.action, when(days_left, &due_date));
+                inbox.notifications.push_back(Notification {
+                    id: self.next_id,
+                    analysis_id,
+                    obligor: obligation.obligor.clone(),
+                    action: obligation.action.clone(),
+                    due_date,
+                    days_left,
+                    message,
+                    created_at: now,
+                    read: false,
+                });
+                while inbox.notifications.len() > MAX_NOTIFICATIONS {
+                    inbox.notifications.pop_front();
+                }
+                self.next_id += 1;
+                created += 1;
+            }
+        }
+
+        // Past occurrences can no longer come up again
+        for inbox in self.inboxes.values_mut() {
+            inbox.sent.retain(|&(_, _, due)| due >= today);
+        }
+        created
+    }
+}
+
+fn when(days_left: u32, due_date: &str) -> String {
+    match days_left {
+        0 => "today".to_string(),
+        1 => format!("tomorrow ({})", due_date),
+        days => format!("in {} days ({})", days, due_date),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obligations::Deadline;
+
+    fn rent_due(date: &str) -> Obligation {
+        Obligation {
+            obligor: "Tenant".to_string(),
+            action: "pay the rent".to_string(),
+            deadline: Some(Deadline { text: String::new(), due_date: Some(date.to_string()), recurrence: None, until: None }),
+            section: None,
+            page: None,
+            clause: String::new(),
+        }
+    }
+
+    #[test]
+    fn inboxes_survive_a_round_trip() {
+        let owner = Principal::from_slice(&[1; 29]);
+        let obligations = [rent_due("2025-03-05")];
+        let now = calendar::parse_iso_date("2025-03-01").unwrap() as u64 * calendar::NANOS_PER_DAY;
+        let mut reminders = Reminders::new();
+        reminders.set_lead_days(owner, 10).unwrap();
+        assert_eq!(reminders.scan([(owner, 1, &obligations[..])], now), 1);
+        reminders.mark_read(&owner, &[1]);
+
+        let bytes = serde_json::to_vec(&reminders).unwrap();
+        let mut restored: Reminders = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(restored.lead_days(&owner), 10);
+        let notifications = restored.list(&owner, false);
+        assert_eq!(notifications.len(), 1);
+        assert!(notifications[0].read);
+        // Occurrences already announced stay announced, and ids keep counting
+        assert_eq!(restored.scan([(owner, 1, &obligations[..])], now), 0);
+        assert_eq!(restored.scan([(owner, 2, &obligations[..])], now), 1);
+        assert_eq!(restored.list(&owner, true)[0].id, 2);
+    }
+}
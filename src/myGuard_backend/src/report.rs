@@ -0,0 +1,337 @@
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+
+use crate::calendar;
+use crate::obligations::Obligation;
+use crate::{ClauseAnalysis, ContractAnalysisResult, Severity};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+    Json,
+}
+
+pub const DISCLAIMER: &str = "This report was generated automatically by MyGuard for educational purposes. It is not legal advice; have important contracts reviewed by a qualified legal professional.";
+
+const MANIPULATION_WARNING: &str = "Some clauses contain wording addressed to an automated analyser rather than to the parties. They were classified without the language model; read them with particular care.";
+
+// Section number and, for PDFs, the page, e.g. "4.2, p. 3"
+fn location(section: Option<&str>, page: Option<u32>) -> String {
+    match (section, page) {
+        (Some(section), Some(page)) => format!("{}, p. {}", section, page),
+        (Some(section), None) => section.to_string(),
+        (None, Some(page)) => format!("p. {}", page),
+        (None, None) => "-".to_string(),
+    }
+}
+
+fn clause_location(clause: &ClauseAnalysis) -> String {
+    location(clause.section.as_deref(), clause.page)
+}
+
+fn obligation_location(obligation: &Obligation) -> String {
+    location(obligation.section.as_deref(), obligation.page)
+}
+
+// "2025-02-05 (monthly)" or the deadline as written when no date is known
+fn obligation_due(obligation: &Obligation) -> String {
+    let Some(deadline) = &obligation.deadline else { return "-".to_string() };
+    let mut due = deadline.due_date.clone().unwrap_or_else(|| deadline.text.clone());
+    if let Some(recurrence) = deadline.recurrence {
+        due.push_str(&format!(" ({})", recurrence.name()));
+    }
+    due
+}
+
+fn degraded_note(fallback_clauses: usize) -> String {
+    format!(
+        "The language model was unavailable, so {} clause(s) were classified by the local rules only.",
+        fallback_clauses
+    )
+}
+
+// Canonical JSON document: the stored analysis plus report metadata
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    report_version: u32,
+    analysis: &'a ContractAnalysisResult,
+    disclaimer: &'a str,
+}
+
+fn severity_name(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Low => "Low",
+        Severity::Medium => "Medium",
+        Severity::High => "High",
+        Severity::Critical => "Critical",
+    }
+}
+
+// Format nanoseconds since the Unix epoch as "YYYY-MM-DD HH:MM UTC"
+pub fn format_timestamp(nanos: u64) -> String {
+    let seconds = nanos / 1_000_000_000;
+    let days = (seconds / 86_400) as i64;
+    let minutes_of_day = (seconds % 86_400) / 60;
+    let (year, month, day) = calendar::civil_from_days(days);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        minutes_of_day / 60,
+        minutes_of_day % 60
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '|' | '#' | '<' | '>') {
+            escaped.push('\\');
+        }
+        escaped.push(if c == '\n' { ' ' } else { c });
+    }
+    escaped
+}
+
+pub fn render_report(result: &ContractAnalysisResult, format: ReportFormat, generated_at: u64) -> Result<String, String> {
+    match format {
+        ReportFormat::Markdown => Ok(render_markdown(result, generated_at)),
+        ReportFormat::Html => Ok(render_html(result, generated_at)),
+        ReportFormat::Json => render_json(result),
+    }
+}
+
+// The JSON report leaves out the generation time so the same analysis always renders identically
+fn render_json(result: &ContractAnalysisResult) -> Result<String, String> {
+    let report = JsonReport {
+        report_version: 1,
+        analysis: result,
+        disclaimer: DISCLAIMER,
+    };
+    // Going through `Value` sorts object keys, giving a stable byte-for-byte output
+    let value = serde_json::to_value(&report).map_err(|e| format!("Could not serialise report: {}", e))?;
+    serde_json::to_string_pretty(&value).map_err(|e| format!("Could not serialise report: {}", e))
+}
+
+fn render_markdown(result: &ContractAnalysisResult, generated_at: u64) -> String {
+    let mut out = String::new();
+
+    out.push_str(&format!("# MyGuard Contract Analysis #{}\n\n", result.analysis_id));
+    out.push_str(&format!(
+        "Analysed {} · report generated {}\n\n",
+        format_timestamp(result.analyzed_at),
+        format_timestamp(generated_at)
+    ));
+
+    out.push_str("## Summary\n\n");
+    out.push_str(&format!("- **Contract type:** {}\n", result.contract_type.name()));
+    out.push_str(&format!(
+        "- **Risk score:** {:.1} / 100 ({})\n",
+        result.risk_score,
+        severity_name(result.risk_level)
+    ));
+    out.push_str(&format!("- **Clauses analysed:** {}\n", result.total_clauses));
+    out.push_str(&format!(
+        "- **Dataset version:** {} · **Rule set:** {}\n",
+        result.dataset_version, result.rule_set_version
+    ));
+    out.push_str(&format!(
+        "- **Allowed:** {} ({:.1}%) · **Not allowed:** {} ({:.1}%) · **Unclassified:** {} ({:.1}%)\n\n",
+        result.allowed_clauses,
+        result.allowed_percentage,
+        result.not_allowed_clauses,
+        result.not_allowed_percentage,
+        result.unclassified_clauses,
+        result.unclassified_percentage
+    ));
+
+    if result.manipulation_detected {
+        out.push_str(&format!("> **Warning:** {}\n\n", MANIPULATION_WARNING));
+    }
+    if let Some(degraded) = &result.degraded_mode {
+        out.push_str(&format!("> **Note:** {}\n\n", degraded_note(degraded.fallback_clauses)));
+    }
+
+    out.push_str("## Flagged clauses\n\n");
+    let flagged: Vec<_> = result.clause_breakdown.iter()
+        .filter(|c| c.label == "Not Allowed")
+        .collect();
+    if flagged.is_empty() {
+        out.push_str("No clauses were flagged.\n\n");
+    } else {
+        out.push_str("| Section | Clause | Severity | Reason | Suggested wording |\n");
+        out.push_str("|---|---|---|---|---|\n");
+        for clause in flagged {
+            out.push_str(&format!(
+                "| {} | {} | {} | {} | {} |\n",
+                escape_markdown(&clause_location(clause)),
+                escape_markdown(&clause.clause),
+                severity_name(clause.severity),
+                escape_markdown(&clause.reason),
+                escape_markdown(clause.suggested_rewrite.as_deref().unwrap_or("-"))
+            ));
+        }
+        out.push('\n');
+    }
+
+    out.push_str("## Missing clauses\n\n");
+    if result.missing_clauses.is_empty() {
+        out.push_str("All expected provisions are present.\n\n");
+    } else {
+        for missing in &result.missing_clauses {
+            out.push_str(&format!(
+                "- **{}** ({}): {}\n",
+                escape_markdown(&missing.title),
+                severity_name(missing.severity),
+                escape_markdown(&missing.suggested_wording)
+            ));
+        }
+        out.push('\n');
+    }
+
+    out.push_str("## Obligations\n\n");
+    if result.obligations.is_empty() {
+        out.push_str("No obligations were found.\n\n");
+    } else {
+        out.push_str("| Section | Party | Obligation | Due |\n");
+        out.push_str("|---|---|---|---|\n");
+        for obligation in &result.obligations {
+            out.push_str(&format!(
+                "| {} | {} | {} | {} |\n",
+                escape_markdown(&obligation_location(obligation)),
+                escape_markdown(&obligation.obligor),
+                escape_markdown(&obligation.action),
+                escape_markdown(&obligation_due(obligation))
+            ));
+        }
+        out.push('\n');
+    }
+
+    out.push_str("---\n\n");
+    out.push_str(&format!("_{}_\n", DISCLAIMER));
+    out
+}
+
+const HTML_STYLE: &str = "body{font-family:Helvetica,Arial,sans-serif;max-width:960px;margin:2em auto;color:#222;line-height:1.5}\
+table{border-collapse:collapse;width:100%}th,td{border:1px solid #ccc;padding:6px;vertical-align:top;text-align:left}\
+th{background:#f3f3f3}.Critical{color:#8b0000;font-weight:bold}.High{color:#c0392b}.Medium{color:#d35400}.Low{color:#555}\
+.warning{background:#fff4e5;border-left:4px solid #d35400;padding:8px}\
+.disclaimer{font-size:0.85em;color:#666;border-top:1px solid #ccc;margin-top:2em;padding-top:1em}";
+
+fn render_html(result: &ContractAnalysisResult, generated_at: u64) -> String {
+    let mut out = String::new();
+
+    out.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str(&format!("<title>MyGuard Contract Analysis #{}</title>\n", result.analysis_id));
+    out.push_str(&format!("<style>{}</style>\n</head>\n<body>\n", HTML_STYLE));
+
+    out.push_str(&format!("<h1>MyGuard Contract Analysis #{}</h1>\n", result.analysis_id));
+    out.push_str(&format!(
+        "<p>Analysed {} &middot; report generated {}</p>\n",
+        format_timestamp(result.analyzed_at),
+        format_timestamp(generated_at)
+    ));
+
+    out.push_str("<h2>Summary</h2>\n<ul>\n");
+    out.push_str(&format!("<li><strong>Contract type:</strong> {}</li>\n", result.contract_type.name()));
+    out.push_str(&format!(
+        "<li><strong>Risk score:</strong> {:.1} / 100 (<span class=\"{}\">{}</span>)</li>\n",
+        result.risk_score,
+        severity_name(result.risk_level),
+        severity_name(result.risk_level)
+    ));
+    out.push_str(&format!("<li><strong>Clauses analysed:</strong> {}</li>\n", result.total_clauses));
+    out.push_str(&format!(
+        "<li><strong>Dataset version:</strong> {} &middot; <strong>Rule set:</strong> {}</li>\n",
+        result.dataset_version, result.rule_set_version
+    ));
+    out.push_str(&format!(
+        "<li><strong>Allowed:</strong> {} ({:.1}%), <strong>Not allowed:</strong> {} ({:.1}%), <strong>Unclassified:</strong> {} ({:.1}%)</li>\n</ul>\n",
+        result.allowed_clauses,
+        result.allowed_percentage,
+        result.not_allowed_clauses,
+        result.not_allowed_percentage,
+        result.unclassified_clauses,
+        result.unclassified_percentage
+    ));
+
+    if result.manipulation_detected {
+        out.push_str(&format!("<p class=\"warning\"><strong>Warning:</strong> {}</p>\n", escape_html(MANIPULATION_WARNING)));
+    }
+    if let Some(degraded) = &result.degraded_mode {
+        out.push_str(&format!("<p class=\"warning\"><strong>Note:</strong> {}</p>\n", escape_html(&degraded_note(degraded.fallback_clauses))));
+    }
+
+    out.push_str("<h2>Flagged clauses</h2>\n");
+    let flagged: Vec<_> = result.clause_breakdown.iter()
+        .filter(|c| c.label == "Not Allowed")
+        .collect();
+    if flagged.is_empty() {
+        out.push_str("<p>No clauses were flagged.</p>\n");
+    } else {
+        out.push_str("<table>\n<tr><th>Section</th><th>Clause</th><th>Severity</th><th>Reason</th><th>Suggested wording</th></tr>\n");
+        for clause in flagged {
+            let severity = severity_name(clause.severity);
+            out.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td class=\"{}\">{}</td><td>{}</td><td>{}</td></tr>\n",
+                escape_html(&clause_location(clause)),
+                escape_html(&clause.clause),
+                severity,
+                severity,
+                escape_html(&clause.reason),
+                escape_html(clause.suggested_rewrite.as_deref().unwrap_or("-"))
+            ));
+        }
+        out.push_str("</table>\n");
+    }
+
+    out.push_str("<h2>Missing clauses</h2>\n");
+    if result.missing_clauses.is_empty() {
+        out.push_str("<p>All expected provisions are present.</p>\n");
+    } else {
+        out.push_str("<ul>\n");
+        for missing in &result.missing_clauses {
+            let severity = severity_name(missing.severity);
+            out.push_str(&format!(
+                "<li><strong>{}</strong> (<span class=\"{}\">{}</span>): {}</li>\n",
+                escape_html(&missing.title),
+                severity,
+                severity,
+                escape_html(&missing.suggested_wording)
+            ));
+        }
+        out.push_str("</ul>\n");
+    }
+
+    out.push_str("<h2>Obligations</h2>\n");
+    if result.obligations.is_empty() {
+        out.push_str("<p>No obligations were found.</p>\n");
+    } else {
+        out.push_str("<table>\n<tr><th>Section</th><th>Party</th><th>Obligation</th><th>Due</th></tr>\n");
+        for obligation in &result.obligations {
+            out.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                escape_html(&obligation_location(obligation)),
+                escape_html(&obligation.obligor),
+                escape_html(&obligation.action),
+                escape_html(&obligation_due(obligation))
+            ));
+        }
+        out.push_str("</table>\n");
+    }
+
+    out.push_str(&format!("<p class=\"disclaimer\">{}</p>\n", escape_html(DISCLAIMER)));
+    out.push_str("</body>\n</html>\n");
+    out
+}
@@ -0,0 +1,141 @@
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+
+use ic_llm::ChatMessage;
+
+use crate::category::ClauseCategory;
+use crate::contract_type::{AnalysisProfile, ContractType};
+use crate::injection;
+use crate::{text_similarity, ContractRecord};
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct RewriteSuggestion {
+    pub original: String,
+    pub suggestion: String,
+    pub category: ClauseCategory,
+    pub contract_type: ContractType,
+    // "Allowed" dataset clauses the suggestion was modelled on
+    pub exemplars: Vec<String>,
+    // Label the classifier gives the suggestion
+    pub validated_label: String,
+    pub validation_confidence: f64,
+    pub validated: bool,
+}
+
+// Number of exemplars shown to the LLM
+pub const MAX_EXEMPLARS: usize = 3;
+
+// Exemplars less similar than this are about something else; the same bar
+// the similarity analyzer sets for a matching dataset clause
+pub const MIN_EXEMPLAR_SIMILARITY: f64 = 0.5;
+
+// "Allowed" records of the clause's category and contract type that are close
+// to it, most similar first
+pub fn allowed_exemplar_records<'a>(
+    clause: &str,
+    category: ClauseCategory,
+    contract_type: ContractType,
+    dataset: &'a [ContractRecord],
+    limit: usize,
+) -> Vec<&'a ContractRecord> {
+    let mut candidates: Vec<(f64, &ContractRecord)> = dataset.iter()
+        .filter(|record| record.label == "Allowed")
+        .filter(|record| record.effective_category() == category)
+        .filter(|record| record.matches_type(contract_type))
+        .map(|record| (text_similarity(clause, &record.contract_text), record))
+        .filter(|(similarity, _)| *similarity >= MIN_EXEMPLAR_SIMILARITY)
+        .collect();
+
+    candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+    candidates.into_iter()
+        .take(limit)
+        .map(|(_, record)| record)
+        .collect()
+}
+
+pub fn allowed_exemplars(
+    clause: &str,
+    category: ClauseCategory,
+    contract_type: ContractType,
+    dataset: &[ContractRecord],
+    limit: usize,
+) -> Vec<String> {
+    allowed_exemplar_records(clause, category, contract_type, dataset, limit)
+        .into_iter()
+        .map(|record| record.contract_text.clone())
+        .collect()
+}
+
+pub fn rewrite_messages(clause: &str, context: &str, profile: &AnalysisProfile, exemplars: &[String]) -> Vec<ChatMessage> {
+    let mut instructions = format!(
+        "{} Rewrite the contract clause given by the user so that it is fair and balanced for both parties and conforms to Rules and Regulation in Rwanda. Keep the same subject matter and respond ONLY with the rewritten clause as a single sentence.\n",
+        profile.prompt_context
+    );
+
+    if !exemplars.is_empty() {
+        instructions.push_str("Examples of acceptable clauses on the same subject:\n");
+        for exemplar in exemplars {
+            instructions.push_str(&format!("- {}\n", exemplar));
+        }
+    }
+
+    let excerpt: String = context.chars().take(1500).collect();
+    let mut untrusted = Vec::new();
+    if !excerpt.trim().is_empty() {
+        untrusted.push(("Surrounding contract text", excerpt.as_str()));
+    }
+    untrusted.push(("Clause to rewrite", clause));
+
+    injection::guarded_messages(instructions.trim_end(), &untrusted)
+}
+
+// Strip labels and quotes the model tends to wrap around its answer
+pub fn clean_suggestion(response: &str) -> String {
+    let first_line = response.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or("");
+
+    let without_label = match first_line.split_once(':') {
+        Some((label, rest)) if label.to_lowercase().contains("clause") || label.to_lowercase().contains("rewrite") => rest,
+        _ => first_line,
+    };
+
+    without_label
+        .trim()
+        .trim_matches(|c| c == '"' || c == '\'' || c == '“' || c == '”')
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{contract_type, exemplar_rewrite, parse_contract_dataset, CONTRACT_DATASET, CSV_DATA};
+
+    fn load_dataset() {
+        CONTRACT_DATASET.with(|dataset| *dataset.borrow_mut() = parse_contract_dataset(CSV_DATA).records);
+    }
+
+    #[test]
+    fn close_exemplars_are_offered() {
+        load_dataset();
+        let profile = contract_type::profile_for(ContractType::Employment);
+        let (suggestion, label, _) = exemplar_rewrite("The employee is not entitled to any paid overtime.", profile).unwrap();
+        assert_eq!(suggestion, "The employee is entitled to paid leave.");
+        assert_ne!(label, "Not Allowed");
+    }
+
+    #[test]
+    fn unrelated_exemplars_are_not_offered() {
+        load_dataset();
+        let profile = contract_type::profile_for(ContractType::Lease);
+        let clause = "The landlord can demand additional fees without prior notice.";
+        assert_eq!(exemplar_rewrite(clause, profile), None);
+        let category = crate::category::categorize_clause(clause);
+        CONTRACT_DATASET.with(|dataset| {
+            assert!(allowed_exemplars(clause, category, ContractType::Lease, &dataset.borrow(), MAX_EXEMPLARS).is_empty());
+        });
+    }
+}
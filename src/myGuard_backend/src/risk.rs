@@ -0,0 +1,188 @@
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+
+use crate::category::ClauseCategory;
+use crate::checklist::MissingClause;
+use crate::{ClauseAnalysis, Severity};
+
+// Risk figures for all clauses of one category
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct CategoryRisk {
+    pub category: ClauseCategory,
+    pub clauses: usize,
+    pub allowed_clauses: usize,
+    pub not_allowed_clauses: usize,
+    pub unclassified_clauses: usize,
+    pub highest_severity: Severity,
+    pub risk_score: f64,
+}
+
+// Phrases that make an unfair clause critical whatever its subject
+const CRITICAL_PATTERNS: &[&str] = &[
+    "unlimited",
+    "waive all rights",
+    "waive any right",
+    "indefinite",
+    "without any reason",
+    "injur",
+    "forced",
+    "force employees",
+    "personal assets",
+    "private conversations",
+    "no breaks",
+    "no rest",
+];
+
+// Phrases that make a clause one level more severe than its subject suggests
+const ESCALATING_PATTERNS: &[&str] = &[
+    "at any time",
+    "without notice",
+    "without consent",
+    "without permission",
+    "immediate",
+    "arbitrarily",
+    "sole discretion",
+];
+
+// Impact of a clause's subject if the clause turns out to be unfair
+fn category_severity(category: ClauseCategory) -> Severity {
+    match category {
+        ClauseCategory::Liability
+        | ClauseCategory::Termination
+        | ClauseCategory::Compensation
+        | ClauseCategory::Privacy
+        | ClauseCategory::NonCompete => Severity::High,
+        ClauseCategory::Payment
+        | ClauseCategory::Deposit
+        | ClauseCategory::Access
+        | ClauseCategory::Maintenance
+        | ClauseCategory::WorkingConditions
+        | ClauseCategory::Leave
+        | ClauseCategory::Confidentiality
+        | ClauseCategory::Delivery
+        | ClauseCategory::Quality
+        | ClauseCategory::DisputeResolution
+        | ClauseCategory::Modification => Severity::Medium,
+        ClauseCategory::Other => Severity::Low,
+    }
+}
+
+fn escalate(severity: Severity) -> Severity {
+    match severity {
+        Severity::Low => Severity::Medium,
+        Severity::Medium => Severity::High,
+        Severity::High | Severity::Critical => Severity::Critical,
+    }
+}
+
+// Severity of a clause: the impact it would have if unfair, from its category
+// and wording. Headings and fragments of fewer than three words are always low.
+pub fn assess_severity(clause: &str, category: ClauseCategory) -> Severity {
+    if clause.split_whitespace().count() < 3 {
+        return Severity::Low;
+    }
+
+    let lower_clause = clause.to_lowercase();
+    if CRITICAL_PATTERNS.iter().any(|p| lower_clause.contains(p)) {
+        return Severity::Critical;
+    }
+
+    let base = category_severity(category);
+    if ESCALATING_PATTERNS.iter().any(|p| lower_clause.contains(p)) {
+        escalate(base)
+    } else {
+        base
+    }
+}
+
+pub fn severity_weight(severity: Severity) -> f64 {
+    match severity {
+        Severity::Low => 1.0,
+        Severity::Medium => 2.0,
+        Severity::High => 4.0,
+        Severity::Critical => 8.0,
+    }
+}
+
+// Share of a clause's weight that counts as risk, by label
+pub fn label_risk(label: &str) -> f64 {
+    match label {
+        "Not Allowed" => 1.0,
+        "Allowed" => 0.0,
+        // Unclassified and Neutral clauses could not be cleared
+        _ => 0.25,
+    }
+}
+
+// Weighted document risk score from 0 (no risk) to 100 (everything risky):
+//
+//   score = 100 * sum(w(s_i) * r(l_i)) / sum(w(s_i))
+//
+// summed over every analysed clause i and every missing expected provision, where
+//   w(Low) = 1, w(Medium) = 2, w(High) = 4, w(Critical) = 8
+//   r(Not Allowed) = 1, r(Unclassified or Neutral) = 0.25, r(Allowed) = 0
+// and a missing provision counts as one unfair item of its checklist severity.
+pub fn weighted_risk_score(clauses: &[ClauseAnalysis], missing_clauses: &[MissingClause]) -> f64 {
+    let mut weighted_risk = 0.0;
+    let mut total_weight = 0.0;
+
+    for clause in clauses {
+        let weight = severity_weight(clause.severity);
+        weighted_risk += weight * label_risk(&clause.label);
+        total_weight += weight;
+    }
+
+    for missing in missing_clauses {
+        let weight = severity_weight(missing.severity);
+        weighted_risk += weight;
+        total_weight += weight;
+    }
+
+    if total_weight > 0.0 {
+        100.0 * weighted_risk / total_weight
+    } else {
+        0.0
+    }
+}
+
+// Overall level for a document risk score
+pub fn risk_level(score: f64) -> Severity {
+    if score >= 60.0 {
+        Severity::Critical
+    } else if score >= 35.0 {
+        Severity::High
+    } else if score >= 15.0 {
+        Severity::Medium
+    } else {
+        Severity::Low
+    }
+}
+
+// Per-category counts and weighted scores, in category order
+pub fn category_breakdown(clauses: &[ClauseAnalysis]) -> Vec<CategoryRisk> {
+    let mut categories: Vec<ClauseCategory> = clauses.iter().map(|c| c.category).collect();
+    categories.sort();
+    categories.dedup();
+
+    categories.into_iter()
+        .map(|category| {
+            let members: Vec<ClauseAnalysis> = clauses.iter()
+                .filter(|c| c.category == category)
+                .cloned()
+                .collect();
+            let count = |label: &str| members.iter().filter(|c| c.label == label).count();
+            let allowed_clauses = count("Allowed");
+            let not_allowed_clauses = count("Not Allowed");
+
+            CategoryRisk {
+                category,
+                clauses: members.len(),
+                allowed_clauses,
+                not_allowed_clauses,
+                unclassified_clauses: members.len() - allowed_clauses - not_allowed_clauses,
+                highest_severity: members.iter().map(|c| c.severity).max().unwrap_or(Severity::Low),
+                risk_score: weighted_risk_score(&members, &[]),
+            }
+        })
+        .collect()
+}
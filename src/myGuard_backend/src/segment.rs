@@ -0,0 +1,163 @@
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+// A clause of the contract together with its section number and position
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment<'a> {
+    pub section: Option<String>,
+    pub text: &'a str,
+    // Byte offsets of `text` in the original contract
+    pub start: usize,
+    pub end: usize,
+}
+
+// Leading section markers: "Section 3", "1.", "3)", "4.2", "(a)". A bare
+// number needs a trailing "." or ")" so that a wrapped line such as "60 days
+// notice ..." is not taken for a heading.
+fn section_marker_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^\s*(?:(?i:section|article|clause)\s+(\d{1,3}(?:\.\d{1,3})*)[.):]?|(\d{1,3}(?:\.\d{1,3})*)[.)]|(\d{1,3}(?:\.\d{1,3})+)|\(([a-z]|[ivx]+)\))(?:\s+|$)")
+            .expect("invalid section marker pattern")
+    })
+}
+
+enum SectionMarker<'a> {
+    Number(&'a str),
+    Letter(&'a str),
+}
+
+// The section marker a line opens with and the offset of the text after it
+fn section_marker(line: &str) -> Option<(SectionMarker<'_>, usize)> {
+    let caps = section_marker_regex().captures(line)?;
+    let end = caps.get(0)?.end();
+    if let Some(number) = caps.get(1).or(caps.get(2)) {
+        return Some((SectionMarker::Number(number.as_str()), end));
+    }
+    if let Some(number) = caps.get(3) {
+        // "4.2 Rent" is a heading, "1.5 million" a wrapped amount
+        let heading = line[end..].chars().next().is_none_or(|c| c.is_uppercase() || c == '(');
+        return heading.then_some((SectionMarker::Number(number.as_str()), end));
+    }
+    caps.get(4).map(|letter| (SectionMarker::Letter(letter.as_str()), end))
+}
+
+// Whether a line opens with a section number such as "4.2" or "(a)"
+pub fn starts_with_section_marker(line: &str) -> bool {
+    section_marker(line).is_some()
+}
+
+// Split a line into sentences at full stops that end a sentence, leaving
+// decimals such as "1,200.50" intact
+fn sentence_ranges(line: &str) -> Vec<(usize, usize)> {
+    let bytes = line.as_bytes();
+    let mut ranges = Vec::new();
+    let mut start = 0;
+
+    for (i, &b) in bytes.iter().enumerate() {
+        if b != b'.' {
+            continue;
+        }
+        let next_is_digit = bytes.get(i + 1).is_some_and(|c| c.is_ascii_digit());
+        let prev_is_digit = i > 0 && bytes[i - 1].is_ascii_digit();
+        if prev_is_digit && next_is_digit {
+            continue;
+        }
+        ranges.push((start, i));
+        start = i + 1;
+    }
+    ranges.push((start, line.len()));
+    ranges
+}
+
+fn child_section(parent: &Option<String>, marker: &str) -> String {
+    match parent {
+        // Lettered items belong to the enclosing numbered section, e.g. "4.2(a)"
+        Some(parent) => format!("{}({})", parent.split('(').next().unwrap_or(parent), marker),
+        None => format!("({})", marker),
+    }
+}
+
+// Segment contract text into clauses. Lines are split into sentences and each
+// sentence inherits the section number of the closest preceding heading.
+pub fn segment_clauses(text: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut current_section: Option<String> = None;
+    let mut line_start = 0;
+
+    for line in text.split_inclusive('\n') {
+        let line_offset = line_start;
+        line_start += line.len();
+        let line = line.trim_end_matches(['\n', '\r']);
+
+        let mut body_offset = 0;
+        if let Some((marker, end)) = section_marker(line) {
+            current_section = Some(match marker {
+                SectionMarker::Number(number) => number.to_string(),
+                SectionMarker::Letter(letter) => child_section(&current_section, letter),
+            });
+            body_offset = end;
+        }
+
+        let body = &line[body_offset..];
+        for (start, end) in sentence_ranges(body) {
+            let sentence = &body[start..end];
+            let trimmed = sentence.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            let leading = sentence.len() - sentence.trim_start().len();
+            let absolute_start = line_offset + body_offset + start + leading;
+            segments.push(Segment {
+                section: current_section.clone(),
+                text: trimmed,
+                start: absolute_start,
+                end: absolute_start + trimmed.len(),
+            });
+        }
+    }
+
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sections(text: &str) -> Vec<(Option<String>, &str)> {
+        segment_clauses(text).into_iter().map(|segment| (segment.section, segment.text)).collect()
+    }
+
+    #[test]
+    fn recognises_section_markers() {
+        for (line, section) in [
+            ("1. Rent is due monthly", "1"),
+            ("4.2 Rent is due monthly", "4.2"),
+            ("4.2. Rent is due monthly", "4.2"),
+            ("3) Rent is due monthly", "3"),
+            ("Section 5 Rent is due monthly", "5"),
+            ("Article 12. Rent is due monthly", "12"),
+            ("clause 7: Rent is due monthly", "7"),
+        ] {
+            assert_eq!(sections(line), vec![(Some(section.to_string()), "Rent is due monthly")], "{}", line);
+        }
+        assert_eq!(sections("2. Payment\n(a) Rent is due monthly"), vec![
+            (Some("2".to_string()), "Payment"),
+            (Some("2(a)".to_string()), "Rent is due monthly"),
+        ]);
+    }
+
+    #[test]
+    fn wrapped_numeric_lines_are_not_sections() {
+        let text = "7. Renewal\nThe Tenant may renew by giving\n60 days notice before renewal of the lease\n1.5 million francs are payable on signing\n2025 rates apply";
+        let segments = sections(text);
+        assert!(segments.iter().all(|(section, _)| section.as_deref() == Some("7")), "{:?}", segments);
+        assert!(segments.iter().any(|(_, text)| *text == "60 days notice before renewal of the lease"), "{:?}", segments);
+        assert!(segments.iter().any(|(_, text)| *text == "1.5 million francs are payable on signing"), "{:?}", segments);
+
+        for line in ["60 days notice before renewal", "30 days after termination", "1.5 million", "2025. The lease", "12 months"] {
+            assert!(!starts_with_section_marker(line), "{}", line);
+        }
+    }
+}
@@ -0,0 +1,714 @@
+use std::collections::BTreeMap;
+
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+
+use crate::category::ClauseCategory;
+use crate::contract_type::ContractType;
+use crate::ContractAnalysisResult;
+
+// Longest accepted parameter value, in characters
+const MAX_PARAMETER_CHARS: usize = 200;
+
+// A vetted clause that is fair to both parties under Rwandan law. `text` may
+// contain {{placeholders}} filled from template parameters.
+pub struct LibraryClause {
+    pub id: &'static str,
+    pub title: &'static str,
+    pub category: ClauseCategory,
+    // Contract types the clause is written for; empty for every type
+    pub contract_types: &'static [ContractType],
+    pub text: &'static str,
+}
+
+pub struct TemplateParameter {
+    pub name: &'static str,
+    pub description: &'static str,
+    // Used when the caller leaves the parameter out; None makes it required
+    pub default: Option<&'static str>,
+    // Only digits and separators, e.g. "300,000"
+    pub numeric: bool,
+    // Inclusive bounds on numeric values where the law or fairness sets one,
+    // e.g. at most 45 working hours a week
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+}
+
+pub struct ContractTemplate {
+    pub id: &'static str,
+    pub title: &'static str,
+    pub contract_type: ContractType,
+    pub preamble: &'static str,
+    pub parameters: &'static [TemplateParameter],
+    // Library clause ids, in the order they appear
+    pub clauses: &'static [&'static str],
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct LibraryClauseInfo {
+    pub id: String,
+    pub title: String,
+    pub category: ClauseCategory,
+    pub contract_types: Vec<ContractType>,
+    pub text: String,
+    pub placeholders: Vec<String>,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct TemplateParameterInfo {
+    pub name: String,
+    pub description: String,
+    pub required: bool,
+    pub default: Option<String>,
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct TemplateInfo {
+    pub id: String,
+    pub title: String,
+    pub contract_type: ContractType,
+    pub parameters: Vec<TemplateParameterInfo>,
+    pub clause_ids: Vec<String>,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct GeneratedContract {
+    pub template_id: String,
+    pub contract_text: String,
+    // The generated terms, without the title, as analysed by `analyze_contract`
+    pub analysis: ContractAnalysisResult,
+}
+
+const fn required(name: &'static str, description: &'static str, numeric: bool) -> TemplateParameter {
+    TemplateParameter { name, description, default: None, numeric, min: None, max: None }
+}
+
+const fn optional(name: &'static str, description: &'static str, default: &'static str, numeric: bool) -> TemplateParameter {
+    TemplateParameter { name, description, default: Some(default), numeric, min: None, max: None }
+}
+
+impl TemplateParameter {
+    const fn at_least(self, min: u64) -> Self {
+        TemplateParameter { min: Some(min), ..self }
+    }
+
+    const fn at_most(self, max: u64) -> Self {
+        TemplateParameter { max: Some(max), ..self }
+    }
+}
+
+pub const LIBRARY: &[LibraryClause] = &[
+    LibraryClause {
+        id: "amendments",
+        title: "Amendments",
+        category: ClauseCategory::Modification,
+        contract_types: &[],
+        text: "This agreement may only be changed by a written amendment signed by both parties.",
+    },
+    LibraryClause {
+        id: "dispute_resolution",
+        title: "Disputes",
+        category: ClauseCategory::DisputeResolution,
+        contract_types: &[],
+        text: "Any dispute arising out of this agreement shall first be settled amicably between the parties. If no settlement is reached within 30 days, either party may refer the dispute to the competent courts of Rwanda or, if both parties agree, to arbitration at the Kigali International Arbitration Centre.",
+    },
+    LibraryClause {
+        id: "governing_law",
+        title: "Governing law",
+        category: ClauseCategory::DisputeResolution,
+        contract_types: &[],
+        text: "This agreement shall be governed by and construed in accordance with the laws of the Republic of Rwanda.",
+    },
+    LibraryClause {
+        id: "mutual_confidentiality",
+        title: "Confidentiality",
+        category: ClauseCategory::Confidentiality,
+        contract_types: &[ContractType::Supply, ContractType::Service],
+        text: "Each party shall keep confidential the non-public business information it receives from the other party under this agreement, except where disclosure is required by law.",
+    },
+    LibraryClause {
+        id: "force_majeure",
+        title: "Force majeure",
+        category: ClauseCategory::Liability,
+        contract_types: &[ContractType::Supply, ContractType::Service],
+        text: "Neither party is liable for failure to perform caused by events beyond its reasonable control, provided it notifies the other party promptly.",
+    },
+    LibraryClause {
+        id: "lease_term",
+        title: "Term",
+        category: ClauseCategory::Other,
+        contract_types: &[ContractType::Lease],
+        text: "This lease commences on {{start_date}} and continues for a term of {{term_months}} months.",
+    },
+    LibraryClause {
+        id: "lease_rent",
+        title: "Rent",
+        category: ClauseCategory::Payment,
+        contract_types: &[ContractType::Lease],
+        text: "The Tenant shall pay a monthly rent of {{monthly_rent}} RWF, payable in advance by the {{rent_due_day}} of each month.",
+    },
+    LibraryClause {
+        id: "lease_deposit",
+        title: "Security deposit",
+        category: ClauseCategory::Deposit,
+        contract_types: &[ContractType::Lease],
+        text: "The Tenant shall pay a security deposit of {{deposit}} RWF on signing this lease. The Landlord shall return the deposit within 30 days of the end of the lease, less only documented deductions for damage beyond normal wear and tear.",
+    },
+    LibraryClause {
+        id: "lease_maintenance",
+        title: "Repairs",
+        category: ClauseCategory::Maintenance,
+        contract_types: &[ContractType::Lease],
+        text: "The Landlord is responsible for structural repairs and major systems, and the Tenant is responsible for minor repairs caused by their own use of the premises.",
+    },
+    LibraryClause {
+        id: "lease_quiet_enjoyment",
+        title: "Quiet enjoyment",
+        category: ClauseCategory::Access,
+        contract_types: &[ContractType::Lease],
+        text: "The Tenant has the right to quiet enjoyment of the premises during the term.",
+    },
+    LibraryClause {
+        id: "lease_access",
+        title: "Access",
+        category: ClauseCategory::Access,
+        contract_types: &[ContractType::Lease],
+        text: "The Landlord may enter the premises for inspection or repairs only after giving the Tenant at least 24 hours notice, except in an emergency.",
+    },
+    LibraryClause {
+        id: "lease_termination",
+        title: "Termination",
+        category: ClauseCategory::Termination,
+        contract_types: &[ContractType::Lease],
+        text: "Either party may terminate this lease by giving the other party at least {{notice_days}} days written notice.",
+    },
+    LibraryClause {
+        id: "employment_position",
+        title: "Position",
+        category: ClauseCategory::WorkingConditions,
+        contract_types: &[ContractType::Employment],
+        text: "The Employee is employed as {{job_title}} from {{start_date}} and shall perform the duties described in the attached job description.",
+    },
+    LibraryClause {
+        id: "employment_salary",
+        title: "Salary",
+        category: ClauseCategory::Compensation,
+        contract_types: &[ContractType::Employment],
+        text: "The Employer shall pay the Employee a gross monthly salary of {{monthly_salary}} RWF, paid no later than the last working day of each month.",
+    },
+    LibraryClause {
+        id: "employment_hours",
+        title: "Working hours",
+        category: ClauseCategory::WorkingConditions,
+        contract_types: &[ContractType::Employment],
+        text: "Normal working hours shall not exceed {{weekly_hours}} hours per week, and overtime shall be compensated in accordance with the labour law.",
+    },
+    LibraryClause {
+        id: "employment_annual_leave",
+        title: "Annual leave",
+        category: ClauseCategory::Leave,
+        contract_types: &[ContractType::Employment],
+        text: "The Employee is entitled to paid annual leave of {{annual_leave_days}} working days per year, in addition to public holidays.",
+    },
+    LibraryClause {
+        id: "employment_sick_leave",
+        title: "Sick leave",
+        category: ClauseCategory::Leave,
+        contract_types: &[ContractType::Employment],
+        text: "The Employee is entitled to paid sick leave on presentation of a medical certificate, in accordance with the labour law.",
+    },
+    LibraryClause {
+        id: "employment_confidentiality",
+        title: "Confidentiality",
+        category: ClauseCategory::Confidentiality,
+        contract_types: &[ContractType::Employment],
+        text: "During and after the employment, the Employee shall keep confidential the non-public business information of the Employer, except where disclosure is required by law.",
+    },
+    LibraryClause {
+        id: "employment_termination",
+        title: "Termination",
+        category: ClauseCategory::Termination,
+        contract_types: &[ContractType::Employment],
+        text: "Either party may terminate this contract by giving at least {{notice_days}} days written notice, except in cases of serious misconduct as defined by law.",
+    },
+    LibraryClause {
+        id: "service_scope",
+        title: "Services",
+        category: ClauseCategory::Quality,
+        contract_types: &[ContractType::Service],
+        text: "The Contractor shall provide the following services: {{services}}, together with the deliverables described in any attached statement of work.",
+    },
+    LibraryClause {
+        id: "service_fees",
+        title: "Fees",
+        category: ClauseCategory::Payment,
+        contract_types: &[ContractType::Service],
+        text: "The Client shall pay fees of {{fee}} RWF for the services. Each correct invoice is payable within {{payment_days}} days of receipt.",
+    },
+    LibraryClause {
+        id: "service_ownership",
+        title: "Ownership of work product",
+        category: ClauseCategory::Other,
+        contract_types: &[ContractType::Service],
+        text: "Upon full payment, ownership of the deliverables created specifically for the Client transfers to the Client.",
+    },
+    LibraryClause {
+        id: "service_liability",
+        title: "Liability",
+        category: ClauseCategory::Liability,
+        contract_types: &[ContractType::Service],
+        text: "Each party is liable for direct losses caused by its own breach of this agreement, up to the total fees paid under it, except in cases of fraud or gross negligence.",
+    },
+    LibraryClause {
+        id: "service_termination",
+        title: "Termination",
+        category: ClauseCategory::Termination,
+        contract_types: &[ContractType::Service],
+        text: "Either party may terminate this agreement by giving {{notice_days}} days written notice, and the Client shall pay for services performed up to the termination date.",
+    },
+    LibraryClause {
+        id: "supply_delivery",
+        title: "Delivery",
+        category: ClauseCategory::Delivery,
+        contract_types: &[ContractType::Supply],
+        text: "The Supplier shall deliver {{goods}} to {{delivery_place}} within {{delivery_days}} days of each purchase order and shall notify the Buyer promptly of any expected delay.",
+    },
+    LibraryClause {
+        id: "supply_quality",
+        title: "Quality",
+        category: ClauseCategory::Quality,
+        contract_types: &[ContractType::Supply],
+        text: "The Supplier warrants that the goods conform to the agreed specifications and shall replace or refund defective goods reported within {{defect_days}} days of delivery.",
+    },
+    LibraryClause {
+        id: "supply_payment",
+        title: "Payment",
+        category: ClauseCategory::Payment,
+        contract_types: &[ContractType::Supply],
+        text: "The Buyer shall pay the agreed price for each delivery within {{payment_days}} days of receiving a correct invoice.",
+    },
+    LibraryClause {
+        id: "supply_termination",
+        title: "Termination",
+        category: ClauseCategory::Termination,
+        contract_types: &[ContractType::Supply],
+        text: "Either party may terminate this agreement by giving {{notice_days}} days written notice. Orders placed before the notice remain binding.",
+    },
+    LibraryClause {
+        id: "nda_definition",
+        title: "Confidential Information",
+        category: ClauseCategory::Confidentiality,
+        contract_types: &[ContractType::NonDisclosure],
+        text: "\"Confidential Information\" means non-public information disclosed by the Disclosing Party for the purpose of {{purpose}} that is marked as confidential or would reasonably be understood to be confidential.",
+    },
+    LibraryClause {
+        id: "nda_exclusions",
+        title: "Exclusions",
+        category: ClauseCategory::Confidentiality,
+        contract_types: &[ContractType::NonDisclosure],
+        text: "Confidential Information does not include information that is publicly available, already known to the Receiving Party, or independently developed without use of the Confidential Information.",
+    },
+    LibraryClause {
+        id: "nda_use",
+        title: "Use of information",
+        category: ClauseCategory::Confidentiality,
+        contract_types: &[ContractType::NonDisclosure],
+        text: "The Receiving Party shall use the Confidential Information only for the purpose of {{purpose}} and shall share it only with employees and advisers who need it for that purpose and are bound by equivalent obligations.",
+    },
+    LibraryClause {
+        id: "nda_duration",
+        title: "Duration",
+        category: ClauseCategory::Confidentiality,
+        contract_types: &[ContractType::NonDisclosure],
+        text: "The obligations of confidentiality shall continue for {{confidentiality_years}} years from the date of disclosure.",
+    },
+    LibraryClause {
+        id: "nda_return",
+        title: "Return of information",
+        category: ClauseCategory::Confidentiality,
+        contract_types: &[ContractType::NonDisclosure],
+        text: "On request or at the end of this agreement, the Receiving Party shall return or destroy all Confidential Information in its possession.",
+    },
+];
+
+pub const TEMPLATES: &[ContractTemplate] = &[
+    ContractTemplate {
+        id: "residential_lease",
+        title: "Residential Lease Agreement",
+        contract_type: ContractType::Lease,
+        preamble: "This lease is made between {{landlord}} (the \"Landlord\") and {{tenant}} (the \"Tenant\") for the premises at {{property_address}}.",
+        parameters: &[
+            required("landlord", "Full name of the landlord", false),
+            required("tenant", "Full name of the tenant", false),
+            required("property_address", "Address of the rented premises", false),
+            required("start_date", "First day of the lease, e.g. 1 January 2025", false),
+            optional("term_months", "Length of the lease in months", "12", true).at_least(1),
+            required("monthly_rent", "Monthly rent in RWF, e.g. 300,000", true),
+            optional("rent_due_day", "Day of the month rent is due by", "5th", false),
+            required("deposit", "Security deposit in RWF", true),
+            optional("notice_days", "Days of written notice to end the lease, at least 30", "30", true).at_least(30),
+        ],
+        clauses: &[
+            "lease_term",
+            "lease_rent",
+            "lease_deposit",
+            "lease_maintenance",
+            "lease_quiet_enjoyment",
+            "lease_access",
+            "lease_termination",
+            "amendments",
+            "dispute_resolution",
+            "governing_law",
+        ],
+    },
+    ContractTemplate {
+        id: "employment_contract",
+        title: "Employment Contract",
+        contract_type: ContractType::Employment,
+        preamble: "This contract is made between {{employer}} (the \"Employer\") and {{employee}} (the \"Employee\").",
+        parameters: &[
+            required("employer", "Name of the employer", false),
+            required("employee", "Full name of the employee", false),
+            required("job_title", "Position held, e.g. Accountant", false),
+            required("start_date", "First day of employment, e.g. 1 March 2025", false),
+            required("monthly_salary", "Gross monthly salary in RWF", true),
+            optional("weekly_hours", "Normal working hours per week, at most 45", "40", true).at_least(1).at_most(45),
+            optional("annual_leave_days", "Paid annual leave in working days, at least 18", "18", true).at_least(18),
+            optional("notice_days", "Days of written notice to end the contract, at least 15", "30", true).at_least(15),
+        ],
+        clauses: &[
+            "employment_position",
+            "employment_salary",
+            "employment_hours",
+            "employment_annual_leave",
+            "employment_sick_leave",
+            "employment_confidentiality",
+            "employment_termination",
+            "amendments",
+            "dispute_resolution",
+            "governing_law",
+        ],
+    },
+    ContractTemplate {
+        id: "service_agreement",
+        title: "Service Agreement",
+        contract_type: ContractType::Service,
+        preamble: "This agreement is made between {{client}} (the \"Client\") and {{contractor}} (the \"Contractor\").",
+        parameters: &[
+            required("client", "Name of the client", false),
+            required("contractor", "Name of the contractor", false),
+            required("services", "Short description of the services", false),
+            required("fee", "Total fees in RWF", true),
+            optional("payment_days", "Days the client has to pay an invoice, at most 60", "30", true).at_most(60),
+            optional("notice_days", "Days of written notice to end the agreement, at least 14", "30", true).at_least(14),
+        ],
+        clauses: &[
+            "service_scope",
+            "service_fees",
+            "service_ownership",
+            "service_liability",
+            "mutual_confidentiality",
+            "force_majeure",
+            "service_termination",
+            "amendments",
+            "dispute_resolution",
+            "governing_law",
+        ],
+    },
+    ContractTemplate {
+        id: "supply_agreement",
+        title: "Supply Agreement",
+        contract_type: ContractType::Supply,
+        preamble: "This agreement is made between {{buyer}} (the \"Buyer\") and {{supplier}} (the \"Supplier\").",
+        parameters: &[
+            required("buyer", "Name of the buyer", false),
+            required("supplier", "Name of the supplier", false),
+            required("goods", "Goods supplied, e.g. office furniture", false),
+            required("delivery_place", "Where the goods are delivered", false),
+            optional("delivery_days", "Days to deliver after each purchase order", "14", true).at_least(1),
+            optional("defect_days", "Days after delivery to report defective goods, at least 7", "30", true).at_least(7),
+            optional("payment_days", "Days the buyer has to pay an invoice, at most 60", "30", true).at_most(60),
+            optional("notice_days", "Days of written notice to end the agreement, at least 14", "60", true).at_least(14),
+        ],
+        clauses: &[
+            "supply_delivery",
+            "supply_quality",
+            "supply_payment",
+            "mutual_confidentiality",
+            "force_majeure",
+            "supply_termination",
+            "amendments",
+            "dispute_resolution",
+            "governing_law",
+        ],
+    },
+    ContractTemplate {
+        id: "non_disclosure_agreement",
+        title: "Non-Disclosure Agreement",
+        contract_type: ContractType::NonDisclosure,
+        preamble: "This agreement is made between {{disclosing_party}} (the \"Disclosing Party\") and {{receiving_party}} (the \"Receiving Party\").",
+        parameters: &[
+            required("disclosing_party", "Name of the party sharing information", false),
+            required("receiving_party", "Name of the party receiving information", false),
+            required("purpose", "Why the information is shared, e.g. evaluating a partnership", false),
+            optional("confidentiality_years", "Years the information stays confidential, 1 to 10", "3", true).at_least(1).at_most(10),
+        ],
+        clauses: &[
+            "nda_definition",
+            "nda_exclusions",
+            "nda_use",
+            "nda_duration",
+            "nda_return",
+            "amendments",
+            "dispute_resolution",
+            "governing_law",
+        ],
+    },
+];
+
+pub fn library_clause(id: &str) -> Option<&'static LibraryClause> {
+    LIBRARY.iter().find(|clause| clause.id == id)
+}
+
+pub fn find_template(id: &str) -> Result<&'static ContractTemplate, String> {
+    TEMPLATES.iter()
+        .find(|template| template.id == id)
+        .ok_or_else(|| format!("Unknown template {}", id))
+}
+
+// Names of the {{placeholders}} in a text, in order of first use
+fn placeholders(text: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else { break };
+        let name = &rest[start + 2..start + end];
+        if !names.iter().any(|known| known == name) {
+            names.push(name.to_string());
+        }
+        rest = &rest[start + end + 2..];
+    }
+    names
+}
+
+fn fill(text: &str, values: &BTreeMap<&str, String>) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..].find("}}")
+            .ok_or_else(|| "Unterminated placeholder in template".to_string())?;
+        let name = &rest[start + 2..start + end];
+        let value = values.get(name)
+            .ok_or_else(|| format!("Template placeholder {} has no parameter", name))?;
+        out.push_str(&rest[..start]);
+        out.push_str(value);
+        rest = &rest[start + end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn validate_value(parameter: &TemplateParameter, value: &str) -> Result<(), String> {
+    if value.is_empty() {
+        return Err(format!("Parameter {} is empty", parameter.name));
+    }
+    if value.chars().count() > MAX_PARAMETER_CHARS {
+        return Err(format!("Parameter {} is longer than {} characters", parameter.name, MAX_PARAMETER_CHARS));
+    }
+    if value.contains(['\n', '\r']) || value.contains("{{") || value.contains("}}") {
+        return Err(format!("Parameter {} must be a single line of plain text", parameter.name));
+    }
+    if parameter.numeric && !value.chars().all(|c| c.is_ascii_digit() || c == ',' || c == '.' || c == ' ') {
+        return Err(format!("Parameter {} must be a number", parameter.name));
+    }
+    if parameter.min.is_some() || parameter.max.is_some() {
+        let number: u64 = value.replace([',', ' '], "").parse()
+            .map_err(|_| format!("Parameter {} must be a whole number", parameter.name))?;
+        if let Some(min) = parameter.min.filter(|&min| number < min) {
+            return Err(format!("Parameter {} must be at least {}", parameter.name, min));
+        }
+        if let Some(max) = parameter.max.filter(|&max| number > max) {
+            return Err(format!("Parameter {} must be at most {}", parameter.name, max));
+        }
+    }
+    Ok(())
+}
+
+// Fill a template's preamble and clauses with the given parameters. Every
+// parameter without a default must be given, and unknown ones are refused.
+// The title is left out; see `with_title`.
+pub fn render_terms(template: &ContractTemplate, params: &[(String, String)]) -> Result<String, String> {
+    let mut values: BTreeMap<&str, String> = BTreeMap::new();
+    for (name, value) in params {
+        let parameter = template.parameters.iter()
+            .find(|parameter| parameter.name == name)
+            .ok_or_else(|| format!("Template {} has no parameter {}", template.id, name))?;
+        let value = value.trim();
+        validate_value(parameter, value)?;
+        if values.insert(parameter.name, value.to_string()).is_some() {
+            return Err(format!("Parameter {} is given more than once", name));
+        }
+    }
+
+    let missing: Vec<&str> = template.parameters.iter()
+        .filter(|parameter| parameter.default.is_none() && !values.contains_key(parameter.name))
+        .map(|parameter| parameter.name)
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("Missing template parameters: {}", missing.join(", ")));
+    }
+    for parameter in template.parameters {
+        if let Some(default) = parameter.default {
+            values.entry(parameter.name).or_insert_with(|| default.to_string());
+        }
+    }
+
+    let mut out = format!("{}\n", fill(template.preamble, &values)?);
+    for (index, clause_id) in template.clauses.iter().enumerate() {
+        let clause = library_clause(clause_id)
+            .ok_or_else(|| format!("Template {} uses unknown clause {}", template.id, clause_id))?;
+        out.push_str(&format!("\n{}. {}\n", index + 1, fill(clause.text, &values)?));
+    }
+    Ok(out)
+}
+
+// The rendered terms under the template's title
+pub fn with_title(template: &ContractTemplate, terms: &str) -> String {
+    format!("{}\n\n{}", template.title.to_uppercase(), terms)
+}
+
+pub fn clause_info(clause: &LibraryClause) -> LibraryClauseInfo {
+    LibraryClauseInfo {
+        id: clause.id.to_string(),
+        title: clause.title.to_string(),
+        category: clause.category,
+        contract_types: clause.contract_types.to_vec(),
+        text: clause.text.to_string(),
+        placeholders: placeholders(clause.text),
+    }
+}
+
+pub fn template_info(template: &ContractTemplate) -> TemplateInfo {
+    TemplateInfo {
+        id: template.id.to_string(),
+        title: template.title.to_string(),
+        contract_type: template.contract_type,
+        parameters: template.parameters.iter()
+            .map(|parameter| TemplateParameterInfo {
+                name: parameter.name.to_string(),
+                description: parameter.description.to_string(),
+                required: parameter.default.is_none(),
+                default: parameter.default.map(str::to_string),
+                min: parameter.min,
+                max: parameter.max,
+            })
+            .collect(),
+        clause_ids: template.clauses.iter().map(|id| id.to_string()).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        contract_type, enhanced_analyze, parse_contract_dataset, split_clauses, too_short_to_assess, unfair_clauses,
+        CONTRACT_DATASET, CSV_DATA,
+    };
+
+    fn sample_value(parameter: &TemplateParameter) -> String {
+        if parameter.numeric {
+            "250,000".to_string()
+        } else if parameter.name.ends_with("date") {
+            "1 March 2025".to_string()
+        } else {
+            format!("Sample {}", parameter.name.replace('_', " "))
+        }
+    }
+
+    // Required parameters get sample values; optional ones keep their defaults
+    // unless `at_bounds`, where bounded ones are set to each end of their range
+    fn sample_params(template: &ContractTemplate, at_bounds: impl Fn(&TemplateParameter) -> Option<u64>) -> Vec<(String, String)> {
+        template.parameters.iter()
+            .filter_map(|parameter| match (parameter.default, at_bounds(parameter)) {
+                (_, Some(bound)) => Some(bound.to_string()),
+                (None, None) => Some(sample_value(parameter)),
+                (Some(_), None) => None,
+            }.map(|value| (parameter.name.to_string(), value)))
+            .collect()
+    }
+
+    fn params(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    // Every template with its default parameters and with each bound
+    fn sample_terms() -> Vec<(&'static ContractTemplate, String)> {
+        TEMPLATES.iter()
+            .flat_map(|template| [
+                sample_params(template, |_| None),
+                sample_params(template, |parameter| parameter.min),
+                sample_params(template, |parameter| parameter.max),
+            ].map(|params| (template, render_terms(template, &params).unwrap())))
+            .collect()
+    }
+
+    #[test]
+    fn generated_contracts_have_no_unfair_clauses() {
+        CONTRACT_DATASET.with(|dataset| *dataset.borrow_mut() = parse_contract_dataset(CSV_DATA).records);
+        for (template, terms) in sample_terms() {
+            let analyses = enhanced_analyze(&terms, contract_type::profile_for(template.contract_type)).unwrap();
+            assert_eq!(unfair_clauses(&analyses), Vec::<String>::new(), "{}", template.id);
+        }
+    }
+
+    // The LLM analyzer labels short segments, such as most titles, "Neutral"
+    // without asking the model, so the analysed terms must not contain any
+    #[test]
+    fn generated_terms_have_no_clause_too_short_to_assess() {
+        for (template, terms) in sample_terms() {
+            for clause in split_clauses(&terms) {
+                assert!(!too_short_to_assess(clause.text.trim()), "{}: {:?}", template.id, clause.text);
+            }
+        }
+    }
+
+    #[test]
+    fn wording_slipped_into_a_parameter_is_not_vetted() {
+        CONTRACT_DATASET.with(|dataset| *dataset.borrow_mut() = parse_contract_dataset(CSV_DATA).records);
+        let template = find_template("service_agreement").unwrap();
+        let text = render_terms(template, &params(&[
+            ("client", "Acme Ltd"),
+            ("contractor", "Kigali Web Studio"),
+            ("services", "any work the Client requests at its sole discretion, with additional fees without notice"),
+            ("fee", "1,000,000"),
+        ])).unwrap();
+        let analyses = enhanced_analyze(&text, contract_type::profile_for(template.contract_type)).unwrap();
+        let scope = analyses.iter().find(|clause| clause.section.as_deref() == Some("1")).unwrap();
+        assert_eq!(unfair_clauses(&analyses), ["1"], "{}", scope.reason);
+    }
+
+    #[test]
+    fn bounded_parameters_are_enforced() {
+        let template = find_template("employment_contract").unwrap();
+        let required = [
+            ("employer", "Acme Ltd"),
+            ("employee", "Jane Doe"),
+            ("job_title", "Accountant"),
+            ("start_date", "1 March 2025"),
+            ("monthly_salary", "500,000"),
+        ];
+        let with = |name: &str, value: &str| {
+            let mut given = params(&required);
+            given.push((name.to_string(), value.to_string()));
+            render_terms(template, &given)
+        };
+        assert!(with("weekly_hours", "45").unwrap().contains("shall not exceed 45 hours"));
+        assert_eq!(with("weekly_hours", "50").unwrap_err(), "Parameter weekly_hours must be at most 45");
+        assert!(with("annual_leave_days", "21").is_ok());
+        assert_eq!(with("annual_leave_days", "10").unwrap_err(), "Parameter annual_leave_days must be at least 18");
+        assert_eq!(with("annual_leave_days", "18.5").unwrap_err(), "Parameter annual_leave_days must be a whole number");
+        assert!(render_terms(template, &params(&required)).is_ok());
+    }
+}
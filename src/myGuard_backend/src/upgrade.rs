@@ -0,0 +1,163 @@
+use candid::Principal;
+use serde::{Deserialize, Serialize};
+
+use crate::cost::CostTotals;
+use crate::dataset::DatasetSnapshot;
+use crate::feedback::Feedback;
+use crate::reminders::Reminders;
+use crate::{
+    ContractRecord, StoredAnalysis, ANALYSES, CALENDAR_FEEDS, CONTRACT_DATASET, COST_LEDGER, CURRENT_DATASET_VERSION,
+    DATASET_VERSIONS, FEEDBACK, LEGAL_REVIEWERS, NEXT_ANALYSIS_ID, NEXT_FEEDBACK_ID, REMINDERS,
+};
+
+// Heap state carried across an upgrade; the audit log already lives in stable
+// memory. The classification cache, circuit breaker and metrics start afresh.
+// Maps are kept as lists because JSON object keys must be strings.
+#[derive(Serialize, Deserialize)]
+pub struct UpgradeState {
+    dataset: Vec<ContractRecord>,
+    dataset_versions: Vec<(u64, DatasetSnapshot)>,
+    current_dataset_version: u64,
+    feedback: Vec<(u64, Feedback)>,
+    next_feedback_id: u64,
+    legal_reviewers: Vec<Principal>,
+    cost_ledger: Vec<((u64, Principal), CostTotals)>,
+    analyses: Vec<(u64, StoredAnalysis)>,
+    next_analysis_id: u64,
+    calendar_feeds: Vec<(String, Principal)>,
+    reminders: Reminders,
+}
+
+// Move the state out of the canister's heap
+pub fn take() -> UpgradeState {
+    UpgradeState {
+        dataset: CONTRACT_DATASET.with(|dataset| dataset.take()),
+        dataset_versions: DATASET_VERSIONS.with(|versions| versions.take().into_iter().collect()),
+        current_dataset_version: CURRENT_DATASET_VERSION.with(|version| version.get()),
+        feedback: FEEDBACK.with(|feedback| feedback.take().into_iter().collect()),
+        next_feedback_id: NEXT_FEEDBACK_ID.with(|id| id.get()),
+        legal_reviewers: LEGAL_REVIEWERS.with(|reviewers| reviewers.take().into_iter().collect()),
+        cost_ledger: COST_LEDGER.with(|ledger| ledger.take().into_iter().collect()),
+        analyses: ANALYSES.with(|analyses| analyses.take().into_iter().collect()),
+        next_analysis_id: NEXT_ANALYSIS_ID.with(|id| id.get()),
+        calendar_feeds: CALENDAR_FEEDS.with(|feeds| feeds.take().into_iter().collect()),
+        reminders: REMINDERS.with(|reminders| reminders.replace(Reminders::new())),
+    }
+}
+
+pub fn restore(state: UpgradeState) {
+    CONTRACT_DATASET.with(|dataset| *dataset.borrow_mut() = state.dataset);
+    DATASET_VERSIONS.with(|versions| *versions.borrow_mut() = state.dataset_versions.into_iter().collect());
+    CURRENT_DATASET_VERSION.with(|version| version.set(state.current_dataset_version));
+    FEEDBACK.with(|feedback| *feedback.borrow_mut() = state.feedback.into_iter().collect());
+    NEXT_FEEDBACK_ID.with(|id| id.set(state.next_feedback_id));
+    LEGAL_REVIEWERS.with(|reviewers| *reviewers.borrow_mut() = state.legal_reviewers.into_iter().collect());
+    COST_LEDGER.with(|ledger| *ledger.borrow_mut() = state.cost_ledger.into_iter().collect());
+    ANALYSES.with(|analyses| *analyses.borrow_mut() = state.analyses.into_iter().collect());
+    NEXT_ANALYSIS_ID.with(|id| id.set(state.next_analysis_id));
+    CALENDAR_FEEDS.with(|feeds| *feeds.borrow_mut() = state.calendar_feeds.into_iter().collect());
+    REMINDERS.with(|reminders| *reminders.borrow_mut() = state.reminders);
+}
+
+pub fn encode(state: &UpgradeState) -> Vec<u8> {
+    serde_json::to_vec(state).expect("failed to encode upgrade state")
+}
+
+pub fn decode(bytes: &[u8]) -> UpgradeState {
+    serde_json::from_slice(bytes).expect("failed to decode upgrade state")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract_type::{self, ContractType};
+    use crate::dataset::DatasetVersion;
+    use crate::feedback::FeedbackStatus;
+    use crate::{build_analysis_result, parse_contract_dataset, CSV_DATA};
+
+    #[test]
+    fn state_survives_a_round_trip() {
+        let admin = Principal::from_slice(&[1; 29]);
+        let user = Principal::from_slice(&[2; 29]);
+        let mut records = parse_contract_dataset(CSV_DATA).records;
+        records.truncate(10);
+        let version = |version: u64, records: &[ContractRecord]| DatasetSnapshot {
+            info: DatasetVersion {
+                version,
+                author: admin,
+                created_at: version * 1_000,
+                description: format!("Version {}", version),
+                record_count: records.len(),
+            },
+            records: records.to_vec(),
+        };
+        CONTRACT_DATASET.with(|dataset| *dataset.borrow_mut() = records.clone());
+        DATASET_VERSIONS.with(|versions| {
+            *versions.borrow_mut() = [(1, version(1, &records[..9])), (2, version(2, &records))].into();
+        });
+        CURRENT_DATASET_VERSION.with(|current| current.set(2));
+        FEEDBACK.with(|feedback| {
+            feedback.borrow_mut().insert(4, Feedback {
+                feedback_id: 4,
+                analysis_id: 7,
+                clause_index: 0,
+                clause: records[0].contract_text.clone(),
+                contract_type: ContractType::Employment,
+                category: records[0].effective_category(),
+                original_label: "Not Allowed".to_string(),
+                correct_label: "Allowed".to_string(),
+                comment: "Fair".to_string(),
+                submitted_by: user,
+                submitted_at: 1,
+                status: FeedbackStatus::Approved,
+                reviewed_by: Some(admin),
+                reviewed_at: Some(2),
+                review_note: None,
+            });
+        });
+        NEXT_FEEDBACK_ID.with(|id| id.set(5));
+        LEGAL_REVIEWERS.with(|reviewers| reviewers.borrow_mut().insert(admin));
+        COST_LEDGER.with(|ledger| {
+            ledger.borrow_mut().insert((20_000, user), CostTotals { analyses: 3, estimated_cycles: u128::MAX, ..Default::default() });
+        });
+        let mut result = build_analysis_result(Vec::new(), ContractType::Lease, contract_type::detect_contract_type(""));
+        result.analysis_id = 7;
+        result.dataset_version = 2;
+        ANALYSES.with(|analyses| {
+            analyses.borrow_mut().insert(7, StoredAnalysis { owner: user, result, access_token: Some("secret".to_string()) });
+        });
+        NEXT_ANALYSIS_ID.with(|id| id.set(8));
+        CALENDAR_FEEDS.with(|feeds| feeds.borrow_mut().insert("token".to_string(), user));
+        REMINDERS.with(|reminders| reminders.borrow_mut().set_lead_days(user, 14).unwrap());
+
+        let bytes = encode(&take());
+        assert_eq!(CONTRACT_DATASET.with(|dataset| dataset.borrow().len()), 0);
+        assert_eq!(ANALYSES.with(|analyses| analyses.borrow().len()), 0);
+        restore(decode(&bytes));
+
+        CONTRACT_DATASET.with(|dataset| {
+            let texts = |records: &[ContractRecord]| records.iter().map(|record| record.contract_text.clone()).collect::<Vec<_>>();
+            assert_eq!(texts(&dataset.borrow()), texts(&records));
+        });
+        DATASET_VERSIONS.with(|versions| {
+            let versions = versions.borrow();
+            assert_eq!(versions.keys().copied().collect::<Vec<_>>(), [1, 2]);
+            assert_eq!(versions[&1].records.len(), 9);
+            assert_eq!(versions[&2].info.description, "Version 2");
+        });
+        assert_eq!(CURRENT_DATASET_VERSION.with(|current| current.get()), 2);
+        FEEDBACK.with(|feedback| assert_eq!(feedback.borrow()[&4].status, FeedbackStatus::Approved));
+        assert_eq!(NEXT_FEEDBACK_ID.with(|id| id.get()), 5);
+        assert!(LEGAL_REVIEWERS.with(|reviewers| reviewers.borrow().contains(&admin)));
+        COST_LEDGER.with(|ledger| assert_eq!(ledger.borrow()[&(20_000, user)].estimated_cycles, u128::MAX));
+        ANALYSES.with(|analyses| {
+            let stored = &analyses.borrow()[&7];
+            assert_eq!(stored.owner, user);
+            assert_eq!(stored.result.dataset_version, 2);
+            assert_eq!(stored.access_token.as_deref(), Some("secret"));
+        });
+        assert_eq!(NEXT_ANALYSIS_ID.with(|id| id.get()), 8);
+        assert_eq!(CALENDAR_FEEDS.with(|feeds| feeds.borrow().get("token").copied()), Some(user));
+        assert_eq!(REMINDERS.with(|reminders| reminders.borrow().lead_days(&user)), 14);
+    }
+}
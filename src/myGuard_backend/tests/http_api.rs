@@ -0,0 +1,157 @@
+// End-to-end tests of the REST API against the canister running in PocketIC.
+//
+// They need the PocketIC server and the release wasm, so they are ignored by
+// default. To run them:
+//
+//   cargo build --target wasm32-unknown-unknown --release -p myGuard_backend
+//   POCKET_IC_BIN=/path/to/pocket-ic cargo test -p myGuard_backend --test http_api -- --ignored
+//
+// Set MYGUARD_BACKEND_WASM to test a wasm built elsewhere.
+
+use std::path::PathBuf;
+
+use candid::{encode_args, CandidType, Deserialize, Principal};
+use pocket_ic::{query_candid_as, update_candid_as, PocketIc};
+use serde_json::Value;
+
+const CONTRACT: &str = "1. The tenant shall pay the rent by the 5th day of each month.\n\
+2. The landlord may enter the premises at any time without notice.";
+
+#[derive(CandidType, Deserialize)]
+struct HttpRequest {
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    #[serde(with = "serde_bytes")]
+    body: Vec<u8>,
+    certificate_version: Option<u16>,
+}
+
+#[derive(CandidType, Deserialize)]
+struct HttpResponse {
+    status_code: u16,
+    headers: Vec<(String, String)>,
+    #[serde(with = "serde_bytes")]
+    body: Vec<u8>,
+    upgrade: Option<bool>,
+}
+
+// Only the fields the tests look at; Candid ignores the rest of the record
+#[derive(CandidType, Deserialize)]
+struct AnalysisSummary {
+    analysis_id: u64,
+}
+
+struct Canister {
+    pic: PocketIc,
+    id: Principal,
+    controller: Principal,
+}
+
+fn wasm() -> Vec<u8> {
+    let path = std::env::var_os("MYGUARD_BACKEND_WASM").map(PathBuf::from).unwrap_or_else(|| {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../../target/wasm32-unknown-unknown/release/myGuard_backend.wasm")
+    });
+    std::fs::read(&path).unwrap_or_else(|error| panic!("Could not read {}: {}", path.display(), error))
+}
+
+fn install() -> Canister {
+    let pic = PocketIc::new();
+    // The anonymous principal must not control the canister, or it could read everything
+    let controller = Principal::from_slice(&[7; 29]);
+    let id = pic.create_canister_with_settings(Some(controller), None);
+    pic.add_cycles(id, 2_000_000_000_000);
+    pic.install_canister(id, wasm(), encode_args(()).unwrap(), Some(controller));
+    Canister { pic, id, controller }
+}
+
+impl Canister {
+    // Send a request the way the HTTP gateway does: as a query first, and again
+    // as an update call when the canister asks for an upgrade
+    fn http(&self, method: &str, url: &str, body: &str) -> (u16, Value) {
+        let request = || HttpRequest {
+            method: method.to_string(),
+            url: url.to_string(),
+            headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+            body: body.as_bytes().to_vec(),
+            certificate_version: Some(2),
+        };
+        let (mut response,): (HttpResponse,) =
+            query_candid_as(&self.pic, self.id, Principal::anonymous(), "http_request", (request(),)).unwrap();
+        if response.upgrade == Some(true) {
+            (response,) =
+                update_candid_as(&self.pic, self.id, Principal::anonymous(), "http_request_update", (request(),)).unwrap();
+        }
+        assert!(response.headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("content-type")));
+        let json = serde_json::from_slice(&response.body).unwrap_or(Value::Null);
+        (response.status_code, json)
+    }
+
+    fn get_analysis(&self, caller: Principal, analysis_id: u64) -> Result<AnalysisSummary, String> {
+        let (result,): (Result<AnalysisSummary, String>,) =
+            query_candid_as(&self.pic, self.id, caller, "get_analysis", (analysis_id,)).unwrap();
+        result
+    }
+}
+
+fn analyze_over_http(canister: &Canister) -> (u64, String) {
+    let body = serde_json::json!({ "contract_text": CONTRACT, "contract_type": "Lease" }).to_string();
+    let (status, json) = canister.http("POST", "/analyze", &body);
+    assert_eq!(status, 200, "{}", json);
+    assert!(json["clause_breakdown"].as_array().is_some_and(|clauses| !clauses.is_empty()), "{}", json);
+    let analysis_id = json["analysis_id"].as_u64().expect("analysis id");
+    let token = json["access_token"].as_str().expect("access token").to_string();
+    (analysis_id, token)
+}
+
+#[test]
+#[ignore = "needs the PocketIC server and the canister wasm"]
+fn analyses_posted_over_http_are_read_back_with_their_token() {
+    let canister = install();
+    let (analysis_id, token) = analyze_over_http(&canister);
+    assert_eq!(token.len(), 32);
+
+    let (status, json) = canister.http("GET", &format!("/analyses/{}?token={}", analysis_id, token), "");
+    assert_eq!(status, 200, "{}", json);
+    assert_eq!(json["analysis_id"].as_u64(), Some(analysis_id));
+    assert!(json.get("access_token").is_none());
+
+    let (status, _) = canister.http("GET", &format!("/analyses/{}", analysis_id), "");
+    assert_eq!(status, 404);
+    let (status, _) = canister.http("GET", &format!("/analyses/{}?token={}", analysis_id, "0".repeat(32)), "");
+    assert_eq!(status, 404);
+    let (status, _) = canister.http("GET", &format!("/analyses/{}?token={}", analysis_id + 1, token), "");
+    assert_eq!(status, 404);
+
+    let (status, json) = canister.http("GET", "/health", "");
+    assert_eq!(status, 200, "{}", json);
+    let (status, _) = canister.http("GET", "/analyses/not-a-number", "");
+    assert_eq!(status, 404);
+    // Gateway requests are anonymous, and metrics are for controllers only
+    let (status, _) = canister.http("GET", "/metrics", "");
+    assert_eq!(status, 403);
+}
+
+#[test]
+#[ignore = "needs the PocketIC server and the canister wasm"]
+fn anonymous_analyses_are_not_readable_by_id() {
+    let canister = install();
+    let (analysis_id, _) = analyze_over_http(&canister);
+
+    // Every caller without a signature is the anonymous principal, so owning
+    // the analysis does not grant access to it
+    assert!(canister.get_analysis(Principal::anonymous(), analysis_id).is_err());
+    assert_eq!(canister.get_analysis(canister.controller, analysis_id).unwrap().analysis_id, analysis_id);
+
+    let user = Principal::from_slice(&[42; 29]);
+    let (owned,): (AnalysisSummary,) =
+        update_candid_as(&canister.pic, canister.id, user, "analyze_contract", (CONTRACT.to_string(),)).unwrap();
+    assert_eq!(canister.get_analysis(user, owned.analysis_id).unwrap().analysis_id, owned.analysis_id);
+    assert!(canister.get_analysis(Principal::anonymous(), owned.analysis_id).is_err());
+    assert!(canister.get_analysis(Principal::from_slice(&[43; 29]), owned.analysis_id).is_err());
+
+    // Tokens are only handed out to HTTP clients
+    let (status, _) = canister.http("GET", &format!("/analyses/{}?token=", owned.analysis_id), "");
+    assert_eq!(status, 404);
+}